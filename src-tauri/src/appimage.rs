@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Byte offset of the filesystem image appended to the runtime, i.e. where the ELF ends.
+///
+/// Type 2 AppImages are an ELF runtime followed by a squashfs; the ELF's section header
+/// table is its last part, so that's where the squashfs begins.
+pub fn squashfs_offset(path: &Path) -> io::Result<u64> {
+    let header = read_header(path)?;
+    let little = match header[5] {
+        1 => true,
+        2 => false,
+        _ => return Err(invalid("Unknown ELF byte order")),
+    };
+    let u16_at = |o: usize| {
+        let b = [header[o], header[o + 1]];
+        if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+    };
+    let u32_at = |o: usize| {
+        let b: [u8; 4] = header[o..o + 4].try_into().unwrap();
+        if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }
+    };
+    let u64_at = |o: usize| {
+        let b: [u8; 8] = header[o..o + 8].try_into().unwrap();
+        if little { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) }
+    };
+    let (sh_off, sh_entsize, sh_num) = match header[4] {
+        1 => (u32_at(0x20) as u64, u16_at(0x2E), u16_at(0x30)),
+        2 => (u64_at(0x28), u16_at(0x3A), u16_at(0x3C)),
+        _ => return Err(invalid("Unknown ELF class")),
+    };
+    Ok(sh_off + sh_entsize as u64 * sh_num as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppImageType {
+    /// ISO 9660 image with an embedded runtime (legacy).
+    Type1,
+    /// ELF runtime followed by a squashfs.
+    Type2,
+    /// An ELF without AppImage magic; may still run but can't be introspected reliably.
+    Unknown,
+}
+
+/// Reads the ELF header, following symlinks to the real file.
+fn read_header(path: &Path) -> io::Result<[u8; 64]> {
+    let mut head = Vec::with_capacity(64);
+    File::open(path)?.take(64).read_to_end(&mut head)?;
+    // Wrapper scripts are a common "AppImage" download and would break extraction and launch
+    if head.starts_with(b"#!") {
+        return Err(invalid("File is a script, not an AppImage"));
+    }
+    if !head.starts_with(b"\x7fELF") {
+        return Err(invalid("Not an AppImage (missing ELF header)"));
+    }
+    head.try_into().map_err(|_| invalid("Truncated ELF header"))
+}
+
+/// Reads the `AI\x01`/`AI\x02` magic the AppImage spec places at offset 8. An ELF
+/// without it but with a squashfs appended, as some custom runtimes build, is type 2 too.
+pub fn detect_type(path: &Path) -> io::Result<AppImageType> {
+    let header = read_header(path)?;
+    Ok(match &header[8..11] {
+        b"AI\x01" => AppImageType::Type1,
+        b"AI\x02" => AppImageType::Type2,
+        _ if payload(path).is_ok_and(|p| p == Payload::Squashfs) => AppImageType::Type2,
+        _ => AppImageType::Unknown,
+    })
+}
+
+/// Largest `.upd_info` section read; the spec reserves 512 bytes for it.
+const MAX_UPDATE_INFO: u64 = 4096;
+
+/// The update information string the spec embeds in the runtime's `.upd_info` ELF
+/// section, like `gh-releases-zsync|owner|repo|latest|App-*x86_64.AppImage.zsync`.
+pub fn update_info(path: &Path) -> io::Result<Option<String>> {
+    let header = read_header(path)?;
+    let little = header[5] == 1;
+    let u16_at = |b: &[u8], o: usize| {
+        let b = [b[o], b[o + 1]];
+        if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+    };
+    let u32_at = |b: &[u8], o: usize| {
+        let b: [u8; 4] = b[o..o + 4].try_into().unwrap();
+        if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }
+    };
+    let u64_at = |b: &[u8], o: usize| {
+        let b: [u8; 8] = b[o..o + 8].try_into().unwrap();
+        if little { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) }
+    };
+    let elf64 = match header[4] {
+        1 => false,
+        2 => true,
+        _ => return Err(invalid("Unknown ELF class")),
+    };
+    let (sh_off, sh_entsize, sh_num, sh_strndx) = if elf64 {
+        (u64_at(&header, 0x28), u16_at(&header, 0x3A), u16_at(&header, 0x3C), u16_at(&header, 0x3E))
+    } else {
+        (u32_at(&header, 0x20) as u64, u16_at(&header, 0x2E), u16_at(&header, 0x30), u16_at(&header, 0x32))
+    };
+    let min_entsize = if elf64 { 0x28 } else { 0x18 };
+    if sh_entsize < min_entsize {
+        return Ok(None);
+    }
+    let mut file = File::open(path)?;
+    let mut read_at = |offset: u64, len: u64| -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(offset))?;
+        (&mut file).take(len).read_to_end(&mut data)?;
+        Ok(data)
+    };
+    let table = read_at(sh_off, sh_entsize as u64 * sh_num as u64)?;
+    let sections: Vec<&[u8]> = table.chunks_exact(sh_entsize as usize).collect();
+    // (name offset, file offset, size) of a section header
+    let section = |s: &[u8]| if elf64 { (u32_at(s, 0), u64_at(s, 0x18), u64_at(s, 0x20)) } else { (u32_at(s, 0), u32_at(s, 0x10) as u64, u32_at(s, 0x14) as u64) };
+    let Some(strtab) = sections.get(sh_strndx as usize) else { return Ok(None) };
+    let (_, names_off, names_len) = section(strtab);
+    let names = read_at(names_off, names_len.min(1 << 16))?;
+    let is_upd_info = |name_off: u32| names.get(name_off as usize..).is_some_and(|n| n.starts_with(b".upd_info\0"));
+    let Some((_, off, len)) = sections.iter().map(|s| section(s)).find(|(name, _, _)| is_upd_info(*name)) else { return Ok(None) };
+    let data = read_at(off, len.min(MAX_UPDATE_INFO))?;
+    let info = String::from_utf8_lossy(&data).trim_end_matches('\0').trim().to_string();
+    Ok((!info.is_empty()).then_some(info))
+}
+
+/// The filesystem image the runtime mounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Payload {
+    Squashfs,
+    /// Used by some newer runtimes; they can run and extract it, but Axec can't read it.
+    Dwarfs,
+    /// Type 1's ISO 9660 image, which wraps the whole file.
+    Iso9660,
+    /// Nothing recognisable after the ELF.
+    None,
+}
+
+/// Identifies the image after the runtime by its magic bytes.
+pub fn payload(path: &Path) -> io::Result<Payload> {
+    let mut file = File::open(path)?;
+    let mut iso_magic = [0u8; 5];
+    // ISO 9660's primary volume descriptor sits at sector 16
+    if file.seek(SeekFrom::Start(0x8001)).is_ok() && file.read_exact(&mut iso_magic).is_ok() && &iso_magic == b"CD001" {
+        return Ok(Payload::Iso9660);
+    }
+    file.seek(SeekFrom::Start(squashfs_offset(path)?))?;
+    let mut magic = Vec::with_capacity(6);
+    (&mut file).take(6).read_to_end(&mut magic)?;
+    Ok(if magic.starts_with(b"hsqs") {
+        Payload::Squashfs
+    } else if magic.starts_with(b"DWARFS") {
+        Payload::Dwarfs
+    } else {
+        Payload::None
+    })
+}
+
+/// CPU architecture of the runtime, from the ELF `e_machine` field.
+pub fn elf_arch(path: &Path) -> io::Result<Option<&'static str>> {
+    let header = read_header(path)?;
+    let b = [header[0x12], header[0x13]];
+    let machine = if header[5] == 2 { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) };
+    Ok(match machine {
+        0x03 => Some("i686"),
+        0x28 => Some("armhf"),
+        0x3E => Some("x86_64"),
+        0xB7 => Some("aarch64"),
+        0xF3 => Some("riscv64"),
+        _ => None,
+    })
+}
+
+/// Most of the file `runtime_info` scans; real runtimes are well under 1 MiB.
+const MAX_RUNTIME_SCAN: u64 = 4 << 20;
+/// Strings runtimes put in front of their version or build commit.
+const RUNTIME_VERSION_MARKERS: [&[u8]; 3] = [b"AppImage runtime version ", b"runtime version ", b"Version: "];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeKind {
+    /// The classic AppImageKit runtime, dynamically linked against `libfuse.so.2`.
+    Libfuse2,
+    /// The static type2-runtime, which mounts with FUSE 2 or 3.
+    Static,
+    Unknown,
+}
+
+/// The ELF runtime in front of the squashfs, as far as its bytes tell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeInfo {
+    pub kind: RuntimeKind,
+    /// Version or build commit the runtime names itself with, when it does.
+    pub version: Option<String>,
+}
+
+/// Whether the ELF has a `PT_INTERP` program header, i.e. is dynamically linked.
+fn has_interpreter(path: &Path, header: &[u8; 64]) -> io::Result<bool> {
+    let little = header[5] == 1;
+    let u16_at = |b: &[u8], o: usize| {
+        let b = [b[o], b[o + 1]];
+        if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) }
+    };
+    let u32_at = |b: &[u8], o: usize| {
+        let b: [u8; 4] = b[o..o + 4].try_into().unwrap();
+        if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) }
+    };
+    let u64_at = |b: &[u8], o: usize| {
+        let b: [u8; 8] = b[o..o + 8].try_into().unwrap();
+        if little { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) }
+    };
+    let (ph_off, ph_entsize, ph_num) = match header[4] {
+        1 => (u32_at(header, 0x1C) as u64, u16_at(header, 0x2A), u16_at(header, 0x2C)),
+        2 => (u64_at(header, 0x20), u16_at(header, 0x36), u16_at(header, 0x38)),
+        _ => return Err(invalid("Unknown ELF class")),
+    };
+    let mut table = Vec::new();
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(ph_off))?;
+    file.take(ph_entsize as u64 * ph_num as u64).read_to_end(&mut table)?;
+    const PT_INTERP: u32 = 3;
+    Ok(table.chunks_exact(ph_entsize.max(4) as usize).any(|entry| u32_at(entry, 0) == PT_INTERP))
+}
+
+/// Printable text right after the first version marker in `data`.
+fn runtime_version(data: &[u8]) -> Option<String> {
+    RUNTIME_VERSION_MARKERS.iter().find_map(|marker| {
+        let start = data.windows(marker.len()).position(|w| w == *marker)? + marker.len();
+        let text: String = data[start..].iter().take(64).take_while(|b| b.is_ascii_graphic()).map(|&b| b as char).collect();
+        // A printf format rather than a baked-in version
+        (!text.is_empty() && !text.contains('%')).then_some(text)
+    })
+}
+
+/// Identifies the runtime: which FUSE it needs and, when it embeds one, its version.
+pub fn runtime_info(path: &Path) -> io::Result<RuntimeInfo> {
+    let header = read_header(path)?;
+    let len = squashfs_offset(path).unwrap_or(MAX_RUNTIME_SCAN).min(MAX_RUNTIME_SCAN);
+    let mut data = Vec::new();
+    File::open(path)?.take(len).read_to_end(&mut data)?;
+    let mentions_libfuse2 = data.windows(12).any(|w| w == b"libfuse.so.2");
+    let kind = if header[8..11] == *b"AI\x01" || mentions_libfuse2 && has_interpreter(path, &header)? {
+        RuntimeKind::Libfuse2
+    } else if !has_interpreter(path, &header)? {
+        RuntimeKind::Static
+    } else {
+        RuntimeKind::Unknown
+    };
+    Ok(RuntimeInfo { kind, version: runtime_version(&data) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn shell_scripts_are_not_appimages() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("Installer.AppImage");
+        fs::write(&script, "#!/bin/sh\nexec ./real.AppImage \"$@\"\n").unwrap();
+        let err = detect_type(&script).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "File is a script, not an AppImage");
+        // A link is judged by its target
+        let link = dir.path().join("Link.AppImage");
+        std::os::unix::fs::symlink(&script, &link).unwrap();
+        assert_eq!(detect_type(&link).unwrap_err().to_string(), "File is a script, not an AppImage");
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fs, path::Path};
+
+/// The parts of an AppStream metainfo file Axec uses.
+#[derive(Debug, Clone, Default)]
+pub struct AppStream {
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    /// Version of the newest `<release>`.
+    pub version: Option<String>,
+    /// Screenshot image URLs, the default screenshot first.
+    pub screenshots: Vec<String>,
+    /// SPDX expression from `<project_license>`.
+    pub license: Option<String>,
+    /// Freedesktop categories from `<categories>`.
+    pub categories: Vec<String>,
+    /// MIME types from `<provides><mediatype>`.
+    pub mime_types: Vec<String>,
+}
+
+pub fn parse(xml: &str) -> Option<AppStream> {
+    let doc = roxmltree::Document::parse(xml).ok()?;
+    let root = doc.root_element();
+    // Untranslated values only; `xml:lang` variants are skipped
+    let text_of = |tag: &str| {
+        root.children()
+            .find(|n| n.has_tag_name(tag) && n.attributes().all(|a| a.name() != "lang"))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+    let version = root
+        .children()
+        .find(|n| n.has_tag_name("releases"))
+        .and_then(|r| r.children().find(|n| n.has_tag_name("release")))
+        .and_then(|n| n.attribute("version"))
+        .map(String::from);
+    let mut shots: Vec<roxmltree::Node> = root
+        .children()
+        .find(|n| n.has_tag_name("screenshots"))
+        .map(|s| s.children().filter(|n| n.has_tag_name("screenshot")).collect())
+        .unwrap_or_default();
+    // Stable sort keeps the file order otherwise
+    shots.sort_by_key(|n| n.attribute("type") != Some("default"));
+    let screenshots = shots
+        .iter()
+        .filter_map(|shot| {
+            let images: Vec<_> = shot.children().filter(|n| n.has_tag_name("image")).collect();
+            // The full-size original beats the pre-scaled thumbnails
+            let image = images.iter().find(|n| n.attribute("type") == Some("source")).or_else(|| images.first())?;
+            image.text().map(|t| t.trim().to_string()).filter(|t| t.starts_with("https://") || t.starts_with("http://"))
+        })
+        .collect();
+    let texts_in = |parent: &str, tag: &str| -> Vec<String> {
+        root.children()
+            .filter(|n| n.has_tag_name(parent))
+            .flat_map(|p| p.children().filter(|n| n.has_tag_name(tag)))
+            .filter_map(|n| n.text().map(|t| t.trim().to_string()))
+            .filter(|t| !t.is_empty())
+            .collect()
+    };
+    Some(AppStream {
+        name: text_of("name"),
+        summary: text_of("summary"),
+        version,
+        screenshots,
+        license: text_of("project_license"),
+        categories: texts_in("categories", "category"),
+        mime_types: crate::desktop::valid_mime_types(texts_in("provides", "mediatype")),
+    })
+}
+
+/// Reads the first metainfo file under `usr/share/metainfo` (or the legacy `appdata` dir).
+pub fn read_embedded(squash_root: &Path) -> Option<AppStream> {
+    for sub in ["usr/share/metainfo", "usr/share/appdata"] {
+        let Ok(rd) = fs::read_dir(squash_root.join(sub)) else { continue };
+        let mut files: Vec<_> = rd.flatten().map(|e| e.path()).filter(|p| p.extension().and_then(|s| s.to_str()) == Some("xml")).collect();
+        files.sort();
+        if let Some(parsed) = files.iter().find_map(|p| fs::read_to_string(p).ok().and_then(|s| parse(&s))) {
+            return Some(parsed);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mediatypes_that_would_add_entry_lines_are_dropped() {
+        let xml = "<component><provides><mediatype>text/plain</mediatype><mediatype>image/png\nExec=/bin/evil</mediatype><mediatype>a;b/c</mediatype></provides></component>";
+        assert_eq!(parse(xml).unwrap().mime_types, ["text/plain"]);
+    }
+}
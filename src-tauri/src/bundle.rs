@@ -0,0 +1,242 @@
+//! Single-app bundles for sharing or archiving: a plain ustar archive holding the
+//! AppImage, its icon and a `manifest.json`.
+//!
+//! AppImages are compressed already, so the archive isn't; the format is simple enough
+//! that any `tar` can unpack it too.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST: &str = "manifest.json";
+pub const APP_FILE: &str = "app.AppImage";
+/// Bumped when a bundle stops being readable by older versions.
+pub const FORMAT_VERSION: u32 = 1;
+
+const BLOCK: usize = 512;
+/// Largest size the 11-digit octal field holds.
+const MAX_ENTRY_SIZE: u64 = 0o77777777777;
+/// Manifests are a few hundred bytes; anything much larger isn't one.
+const MAX_MANIFEST: u64 = 1 << 20;
+
+/// What a bundle says about the app it carries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format: u32,
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub version: Option<String>,
+    pub app_version: Option<String>,
+    /// Where updates come from: a URL or a GitHub `owner/repo`.
+    pub source: Option<String>,
+    /// SHA-256 of `app.AppImage`, checked on import.
+    pub sha256: String,
+    /// File name of the icon in the bundle, like `icon.png`.
+    pub icon: Option<String>,
+}
+
+fn header(name: &str, size: u64, mode: u32) -> io::Result<[u8; BLOCK]> {
+    if name.len() > 100 || size > MAX_ENTRY_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{name} is too large for a bundle")));
+    }
+    let mut h = [0u8; BLOCK];
+    h[..name.len()].copy_from_slice(name.as_bytes());
+    h[100..107].copy_from_slice(format!("{mode:07o}").as_bytes());
+    h[108..115].copy_from_slice(b"0000000");
+    h[116..123].copy_from_slice(b"0000000");
+    h[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+    h[136..147].copy_from_slice(b"00000000000");
+    h[156] = b'0';
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+    // The checksum is taken with its own field read as spaces
+    h[148..156].fill(b' ');
+    let sum: u32 = h.iter().map(|&b| u32::from(b)).sum();
+    h[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+    Ok(h)
+}
+
+fn write_entry(out: &mut impl Write, name: &str, size: u64, mode: u32, data: &mut impl Read) -> io::Result<()> {
+    out.write_all(&header(name, size, mode)?)?;
+    let copied = io::copy(&mut data.take(size), out)?;
+    if copied != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{name} changed while it was being bundled")));
+    }
+    let pad = (BLOCK - (size as usize % BLOCK)) % BLOCK;
+    out.write_all(&vec![0u8; pad])
+}
+
+/// Writes a bundle at `dest` from the manifest, the AppImage and an optional icon.
+pub fn write(dest: &Path, manifest: &BundleManifest, app_path: &Path, icon: Option<&Path>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    let json = serde_json::to_vec_pretty(manifest).map_err(io::Error::other)?;
+    write_entry(&mut out, MANIFEST, json.len() as u64, 0o644, &mut json.as_slice())?;
+    let app = File::open(app_path)?;
+    let size = app.metadata()?.len();
+    write_entry(&mut out, APP_FILE, size, 0o755, &mut BufReader::new(app))?;
+    if let (Some(icon), Some(name)) = (icon, &manifest.icon) {
+        let file = File::open(icon)?;
+        let size = file.metadata()?.len();
+        write_entry(&mut out, name, size, 0o644, &mut BufReader::new(file))?;
+    }
+    // End of archive: two zero blocks
+    out.write_all(&[0u8; BLOCK * 2])?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// Whether `name` is a plain file name a bundle may contain.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && name != "." && name != ".."
+}
+
+/// Unpacks the bundle at `path` into `dest_dir` and returns its manifest. Only plain
+/// files directly in the archive are taken; anything else is skipped. A bundle whose
+/// AppImage doesn't match the manifest's hash is refused.
+pub fn read(path: &Path, dest_dir: &Path) -> io::Result<BundleManifest> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Not an Axec bundle: {message}"));
+    let mut input = BufReader::new(File::open(path)?);
+    let mut manifest = None;
+    loop {
+        let mut h = [0u8; BLOCK];
+        input.read_exact(&mut h).map_err(|_| invalid("the archive ends early"))?;
+        if h.iter().all(|&b| b == 0) {
+            break;
+        }
+        let name_len = h[..100].iter().position(|&b| b == 0).unwrap_or(100);
+        let name = String::from_utf8_lossy(&h[..name_len]).to_string();
+        let size = parse_octal(&h[124..136]).ok_or_else(|| invalid("a size field is corrupt"))?;
+        let regular = h[156] == b'0' || h[156] == 0;
+        let mut data = (&mut input).take(size);
+        if name == MANIFEST && regular {
+            if size > MAX_MANIFEST {
+                return Err(invalid("the manifest is too large"));
+            }
+            let mut json = Vec::new();
+            data.read_to_end(&mut json)?;
+            manifest = Some(serde_json::from_slice::<BundleManifest>(&json).map_err(|e| invalid(&e.to_string()))?);
+        } else if regular && is_plain_name(&name) {
+            io::copy(&mut data, &mut File::create(dest_dir.join(&name))?)?;
+        } else {
+            io::copy(&mut data, &mut io::sink())?;
+        }
+        let pad = (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64;
+        io::copy(&mut (&mut input).take(pad), &mut io::sink())?;
+    }
+    let manifest = manifest.ok_or_else(|| invalid("it has no manifest.json"))?;
+    if manifest.format > FORMAT_VERSION {
+        return Err(invalid(&format!("format {} needs a newer Axec", manifest.format)));
+    }
+    let app = dest_dir.join(APP_FILE);
+    if !app.is_file() {
+        return Err(invalid("it has no AppImage"));
+    }
+    if crate::storage::sha256_file(&app)? != manifest.sha256 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The bundled AppImage doesn't match the hash in its manifest; the bundle is damaged"));
+    }
+    Ok(manifest)
+}
+
+/// The icon the manifest names, if `read` unpacked it into `dir`.
+pub fn icon_path(dir: &Path, manifest: &BundleManifest) -> Option<PathBuf> {
+    let name = manifest.icon.as_deref().filter(|n| is_plain_name(n))?;
+    let path = dir.join(name);
+    fs::metadata(&path).is_ok_and(|m| m.is_file()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(sha256: &str) -> BundleManifest {
+        BundleManifest {
+            format: FORMAT_VERSION,
+            id: "tool".into(),
+            name: Some("Tool".into()),
+            categories: vec!["Utility".into()],
+            version: Some("v1.2".into()),
+            app_version: None,
+            source: Some("owner/tool".into()),
+            sha256: sha256.into(),
+            icon: Some("icon.png".into()),
+        }
+    }
+
+    /// An AppImage of `len` bytes that ends mid-block, and its hash.
+    fn app_file(dir: &Path, len: usize) -> (PathBuf, String) {
+        let path = dir.join("Tool.AppImage");
+        fs::write(&path, (0..len).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+        let sha = crate::storage::sha256_file(&path).unwrap();
+        (path, sha)
+    }
+
+    #[test]
+    fn bundles_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, sha) = app_file(dir.path(), 3 * BLOCK + 17);
+        let icon = dir.path().join("tool.png");
+        fs::write(&icon, b"not really a png").unwrap();
+        let bundle = dir.path().join("tool.axec");
+        write(&bundle, &manifest(&sha), &app, Some(&icon)).unwrap();
+        assert_eq!(fs::metadata(&bundle).unwrap().len() % BLOCK as u64, 0);
+
+        let out = tempfile::tempdir().unwrap();
+        let read_back = read(&bundle, out.path()).unwrap();
+        assert_eq!((read_back.id.as_str(), read_back.name.as_deref(), read_back.sha256.as_str()), ("tool", Some("Tool"), sha.as_str()));
+        assert_eq!(read_back.categories, ["Utility"]);
+        assert_eq!(fs::read(out.path().join(APP_FILE)).unwrap(), fs::read(&app).unwrap());
+        assert_eq!(fs::read(icon_path(out.path(), &read_back).unwrap()).unwrap(), b"not really a png");
+    }
+
+    #[test]
+    fn bundles_with_a_wrong_hash_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, sha) = app_file(dir.path(), 1000);
+        let bundle = dir.path().join("tool.axec");
+        let wrong = sha.chars().rev().collect::<String>();
+        write(&bundle, &manifest(&wrong), &app, None).unwrap();
+        let err = read(&bundle, tempfile::tempdir().unwrap().path()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("doesn't match the hash"));
+    }
+
+    #[test]
+    fn entries_that_climb_out_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, sha) = app_file(dir.path(), 10);
+        let mut manifest = manifest(&sha);
+        manifest.icon = Some("../escape.png".into());
+        let bundle = dir.path().join("tool.axec");
+        let mut out = File::create(&bundle).unwrap();
+        let json = serde_json::to_vec(&manifest).unwrap();
+        write_entry(&mut out, MANIFEST, json.len() as u64, 0o644, &mut json.as_slice()).unwrap();
+        write_entry(&mut out, APP_FILE, 10, 0o755, &mut File::open(&app).unwrap()).unwrap();
+        for name in ["../escape.png", "sub/../../escape.png", ".."] {
+            write_entry(&mut out, name, 5, 0o644, &mut b"owned".as_slice()).unwrap();
+        }
+        out.write_all(&[0u8; BLOCK * 2]).unwrap();
+        drop(out);
+
+        let dest = dir.path().join("unpacked");
+        fs::create_dir(&dest).unwrap();
+        let read_back = read(&bundle, &dest).unwrap();
+        assert!(!dir.path().join("escape.png").exists());
+        assert_eq!(icon_path(&dest, &read_back), None);
+        let mut names: Vec<_> = fs::read_dir(&dest).unwrap().map(|e| e.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, [APP_FILE]);
+    }
+}
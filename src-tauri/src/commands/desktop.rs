@@ -0,0 +1,261 @@
+//! Commands that manage the desktop entries and categories of integrated apps.
+
+use crate::{
+    app_entry, appimage, desktop, doctor, ensure_dirs, extract_metadata_tree, extraction_tempdir,
+    find_appimage, in_flatpak_sandbox, launch, metadata, parse_appimage_name,
+    refresh_desktop_database, settings, validate_id, write_app_desktop, write_app_desktop_tree,
+    AppImageEntry,
+};
+use serde::Serialize;
+use std::fs;
+use super::{installed_appimage, rewrite_entries, with_app_meta};
+use tauri::AppHandle;
+use tauri_plugin_opener::OpenerExt;
+
+/// Desktop file naming `migrate_desktop_naming` brings entries up to; 1 is reverse-DNS.
+pub const DESKTOP_NAMING: u32 = 1;
+
+/// Adds or removes the app's menu entry; either way it stays in Axec's list.
+#[tauri::command]
+pub fn set_integration(id: String, integrate: bool) -> Result<(), String> {
+    with_app_meta(&id, |m| m.integrate = Some(integrate))?;
+    rewrite_entries(&id, false)
+}
+
+#[tauri::command]
+pub fn apply_category_preset(id: String, preset: String) -> Result<Vec<String>, String> {
+    validate_id(&id)?;
+    let categories = desktop::category_preset(&preset).ok_or_else(|| format!("Unknown category preset: {preset}"))?;
+    let categories: Vec<String> = categories.iter().map(|c| c.to_string()).collect();
+    with_app_meta(&id, |m| m.categories = categories.clone())?;
+    rewrite_entries(&id, true)?;
+    Ok(categories)
+}
+
+/// Per-app result of a bulk operation; `error` is `None` on success.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkOutcome {
+    pub id: String,
+    pub error: Option<String>,
+}
+
+/// Gives every app in `ids` the same categories, refreshing the menu once at the end.
+#[tauri::command]
+pub fn recategorize(ids: Vec<String>, categories: Vec<String>) -> Result<Vec<BulkOutcome>, String> {
+    desktop::validate_categories(&categories)?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let mut outcomes = Vec::new();
+    for id in ids {
+        let error = validate_id(&id).err().or_else(|| installed_appimage(&storage, &meta, &id).err());
+        if error.is_none() {
+            meta.entry(&id).categories = categories.clone();
+        }
+        outcomes.push(BulkOutcome { id, error });
+    }
+    meta.save().map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        for outcome in outcomes.iter_mut().filter(|o| o.error.is_none()) {
+            if let Err(e) = write_app_desktop_tree(&storage, &apps_dir, &outcome.id, &meta) {
+                outcome.error = Some(e.to_string());
+            }
+        }
+        refresh_desktop_database(&apps_dir);
+    }
+    Ok(outcomes)
+}
+
+/// Renames installed apps' `axec-{id}.desktop` entries to the reverse-DNS scheme and
+/// records that it's done. Safe to re-run; returns the ids whose entries moved.
+#[tauri::command]
+pub fn migrate_desktop_naming() -> Result<Vec<String>, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    // The sandbox's menu dir isn't the host's; leave the migration for a native run
+    if in_flatpak_sandbox() {
+        return Ok(Vec::new());
+    }
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let mut migrated = Vec::new();
+    for e in fs::read_dir(&apps_dir).map_err(|e| e.to_string())?.flatten() {
+        let file_name = e.file_name();
+        let Some((id, true)) = file_name.to_str().and_then(desktop::entry_id) else { continue };
+        // Entries of apps that are gone are left to compact_storage
+        if find_appimage(&storage, &meta, id).is_none() {
+            continue;
+        }
+        write_app_desktop(&storage, &apps_dir, id, &meta).map_err(|e| e.to_string())?;
+        fs::remove_file(e.path()).map_err(|e| e.to_string())?;
+        migrated.push(id.to_string());
+    }
+    if !migrated.is_empty() {
+        refresh_desktop_database(&apps_dir);
+    }
+    meta.desktop_naming = DESKTOP_NAMING;
+    meta.save().map_err(|e| e.to_string())?;
+    Ok(migrated)
+}
+
+/// Opens `id`'s desktop entry with the default handler for hand edits; `reload_from_desktop`
+/// brings them back into Axec's metadata.
+#[tauri::command]
+pub fn edit_desktop_file(app: AppHandle, id: String) -> Result<(), String> {
+    validate_id(&id)?;
+    let (_storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = apps_dir.join(desktop::entry_file_name(&id));
+    if !path.exists() {
+        return Err(format!("{id} has no desktop entry; repair it to create one"));
+    }
+    app.opener().open_path(path.to_string_lossy(), None::<&str>).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DesktopReload {
+    pub entry: AppImageEntry,
+    /// Edits that weren't taken over, and why.
+    pub warnings: Vec<String>,
+}
+
+/// Quotes a wrapper word so `launch::split_wrapper` reads it back as one word.
+fn quote_wrapper_word(word: &str) -> String {
+    if !word.is_empty() && !word.chars().any(|c| c.is_whitespace() || c == '"' || c == '\'') {
+        word.to_string()
+    } else if word.contains('\'') {
+        format!("\"{word}\"")
+    } else {
+        format!("'{word}'")
+    }
+}
+
+/// Reads a hand-edited desktop entry back into metadata: name, comment,
+/// categories, terminal flag, and the launch args and wrapper around the managed
+/// binary. An `Exec=` that no longer runs that binary is reported, not trusted.
+#[tauri::command]
+pub fn reload_from_desktop(id: String) -> Result<DesktopReload, String> {
+    validate_id(&id)?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), &id)?;
+    let content = fs::read_to_string(apps_dir.join(desktop::entry_file_name(&id))).map_err(|e| e.to_string())?;
+    let entry = desktop::parse(&content);
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let inherited = meta.resolved_categories(&id);
+    let app_meta = meta.entry(&id);
+    let mut warnings = Vec::new();
+    if let Some(name) = entry.get("Name").filter(|n| !n.trim().is_empty()) {
+        app_meta.name = Some(settings::undecorate_name(&settings::load(), name).to_string());
+    }
+    app_meta.comment = entry.get("Comment").filter(|c| !c.is_empty()).map(String::from);
+    app_meta.terminal = entry.get_bool("Terminal").unwrap_or(false);
+    app_meta.mime_types = desktop::valid_mime_types(entry.get_list("MimeType"));
+    app_meta.keywords = entry.get_list("Keywords");
+    // A class that's just the guess stays a guess, so turning the setting off drops it
+    app_meta.startup_wm_class = entry.get("StartupWMClass").filter(|c| !c.is_empty() && Some(*c) != app_meta.wm_class_guess.as_deref()).map(String::from);
+    if let Some(list) = entry.get("Categories") {
+        let categories: Vec<String> = list.split(';').filter(|c| !c.is_empty()).map(String::from).collect();
+        match desktop::validate_categories(&categories) {
+            // Unchanged inherited categories stay inherited rather than becoming an override
+            Ok(()) if categories == inherited => {}
+            Ok(()) => app_meta.categories = categories,
+            Err(e) => warnings.push(format!("Categories left unchanged: {e}")),
+        }
+    }
+    if let Some(exec) = entry.get("Exec") {
+        let words = desktop::split_exec(exec);
+        let binary = desktop::ExecWord::Arg(path.to_string_lossy().to_string());
+        match words.iter().position(|w| *w == binary) {
+            None => warnings.push(format!("Exec= no longer runs {}; launch args and wrapper left unchanged", path.display())),
+            Some(at) => {
+                let args_of = |ws: &[desktop::ExecWord]| -> Vec<String> {
+                    ws.iter().filter_map(|w| if let desktop::ExecWord::Arg(a) = w { Some(a.clone()) } else { None }).collect()
+                };
+                let rest = &words[at + 1..];
+                let code = rest.iter().position(|w| matches!(w, desktop::ExecWord::FieldCode(_))).unwrap_or(rest.len());
+                app_meta.field_code = match rest.get(code) {
+                    Some(desktop::ExecWord::FieldCode(c)) => Some(*c),
+                    _ => None,
+                };
+                let prefix = args_of(&words[..at]);
+                let suffix = args_of(rest.get(code + 1..).unwrap_or_default());
+                app_meta.default_args = args_of(&rest[..code]);
+                app_meta.exec_wrapper = (!prefix.is_empty() || !suffix.is_empty()).then(|| {
+                    let command = [launch::COMMAND_PLACEHOLDER.to_string()];
+                    prefix.iter().chain(&command).chain(&suffix).map(|w| quote_wrapper_word(w)).collect::<Vec<_>>().join(" ")
+                });
+            }
+        }
+    }
+    meta.save().map_err(|e| e.to_string())?;
+    let entry = app_entry(&storage, &apps_dir, &path, &meta).map_err(|e| e.to_string())?;
+    Ok(DesktopReload { entry, warnings })
+}
+
+/// Takes the display name from the AppImage's own desktop entry again, or from its file
+/// name when it has none, and rewrites the generated entry to match.
+#[tauri::command]
+pub async fn set_name_from_metadata(id: String) -> Result<AppImageEntry, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let path = installed_appimage(&storage, &metadata::load(), &id)?;
+        let app_type = metadata::load().get(&id).app_type.unwrap_or(appimage::AppImageType::Unknown);
+        let tmp_dir = extraction_tempdir(&path)?;
+        let embedded = extract_metadata_tree(&path, tmp_dir.path(), app_type, settings::load().safe_extraction).as_deref().and_then(desktop::read_embedded);
+        let locales = desktop::current_locales();
+        let name = embedded.as_ref().and_then(|d| d.get_localized("Name", &locales)).filter(|n| !n.trim().is_empty());
+        let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+        let app_meta = meta.entry(&id);
+        app_meta.name = Some(name.map(String::from).unwrap_or_else(|| parse_appimage_name(&path)));
+        // Stale translations would otherwise still win in other locales
+        app_meta.localized.retain(|key, _| !key.starts_with("Name["));
+        if let Some(entry) = &embedded {
+            app_meta.localized.extend(entry.translations("Name"));
+        }
+        meta.save().map_err(|e| e.to_string())?;
+        if !in_flatpak_sandbox() {
+            write_app_desktop(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())?;
+            refresh_desktop_database(&apps_dir);
+        }
+        app_entry(&storage, &apps_dir, &path, &meta).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Drops `id`'s own categories so it inherits its parent's again.
+#[tauri::command]
+pub fn reset_categories(id: String) -> Result<Vec<String>, String> {
+    with_app_meta(&id, |m| m.categories.clear())?;
+    rewrite_entries(&id, true)?;
+    Ok(metadata::load().resolved_categories(&id))
+}
+
+/// Checks why `id` might be missing from the menu, unlike `doctor` which checks the host.
+#[tauri::command]
+pub fn verify_desktop_integration(id: String) -> Result<doctor::DoctorReport, String> {
+    validate_id(&id)?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    installed_appimage(&storage, &metadata::load(), &id)?;
+    let desktop_path = apps_dir.join(desktop::entry_file_name(&id));
+    Ok(doctor::verify_integration(&desktop_path, &apps_dir, metadata::load().get(&id).integrated(), in_flatpak_sandbox()))
+}
+
+/// Rewrites every installed app's desktop entry and theme icon from its metadata. Files
+/// that already match are skipped, and the menu is only asked to reload when one changed.
+/// Returns the ids whose entries changed.
+#[tauri::command]
+pub fn regenerate_desktop_files() -> Result<Vec<String>, String> {
+    if in_flatpak_sandbox() {
+        return Ok(Vec::new());
+    }
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let meta = metadata::load();
+    let mut changed = Vec::new();
+    for id in meta.apps.keys().filter(|id| find_appimage(&storage, &meta, id).is_some()) {
+        if write_app_desktop(&storage, &apps_dir, id, &meta).map_err(|e| e.to_string())? {
+            changed.push(id.clone());
+        }
+    }
+    if !changed.is_empty() {
+        refresh_desktop_database(&apps_dir);
+    }
+    Ok(changed)
+}
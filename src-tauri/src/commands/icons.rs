@@ -0,0 +1,149 @@
+//! Commands that find, theme and serve app icons.
+
+use crate::{
+    app_dir, appimage, ensure_dirs, extract_embedded, extraction_tempdir, find_app_icon,
+    find_appimage, for_each_parallel, icons, in_flatpak_sandbox, metadata,
+    refresh_desktop_database, settings, storage, stored_appimage, thumbnails_dir, validate_id,
+    write_app_desktop, DEFAULT_PARALLEL_IMPORTS,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tauri::{AppHandle, Emitter};
+
+/// Progress `rescan_icons` emits after each app.
+#[derive(Debug, Clone, Serialize)]
+struct RescanProgress {
+    id: String,
+    done: usize,
+    total: usize,
+}
+
+/// Re-extracts every app's icon with the current extraction logic and returns how many
+/// changed. Icons the user replaced by hand are left alone.
+#[tauri::command]
+pub async fn rescan_icons(app: AppHandle) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || rescan_icons_blocking(&app)).await.map_err(|e| e.to_string())?
+}
+
+fn rescan_icons_blocking(app: &AppHandle) -> Result<usize, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let snapshot = metadata::load();
+    let ids: Vec<String> = snapshot.apps.keys().filter(|id| find_appimage(&storage, &snapshot, id).is_some()).cloned().collect();
+    let done = AtomicUsize::new(0);
+    let workers = settings::load().max_parallel_imports.unwrap_or(DEFAULT_PARALLEL_IMPORTS);
+    let rescanned = for_each_parallel(&ids, workers, |id| {
+        let sha = rescan_icon(&storage, id, &snapshot.get(id));
+        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = app.emit("axec://rescan-progress", RescanProgress { id: id.clone(), done, total: ids.len() });
+        sha
+    });
+    // New icon hashes by id, for the apps whose icon changed
+    let changed: HashMap<&String, String> = ids.iter().zip(rescanned).filter_map(|(id, sha)| Some((id, sha?))).collect();
+    if changed.is_empty() {
+        return Ok(0);
+    }
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    for (id, sha) in &changed {
+        meta.entry(id).icon_sha256 = Some(sha.clone());
+    }
+    meta.save().map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        let rewritten = changed.keys().filter(|id| write_app_desktop(&storage, &apps_dir, id, &meta).unwrap_or(false)).count();
+        if rewritten > 0 {
+            refresh_desktop_database(&apps_dir);
+        }
+    }
+    Ok(changed.len())
+}
+
+/// Extracts `id`'s icon afresh and swaps it in if it differs; returns the new icon's hash
+/// when it did. A stored icon that doesn't match the recorded hash is the user's and stays.
+fn rescan_icon(storage: &Path, id: &str, app_meta: &metadata::AppMeta) -> Option<String> {
+    let current = icons::find_icon(&app_dir(storage, app_meta), id);
+    let current_sha = current.as_deref().and_then(|p| storage::sha256_file(p).ok());
+    if let (Some(recorded), Some(current_sha)) = (&app_meta.icon_sha256, &current_sha) {
+        if recorded != current_sha {
+            return None;
+        }
+    }
+    let path = stored_appimage(storage, id, app_meta)?;
+    let tmp = extraction_tempdir(&path).ok()?;
+    let fresh = extract_embedded(&path, tmp.path(), id, app_meta.app_type.unwrap_or(appimage::AppImageType::Unknown)).icon?;
+    let fresh_sha = storage::sha256_file(&fresh).ok()?;
+    let dir = app_dir(storage, app_meta);
+    let dest = dir.join(fresh.file_name()?);
+    if current_sha.as_ref() == Some(&fresh_sha) && current.as_ref() == Some(&dest) {
+        return None;
+    }
+    // Copy under a temp name so a failure never leaves the app without its old icon
+    let partial = dir.join(format!(".{id}.icon.partial"));
+    fs::copy(&fresh, &partial).ok()?;
+    if let Some(old) = current.filter(|old| *old != dest) {
+        let _ = fs::remove_file(old);
+    }
+    if fs::rename(&partial, &dest).is_err() {
+        let _ = fs::remove_file(&partial);
+        return None;
+    }
+    Some(fresh_sha)
+}
+
+/// Makes every app that ships light and dark icons show the one for `variant`, or for
+/// the current desktop theme when omitted. Returns the ids whose icon was swapped.
+#[tauri::command]
+pub fn apply_icon_theme(variant: Option<icons::ThemeVariant>) -> Result<Vec<String>, String> {
+    let variant = variant.unwrap_or_else(icons::system_variant);
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let ids: Vec<String> = meta.apps.iter().filter(|(_, m)| m.icon_variant.is_some_and(|v| v != variant)).map(|(id, _)| id.clone()).collect();
+    let mut swapped = Vec::new();
+    for id in ids {
+        let dir = app_dir(&storage, &meta.get(&id));
+        let Some(active) = icons::swap_alternate(&dir, &id).map_err(|e| e.to_string())? else { continue };
+        let app_meta = meta.entry(&id);
+        app_meta.icon_variant = Some(variant);
+        app_meta.icon_sha256 = storage::sha256_file(&active).ok();
+        swapped.push(id);
+    }
+    if swapped.is_empty() {
+        return Ok(swapped);
+    }
+    meta.save().map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        let rewritten = swapped.iter().filter(|id| write_app_desktop(&storage, &apps_dir, id, &meta).unwrap_or(false)).count();
+        if rewritten > 0 {
+            refresh_desktop_database(&apps_dir);
+        }
+    }
+    Ok(swapped)
+}
+
+#[tauri::command]
+pub fn thumbnail(id: String, size: u32) -> Result<String, String> {
+    validate_id(&id)?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let icon = find_app_icon(&storage, &metadata::load(), &id).ok_or("App has no icon")?;
+    let cache_dir = thumbnails_dir().map_err(|e| e.to_string())?;
+    icons::thumbnail(&icon, &cache_dir, &id, size)
+}
+
+/// An app's icon as stored, for UIs that can't read files directly (strict asset scopes, Flatpak).
+#[derive(Debug, Clone, Serialize)]
+pub struct IconData {
+    pub mime: String,
+    pub data_uri: String,
+}
+
+#[tauri::command]
+pub fn get_icon_data(id: String) -> Result<IconData, String> {
+    validate_id(&id)?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let icon = find_app_icon(&storage, &metadata::load(), &id).ok_or("App has no icon")?;
+    let data = fs::read(&icon).map_err(|e| e.to_string())?;
+    Ok(IconData { mime: icons::mime_type(&icon, &data).to_string(), data_uri: icons::encode_data_uri(&icon, &data) })
+}
@@ -0,0 +1,605 @@
+//! Commands that add AppImages: from a file, a URL, a folder, an existing menu entry
+//! or a bundle, and the staged add that previews a file before committing it.
+
+use crate::{
+    app_dir, app_dirs, app_entry, app_file_candidates, appimage, appstream, bundle, desktop,
+    display_name_from_file, download, download_source, ensure_dirs, extract_metadata_tree,
+    extraction_base, extraction_tempdir, find_app_icon, find_appimage, for_each_parallel, icons,
+    import_appimage, import_appimage_with, in_flatpak_sandbox, metadata, parse_appimage_name,
+    plan, plan_import, refresh_desktop_database, resolve_collision, sanitize_filename, settings,
+    storage, tray, validate_id, write_app_desktop, AppImageEntry, DEFAULT_PARALLEL_IMPORTS,
+    ImportPlan, InstallMode, Prepared,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use super::installed_appimage;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+
+/// What `probe_appimage` learns about a candidate file without importing it.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppImageProbe {
+    pub name: String,
+    pub app_type: appimage::AppImageType,
+    pub arch: Option<String>,
+    pub version: Option<String>,
+    pub comment: Option<String>,
+    /// Icon as a data URI, read from a throwaway extraction.
+    pub icon: Option<String>,
+    /// Why adding it may go wrong or produce a bare entry, e.g. an unreadable image format.
+    pub warning: Option<String>,
+    /// Args it will launch with by default, from its embedded entry.
+    pub recommended_args: Vec<String>,
+    /// `Categories=` from its embedded entry; empty when it has none usable.
+    pub categories: Vec<String>,
+}
+
+/// Copy progress `add_appimage` emits while copying the file into storage.
+#[derive(Debug, Clone, Serialize)]
+struct AddProgress {
+    path: String,
+    copied: u64,
+    total: u64,
+}
+
+/// Adds the AppImage at `file_path`, emitting `axec://add-progress` as it's copied.
+/// With `dry_run`, returns the steps the import would take instead of taking them.
+#[tauri::command]
+pub async fn add_appimage(
+    app: AppHandle,
+    file_path: String,
+    force: Option<bool>,
+    dry_run: Option<bool>,
+    install_mode: Option<InstallMode>,
+) -> Result<plan::DryRun<AppImageEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let src = PathBuf::from(&file_path);
+        let force = force.unwrap_or(false);
+        let install = install_mode.unwrap_or_default();
+        if dry_run.unwrap_or(false) {
+            let (storage, apps_dir) = app_dirs().map_err(|e| e.to_string())?;
+            let planned = plan_import(&src, None, None, force, install, &settings::load(), &storage)?;
+            return Ok(plan::DryRun::Planned(planned.describe(&storage, &apps_dir, &metadata::load())));
+        }
+        let mut on_progress = |copied, total| {
+            let _ = app.emit("axec://add-progress", AddProgress { path: file_path.clone(), copied, total });
+        };
+        import_appimage_with(&src, None, None, force, install, None, &mut on_progress).map(plan::DryRun::Applied)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Asks for an AppImage with the native (or portal) file dialog and adds it; `None` when
+/// the dialog is cancelled. All files are offered too, since many AppImages lack the extension.
+#[tauri::command]
+pub async fn pick_and_add(app: AppHandle) -> Result<Option<AppImageEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let picked = app
+            .dialog()
+            .file()
+            .set_title("Add AppImage")
+            .add_filter("AppImage", &["AppImage", "appimage"])
+            .add_filter("All files", &["*"])
+            .blocking_pick_file();
+        let Some(picked) = picked else { return Ok(None) };
+        let src = picked.into_path().map_err(|e| e.to_string())?;
+        let file_path = src.to_string_lossy().to_string();
+        let mut on_progress = |copied, total| {
+            let _ = app.emit("axec://add-progress", AddProgress { path: file_path.clone(), copied, total });
+        };
+        import_appimage(&src, None, None, false, &mut on_progress).map(Some)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Downloads and adds an AppImage from a URL, or from the latest GitHub release when
+/// `source` is `owner/repo`. `headers` (e.g. `Authorization`) go with every request but
+/// are never stored; only the source and release tag are kept for update checks.
+#[tauri::command]
+pub async fn add_from_url(source: String, headers: Option<HashMap<String, String>>) -> Result<AppImageEntry, download::DownloadError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let headers: Vec<(String, String)> = headers.unwrap_or_default().into_iter().collect();
+        let tmp = tempfile::Builder::new().prefix("axec-download-").tempdir()?;
+        let (path, version) = download_source(&source, &headers, tmp.path(), &mut |_, _| {})?;
+        let entry = import_appimage(&path, None, None, false, &mut |_, _| {}).map_err(|message| download::DownloadError::Import { message })?;
+        let mut meta = metadata::lock()?;
+        let app_meta = meta.entry(&entry.id);
+        app_meta.source = Some(source);
+        app_meta.version = version;
+        meta.save()?;
+        Ok(entry)
+    })
+    .await
+    .map_err(|e| download::DownloadError::Io { message: e.to_string() })?
+}
+
+/// What `import_directory` does with a file whose id or binary is already installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Leave the installed app alone.
+    Skip,
+    /// Add it alongside as `{id}-2`, `{id}-3`, ..., even if it's the same binary.
+    Rename,
+    /// Replace the installed app with the same id, keeping its settings.
+    Overwrite,
+    /// Skip identical binaries, but add a different one with a taken id under a new id.
+    #[default]
+    SkipIfSameHash,
+}
+
+/// How one file in an `import_directory` run went. `conflict` is the strategy applied,
+/// when the file clashed with an installed app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Added { entry: Box<AppImageEntry>, conflict: Option<ConflictStrategy> },
+    /// The same binary or id is already in storage (or earlier in this run).
+    Skipped { path: String, existing_id: Option<String>, conflict: ConflictStrategy },
+    Failed { path: String, error: String },
+}
+
+/// Imports one file of an `import_directory` run. `same_binary` is set when its hash is
+/// already installed, with the id that has it.
+fn import_with_strategy(storage: &Path, path: &Path, sha: String, same_binary: Option<Option<String>>, on_conflict: ConflictStrategy) -> ImportOutcome {
+    let path_str = path.to_string_lossy().to_string();
+    if let Some(existing_id) = same_binary.clone().filter(|_| matches!(on_conflict, ConflictStrategy::Skip | ConflictStrategy::SkipIfSameHash)) {
+        return ImportOutcome::Skipped { path: path_str, existing_id, conflict: on_conflict };
+    }
+    let id = sanitize_filename(&parse_appimage_name(path));
+    let taken = !id.is_empty() && find_appimage(storage, &metadata::load(), &id).is_some();
+    let (as_id, force) = match on_conflict {
+        _ if !taken => (None, false),
+        ConflictStrategy::Skip => return ImportOutcome::Skipped { path: path_str, existing_id: Some(id), conflict: on_conflict },
+        ConflictStrategy::Overwrite => (None, true),
+        ConflictStrategy::Rename | ConflictStrategy::SkipIfSameHash => match resolve_collision(storage, id, settings::CollisionPolicy::Suffix) {
+            Ok(id) => (Some(id), false),
+            Err(error) => return ImportOutcome::Failed { path: path_str, error },
+        },
+    };
+    let conflict = (taken || same_binary.is_some()).then_some(on_conflict);
+    match import_appimage(path, as_id.as_deref(), Some(sha), force, &mut |_, _| {}) {
+        Ok(entry) => ImportOutcome::Added { entry: Box::new(entry), conflict },
+        Err(error) => ImportOutcome::Failed { path: path_str, error },
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ImportProgress {
+    path: String,
+    done: usize,
+    total: usize,
+}
+
+/// AppImages under `dir`, found by magic bytes since many downloads lack the extension.
+fn find_importable(dir: &Path, recursive: bool, storage: &Path) -> Vec<PathBuf> {
+    let walker = walkdir::WalkDir::new(dir).max_depth(if recursive { usize::MAX } else { 1 });
+    walker
+        .into_iter()
+        // Importing the data dir into itself would only find our own copies
+        .filter_entry(|e| !e.path().starts_with(storage))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            let has_ext = p.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("appimage"));
+            match appimage::detect_type(p) {
+                Ok(appimage::AppImageType::Unknown) => has_ext,
+                Ok(_) => true,
+                Err(_) => false,
+            }
+        })
+        .collect()
+}
+
+/// Imports every AppImage under `dir`; `on_conflict` (default `skip_if_same_hash`) says
+/// what happens to files that clash with installed apps.
+#[tauri::command]
+pub async fn import_directory(app: AppHandle, dir: String, recursive: bool, on_conflict: Option<ConflictStrategy>) -> Result<Vec<ImportOutcome>, String> {
+    tauri::async_runtime::spawn_blocking(move || import_directory_blocking(&app, Path::new(&dir), recursive, on_conflict.unwrap_or_default()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn import_directory_blocking(app: &AppHandle, dir: &Path, recursive: bool, on_conflict: ConflictStrategy) -> Result<Vec<ImportOutcome>, String> {
+    if !dir.is_dir() {
+        return Err("Directory not found".into());
+    }
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let files = find_importable(dir, recursive, &storage);
+    let known: HashMap<String, Option<String>> =
+        metadata::load().apps.into_iter().filter_map(|(id, m)| m.sha256.map(|sha| (sha, Some(id)))).collect();
+    let known = Mutex::new(known);
+    // Hashing runs in parallel; the import itself touches shared metadata, so one at a time
+    let import_lock = Mutex::new(());
+    let done = AtomicUsize::new(0);
+    let workers = settings::load().max_parallel_imports.unwrap_or(DEFAULT_PARALLEL_IMPORTS);
+    let outcomes = for_each_parallel(&files, workers, |path| {
+        let path_str = path.to_string_lossy().to_string();
+        let outcome = match storage::sha256_file(path) {
+            Err(e) => ImportOutcome::Failed { path: path_str.clone(), error: e.to_string() },
+            Ok(sha) => {
+                // Claim the hash before importing so a duplicate in the same run is skipped
+                let existing = {
+                    let mut known = known.lock().unwrap();
+                    let existing = known.get(&sha).cloned();
+                    if existing.is_none() {
+                        known.insert(sha.clone(), None);
+                    }
+                    existing
+                };
+                let _guard = import_lock.lock().unwrap();
+                import_with_strategy(&storage, path, sha, existing, on_conflict)
+            }
+        };
+        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = app.emit("axec://import-progress", ImportProgress { path: path_str, done, total: files.len() });
+        outcome
+    });
+    if !in_flatpak_sandbox() {
+        refresh_desktop_database(&apps_dir);
+    }
+    let _ = tray::refresh(app);
+    Ok(outcomes)
+}
+
+/// A menu entry another tool (appimaged, Gear Lever, ...) wrote for an AppImage.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalEntry {
+    pub path: String,
+    pub name: Option<String>,
+    /// The AppImage its `Exec=` runs.
+    pub target: String,
+    /// The Axec app with that same binary, if the target is one; otherwise adopting imports it.
+    pub managed_id: Option<String>,
+}
+
+/// The AppImage an entry's `Exec=` runs: its first absolute-path word that is one.
+fn exec_target(entry: &desktop::DesktopEntry) -> Option<PathBuf> {
+    desktop::split_exec(entry.get("Exec")?).into_iter().find_map(|word| match word {
+        desktop::ExecWord::Arg(arg) if arg.starts_with('/') && appimage::detect_type(Path::new(&arg)).is_ok() => Some(PathBuf::from(arg)),
+        _ => None,
+    })
+}
+
+/// The managed app `target` is: a stored file by path, or else one with the same content.
+fn managed_id_for(storage: &Path, target: &Path, meta: &metadata::Metadata) -> Option<String> {
+    let real = fs::canonicalize(target).ok()?;
+    let by_path = meta.apps.iter().find(|(id, m)| app_file_candidates(storage, id, m).iter().any(|p| fs::canonicalize(p).ok().as_ref() == Some(&real)));
+    if let Some((id, _)) = by_path {
+        return Some(id.clone());
+    }
+    let sha = storage::sha256_file(&real).ok()?;
+    meta.apps.iter().find(|(_, m)| m.sha256.as_deref() == Some(sha.as_str())).map(|(id, _)| id.clone())
+}
+
+fn external_entry(storage: &Path, path: &Path, meta: &metadata::Metadata) -> Option<ExternalEntry> {
+    let name = path.file_name()?.to_str()?;
+    if !name.ends_with(".desktop") || desktop::entry_id(name).is_some() {
+        return None;
+    }
+    let entry = desktop::parse(&fs::read_to_string(path).ok()?);
+    let target = exec_target(&entry)?;
+    Some(ExternalEntry {
+        path: path.to_string_lossy().to_string(),
+        name: entry.get("Name").map(String::from),
+        managed_id: managed_id_for(storage, &target, meta),
+        target: target.to_string_lossy().to_string(),
+    })
+}
+
+/// Menu entries not written by Axec that launch an AppImage, so duplicates left by other
+/// managers can be adopted.
+#[tauri::command]
+pub async fn find_external_entries() -> Result<Vec<ExternalEntry>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let meta = metadata::load();
+        let rd = fs::read_dir(&apps_dir).map_err(|e| e.to_string())?;
+        Ok(rd.flatten().filter_map(|e| external_entry(&storage, &e.path(), &meta)).collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Replaces an external entry with an Axec one: its AppImage is imported unless Axec
+/// already has it, then the other tool's entry is deleted.
+#[tauri::command]
+pub async fn adopt_entry(app: AppHandle, path: String) -> Result<AppImageEntry, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let path = PathBuf::from(path);
+        // Only entries from the menu dir, so this can't be aimed at arbitrary files
+        if path.parent() != Some(apps_dir.as_path()) {
+            return Err("Not an entry in the applications directory".into());
+        }
+        let external = external_entry(&storage, &path, &metadata::load()).ok_or("Not an external AppImage entry")?;
+        let entry = match &external.managed_id {
+            Some(id) => {
+                let found = installed_appimage(&storage, &metadata::load(), id)?;
+                let meta = metadata::load();
+                if !in_flatpak_sandbox() {
+                    write_app_desktop(&storage, &apps_dir, id, &meta).map_err(|e| e.to_string())?;
+                }
+                app_entry(&storage, &apps_dir, &found, &meta).map_err(|e| e.to_string())?
+            }
+            None => import_appimage(Path::new(&external.target), None, None, false, &mut |_, _| {})?,
+        };
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+        if !in_flatpak_sandbox() {
+            refresh_desktop_database(&apps_dir);
+        }
+        let _ = tray::refresh(&app);
+        Ok(entry)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn probe_appimage(path: String) -> Result<AppImageProbe, String> {
+    let src = PathBuf::from(&path);
+    if !src.is_file() {
+        return Err("File not found".into());
+    }
+    let app_type = appimage::detect_type(&src).map_err(|e| e.to_string())?;
+    // Never chmod or run the candidate; it is read in-process unless that fails
+    let tmp_dir = extraction_tempdir(&src)?;
+    let squash_root = extract_metadata_tree(&src, tmp_dir.path(), app_type, true);
+    probe_tree(&src, app_type, squash_root.as_deref())
+}
+
+/// `probe_appimage`'s findings, from a metadata tree already unpacked to `squash_root`.
+fn probe_tree(src: &Path, app_type: appimage::AppImageType, squash_root: Option<&Path>) -> Result<AppImageProbe, String> {
+    let arch = appimage::elf_arch(src).map_err(|e| e.to_string())?.map(String::from);
+    let embedded = squash_root.and_then(desktop::read_embedded);
+    let stream = squash_root.and_then(appstream::read_embedded);
+
+    let locales = desktop::current_locales();
+    let name = embedded
+        .as_ref()
+        .and_then(|d| d.get_localized("Name", &locales).map(String::from))
+        .or_else(|| stream.as_ref().and_then(|s| s.name.clone()))
+        .unwrap_or_else(|| display_name_from_file(src));
+    let version = embedded
+        .as_ref()
+        .and_then(|d| d.get("X-AppImage-Version").map(String::from))
+        .or_else(|| stream.as_ref().and_then(|s| s.version.clone()));
+    let comment = embedded
+        .as_ref()
+        .and_then(|d| d.get_localized("Comment", &locales).map(String::from))
+        .or_else(|| stream.as_ref().and_then(|s| s.summary.clone()));
+    let icon = squash_root.and_then(icons::find_in_tree).and_then(|p| icons::data_uri(&p));
+    let warning = match appimage::payload(src).unwrap_or(appimage::Payload::None) {
+        appimage::Payload::Dwarfs => Some("Uses a DwarFS image, which Axec can't read; it should still launch, but without its icon or menu details".to_string()),
+        appimage::Payload::None if app_type == appimage::AppImageType::Unknown => Some("Not an AppImage: there's no filesystem image after the runtime".to_string()),
+        appimage::Payload::None => Some("Has AppImage magic but no image Axec recognises; launching or extracting it may fail".to_string()),
+        _ if squash_root.is_none() => Some("Couldn't read the AppImage's contents; it may be damaged".to_string()),
+        _ => None,
+    };
+    let recommended_args = embedded.as_ref().map(|d| desktop::recommended_args(d).0).unwrap_or_default();
+    let categories = embedded
+        .as_ref()
+        .and_then(desktop::embedded_categories)
+        .or_else(|| stream.as_ref().and_then(|s| desktop::usable_categories(s.categories.clone())))
+        .unwrap_or_default();
+    Ok(AppImageProbe { name, app_type, arch, version, comment, icon, warning, recommended_args, categories })
+}
+
+/// Stages older than this are dropped when the next one is made.
+const STAGE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A file `stage_appimage` looked at but hasn't added yet.
+struct Staged {
+    src: PathBuf,
+    sha256: String,
+    /// Size and mtime when staged, to notice the file changing before it's committed.
+    size: u64,
+    modified: Option<SystemTime>,
+    staged_at: Instant,
+    /// Holds the icon, entry and other metadata files unpacked for the preview, which
+    /// the commit reuses; removed with the stage.
+    _work: tempfile::TempDir,
+    squash_root: Option<PathBuf>,
+}
+
+/// Imports waiting for `commit_staged` or `discard_staged`, by stage id.
+#[derive(Default)]
+pub struct Staging(Mutex<HashMap<String, Staged>>);
+
+/// What `stage_appimage` returns for the confirmation dialog.
+#[derive(Debug, Clone, Serialize)]
+pub struct StagedImport {
+    pub stage_id: String,
+    /// The id the app would get, and whether an installed app already has it.
+    pub id: String,
+    pub exists: bool,
+    pub probe: AppImageProbe,
+}
+
+/// What the user changed in the confirmation dialog; unset fields keep what was found.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StageOverrides {
+    pub name: Option<String>,
+    pub categories: Option<Vec<String>>,
+    /// An image file to use as the icon instead of the embedded one.
+    pub icon: Option<String>,
+    /// Replace an installed app with the same id, as `add_appimage`'s `force` does.
+    pub force: bool,
+}
+
+/// Checks and previews `path` without copying it, for `commit_staged` to add later.
+#[tauri::command]
+pub async fn stage_appimage(app: AppHandle, path: String) -> Result<StagedImport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let src = PathBuf::from(&path);
+        let storage = ensure_dirs().map_err(|e| e.to_string())?.0;
+        // The checks `add_appimage` makes, short of the id being free; the dialog may force it
+        let plan = plan_import(&src, None, None, true, InstallMode::Copy, &settings::load(), &storage)?;
+        let ImportPlan { id, app_type, replacing: exists, sha256, .. } = plan;
+        // Never chmod or run the candidate before the user confirms
+        let work = extraction_tempdir(&src)?;
+        let squash_root = extract_metadata_tree(&src, work.path(), app_type, true);
+        let probe = probe_tree(&src, app_type, squash_root.as_deref())?;
+        let file_meta = fs::metadata(&src).map_err(|e| e.to_string())?;
+        let stage_id = uuid::Uuid::new_v4().to_string();
+        let staged = Staged { src, sha256, size: file_meta.len(), modified: file_meta.modified().ok(), staged_at: Instant::now(), _work: work, squash_root };
+        let staging = app.state::<Staging>();
+        let mut stages = staging.0.lock().unwrap();
+        // Dialogs closed without discarding would otherwise pile up
+        stages.retain(|_, s| s.staged_at.elapsed() < STAGE_TTL);
+        stages.insert(stage_id.clone(), staged);
+        Ok(StagedImport { stage_id, id, exists, probe })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Adds a staged file with the dialog's `overrides` applied, copying it into storage.
+#[tauri::command]
+pub async fn commit_staged(app: AppHandle, stage_id: String, overrides: Option<StageOverrides>) -> Result<AppImageEntry, String> {
+    let overrides = overrides.unwrap_or_default();
+    if let Some(categories) = &overrides.categories {
+        desktop::validate_categories(categories)?;
+    }
+    let staged = app.state::<Staging>().0.lock().unwrap().remove(&stage_id).ok_or("Nothing staged under that id; stage the file again")?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let file_meta = fs::metadata(&staged.src).map_err(|e| e.to_string())?;
+        if file_meta.len() != staged.size || file_meta.modified().ok() != staged.modified {
+            return Err("The file changed after it was staged; stage it again".into());
+        }
+        // Read up front, so a bad icon fails the commit before anything is copied
+        let icon = match overrides.icon.map(PathBuf::from) {
+            Some(icon) => {
+                let data = fs::read(&icon).map_err(|e| format!("Couldn't read the icon: {e}"))?;
+                let ext = icons::extension_for(&icon, &data);
+                Some((data, ext))
+            }
+            None => None,
+        };
+        let prepared = Prepared {
+            squash_root: staged.squash_root.clone(),
+            name: overrides.name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()),
+            categories: overrides.categories,
+            icon,
+        };
+        let file_path = staged.src.to_string_lossy().to_string();
+        let mut on_progress = |copied, total| {
+            let _ = app.emit("axec://add-progress", AddProgress { path: file_path.clone(), copied, total });
+        };
+        let entry = import_appimage_with(&staged.src, None, Some(staged.sha256.clone()), overrides.force, InstallMode::Copy, Some(&prepared), &mut on_progress)?;
+        // Kept until here: the import read the tree it holds
+        drop(staged);
+        let _ = tray::refresh(&app);
+        Ok(entry)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Forgets a staged file; nothing was copied, so nothing else needs undoing.
+#[tauri::command]
+pub fn discard_staged(stage_id: String, staging: State<Staging>) -> Result<(), String> {
+    staging.0.lock().unwrap().remove(&stage_id).map(|_| ()).ok_or_else(|| "Nothing staged under that id".into())
+}
+
+/// File name `export_app_bundle` uses when given a directory.
+fn bundle_file_name(id: &str) -> String {
+    format!("{id}.axec.tar")
+}
+
+/// Writes `id`'s AppImage, icon and basic metadata to a bundle at `dest`, which may be a
+/// directory to put `{id}.axec.tar` in. Returns the bundle's path.
+#[tauri::command]
+pub async fn export_app_bundle(id: String, dest: String) -> Result<String, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let storage = ensure_dirs().map_err(|e| e.to_string())?.0;
+        let path = installed_appimage(&storage, &metadata::load(), &id)?;
+        let app_meta = metadata::load().get(&id);
+        let dest = PathBuf::from(dest);
+        let dest = if dest.is_dir() { dest.join(bundle_file_name(&id)) } else { dest };
+        let icon = find_app_icon(&storage, &metadata::load(), &id);
+        let sha256 = match &app_meta.sha256 {
+            Some(sha) => sha.clone(),
+            None => storage::sha256_file(&path).map_err(|e| e.to_string())?,
+        };
+        let manifest = bundle::BundleManifest {
+            format: bundle::FORMAT_VERSION,
+            id: id.clone(),
+            name: app_meta.name.clone(),
+            categories: app_meta.categories.clone(),
+            version: app_meta.version.clone(),
+            app_version: app_meta.app_version.clone(),
+            source: app_meta.source.clone(),
+            sha256,
+            icon: icon.as_deref().and_then(|p| p.extension()).map(|ext| format!("icon.{}", ext.to_string_lossy())),
+        };
+        // Never leave half a bundle under the real name
+        let partial = dest.with_file_name(format!(".{}.partial", dest.file_name().unwrap_or_default().to_string_lossy()));
+        if let Err(e) = bundle::write(&partial, &manifest, &path, icon.as_deref()).and_then(|_| fs::rename(&partial, &dest)) {
+            let _ = fs::remove_file(&partial);
+            return Err(e.to_string());
+        }
+        Ok(dest.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Adds the app in a bundle from `export_app_bundle`, with its name, categories and
+/// icon. `bundle::read` refuses one whose AppImage doesn't match the recorded hash.
+#[tauri::command]
+pub async fn import_app_bundle(app: AppHandle, path: String) -> Result<AppImageEntry, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = Path::new(&path);
+        let needed = fs::metadata(path).map_err(|e| e.to_string())?.len();
+        let tmp = tempfile::Builder::new().prefix("axec-bundle-").tempdir_in(extraction_base(needed)?).map_err(|e| e.to_string())?;
+        let manifest = bundle::read(path, tmp.path()).map_err(|e| e.to_string())?;
+        validate_id(&manifest.id)?;
+        let app_file = tmp.path().join(bundle::APP_FILE);
+        let id = import_appimage(&app_file, Some(&manifest.id), Some(manifest.sha256.clone()), false, &mut |_, _| {})?.id;
+        let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+        let app_meta = meta.entry(&id);
+        if manifest.name.is_some() {
+            app_meta.name = manifest.name.clone();
+        }
+        if !manifest.categories.is_empty() {
+            app_meta.categories = manifest.categories.clone();
+        }
+        app_meta.version = manifest.version.clone();
+        app_meta.source = manifest.source.clone();
+        let dir = app_dir(&storage, app_meta);
+        meta.save().map_err(|e| e.to_string())?;
+        // The bundled icon may be one the user picked; the extracted hash stays recorded, so it counts as theirs
+        if let Some(icon) = bundle::icon_path(tmp.path(), &manifest) {
+            let ext = icon.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+            for old in icons::stored_icons(&dir, &id) {
+                let _ = fs::remove_file(old);
+            }
+            fs::copy(&icon, dir.join(format!("{id}.{ext}"))).map_err(|e| e.to_string())?;
+        }
+        if !in_flatpak_sandbox() {
+            write_app_desktop(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())?;
+            refresh_desktop_database(&apps_dir);
+        }
+        let _ = tray::refresh(&app);
+        let stored = installed_appimage(&storage, &meta, &id)?;
+        app_entry(&storage, &apps_dir, &stored, &meta).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
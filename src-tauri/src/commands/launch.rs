@@ -0,0 +1,251 @@
+//! Commands that start, watch and stop apps, and the per-app launch settings.
+
+use crate::{
+    app_log_path, ensure_dirs, extracted_dir, launch,
+    process::{self, Supervisor},
+    spawn_app, spawn_app_with, start_app, storage, validate_id,
+};
+use serde::Serialize;
+use std::{fs, io, os::unix::process::ExitStatusExt, path::Path, time::Duration};
+use super::{rewrite_entries, with_app_meta};
+use tauri::{AppHandle, Manager, State};
+
+/// What `clear_extract_cache` freed, and the apps it skipped because they're running
+/// from their cached tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractCacheCleared {
+    pub bytes_reclaimed: u64,
+    pub skipped_running: Vec<String>,
+}
+
+/// Deletes `id`'s cached extract-and-run tree, or every app's without an id, so the next
+/// launch unpacks afresh. Apps Axec is running are left alone.
+#[tauri::command]
+pub fn clear_extract_cache(id: Option<String>, supervisor: State<Supervisor>) -> Result<ExtractCacheCleared, String> {
+    let storage = ensure_dirs().map_err(|e| e.to_string())?.0;
+    let root = extracted_dir(&storage);
+    let ids = match id {
+        Some(id) => {
+            validate_id(&id)?;
+            vec![id]
+        }
+        None => fs::read_dir(&root).map(|rd| rd.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect()).unwrap_or_default(),
+    };
+    let mut cleared = ExtractCacheCleared { bytes_reclaimed: 0, skipped_running: Vec::new() };
+    for id in ids {
+        let dir = root.join(&id);
+        if !dir.is_dir() {
+            continue;
+        }
+        if supervisor.is_running(&id) {
+            cleared.skipped_running.push(id);
+            continue;
+        }
+        let bytes = storage::usage(&dir);
+        fs::remove_dir_all(&dir).map_err(|e| format!("Couldn't remove {}: {e}", dir.display()))?;
+        cleared.bytes_reclaimed += bytes;
+    }
+    Ok(cleared)
+}
+
+// Async since the launch watches the app for a moment before returning
+#[tauri::command]
+pub async fn launch_app(app: AppHandle, id: String, args: Option<Vec<String>>) -> Result<(), launch::LaunchError> {
+    tauri::async_runtime::spawn_blocking(move || start_app(&app, &app.state::<Supervisor>(), &id, &args.unwrap_or_default()))
+        .await
+        .map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?
+}
+
+/// Runs the app to completion and returns its exit code (`128 + signal` if it was
+/// killed), for scripted use. It isn't tracked, so `kill_app` can't stop it.
+#[tauri::command]
+pub async fn launch_and_wait(id: String, args: Option<Vec<String>>) -> Result<i32, launch::LaunchError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let child = match spawn_app(&id, &args.unwrap_or_default()) {
+            Ok((child, _, _)) => child,
+            // A plain non-zero exit is a result here, not a launch failure
+            Err(launch::LaunchError::ExitedEarly { code: Some(code), .. }) => return Ok(code),
+            Err(e) => return Err(e),
+        };
+        let Some(mut child) = child else { return Ok(0) };
+        let status = child.wait().map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?;
+        Ok(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)))
+    })
+    .await
+    .map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?
+}
+
+/// How long `test_launch` lets an app run before calling it working.
+const TEST_LAUNCH_WINDOW: Duration = Duration::from_secs(3);
+
+/// Tail of the log `test_launch` reports back.
+const TEST_LAUNCH_OUTPUT: u64 = 16 << 10;
+
+/// Tail of an app's log `get_app_log` returns by default, and the most it returns.
+const APP_LOG_TAIL: u64 = 64 << 10;
+
+const MAX_APP_LOG_TAIL: u64 = 4 << 20;
+
+/// What `test_launch` saw.
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchTestResult {
+    /// Still running at the end of the window, or exited cleanly before it.
+    pub ok: bool,
+    /// Exit code, when it exited within the window by itself.
+    pub code: Option<i32>,
+    /// The end of what it printed to stderr and stdout.
+    pub output: String,
+    /// Why it never got going, for failures the launcher itself diagnosed.
+    pub error: Option<launch::LaunchError>,
+}
+
+fn log_tail(path: &Path, max: u64) -> String {
+    use io::{Read, Seek};
+    let Ok(mut file) = fs::File::open(path) else { return String::new() };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let _ = file.seek(io::SeekFrom::Start(len.saturating_sub(max)));
+    let mut data = Vec::new();
+    let _ = file.read_to_end(&mut data);
+    String::from_utf8_lossy(&data).trim().to_string()
+}
+
+/// Starts `id` untracked, watches it for `TEST_LAUNCH_WINDOW` and stops it again, to
+/// check it runs on this system at all.
+#[tauri::command]
+pub async fn test_launch(id: String) -> Result<LaunchTestResult, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let log_path = app_log_path(&id).map_err(|e| e.to_string())?;
+        let output = || log_tail(&log_path, TEST_LAUNCH_OUTPUT);
+        match spawn_app_with(&id, &[], Some(TEST_LAUNCH_WINDOW)) {
+            // Lasted the whole window
+            Ok((Some(_), ..)) => Ok(LaunchTestResult { ok: true, code: None, output: output(), error: None }),
+            // Exiting cleanly this fast usually means it handed off to an instance already open
+            Ok((None, ..)) => Ok(LaunchTestResult { ok: true, code: Some(0), output: output(), error: None }),
+            Err(launch::LaunchError::ExitedEarly { code, stderr }) => Ok(LaunchTestResult { ok: false, code, output: stderr, error: None }),
+            Err(launch::LaunchError::NotFound { .. }) => Err("AppImage not found".into()),
+            Err(e) => Ok(LaunchTestResult { ok: false, code: None, output: String::new(), error: Some(e) }),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn list_running(supervisor: State<Supervisor>) -> Vec<process::RunningApp> {
+    supervisor.running()
+}
+
+/// The end of what `id` printed on its last tracked launch, at most `max_bytes` of it.
+#[tauri::command]
+pub fn get_app_log(id: String, max_bytes: Option<u64>) -> Result<String, String> {
+    validate_id(&id)?;
+    let path = app_log_path(&id).map_err(|e| e.to_string())?;
+    Ok(log_tail(&path, max_bytes.unwrap_or(APP_LOG_TAIL).min(MAX_APP_LOG_TAIL)))
+}
+
+/// Memory and CPU time of a launched app and the helpers in its process group.
+#[tauri::command]
+pub fn app_usage(supervisor: State<Supervisor>, id: String) -> Result<process::ResourceUsage, String> {
+    validate_id(&id)?;
+    Ok(supervisor.usage(&id))
+}
+
+/// Freezes a running app with `SIGSTOP` without closing it.
+#[tauri::command]
+pub fn pause_app(supervisor: State<Supervisor>, id: String) -> Result<(), String> {
+    validate_id(&id)?;
+    supervisor.set_paused(&id, true)
+}
+
+#[tauri::command]
+pub fn resume_app(supervisor: State<Supervisor>, id: String) -> Result<(), String> {
+    validate_id(&id)?;
+    supervisor.set_paused(&id, false)
+}
+
+// Async since stopping waits for the app to exit
+#[tauri::command]
+pub async fn kill_app(app: AppHandle, id: String) -> Result<(), String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || app.state::<Supervisor>().kill(&id)).await.map_err(|e| e.to_string())?
+}
+
+/// Stops a tracked instance, waiting for it to exit so it releases its lock files, then launches afresh.
+#[tauri::command]
+pub async fn relaunch_app(app: AppHandle, id: String) -> Result<(), launch::LaunchError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let supervisor = app.state::<Supervisor>();
+        // Keep the per-call args the old instance was started with
+        let extra_args = supervisor.running().into_iter().find(|r| r.id == id).map(|r| r.args).unwrap_or_default();
+        if supervisor.is_running(&id) {
+            supervisor.kill(&id).map_err(|message| launch::LaunchError::SpawnFailed { message })?;
+        }
+        start_app(&app, &supervisor, &id, &extra_args)
+    })
+    .await
+    .map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?
+}
+
+#[tauri::command]
+pub fn set_launch_args(id: String, args: Vec<String>) -> Result<(), String> {
+    with_app_meta(&id, |m| m.default_args = args)?;
+    rewrite_entries(&id, false)
+}
+
+/// Sets the HiDPI scale `id` launches with, or clears it with `None`.
+#[tauri::command]
+pub fn set_scale_factor(id: String, scale_factor: Option<f32>) -> Result<(), String> {
+    if let Some(scale) = scale_factor {
+        launch::validate_scale_factor(scale)?;
+    }
+    with_app_meta(&id, |m| m.scale_factor = scale_factor)
+}
+
+/// Sets whether the app launches in a systemd scope (`None` follows the setting) and the
+/// scope's limits; `None` limits are cleared. Applies from the next launch.
+#[tauri::command]
+pub fn set_resource_limits(id: String, systemd_scope: Option<bool>, memory_max: Option<String>, cpu_quota: Option<String>) -> Result<(), String> {
+    let memory_max = memory_max.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+    let cpu_quota = cpu_quota.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    memory_max.as_deref().map(launch::validate_memory_max).transpose()?;
+    cpu_quota.as_deref().map(launch::validate_cpu_quota).transpose()?;
+    with_app_meta(&id, |m| {
+        m.systemd_scope = systemd_scope;
+        m.memory_max = memory_max;
+        m.cpu_quota = cpu_quota;
+    })
+}
+
+/// Sets whether the app is relaunched when it exits non-zero or dies from a signal.
+#[tauri::command]
+pub fn set_auto_restart(id: String, auto_restart: bool) -> Result<(), String> {
+    with_app_meta(&id, |m| m.auto_restart = auto_restart)
+}
+
+/// Picks how the app is mounted at launch; takes effect from the next launch.
+#[tauri::command]
+pub fn set_launch_mode(id: String, mode: launch::LaunchMode) -> Result<(), String> {
+    with_app_meta(&id, |m| m.launch_mode = mode)?;
+    if mode != launch::LaunchMode::ExtractAndRun {
+        let (storage, _) = ensure_dirs().map_err(|e| e.to_string())?;
+        let _ = fs::remove_dir_all(extracted_dir(&storage).join(&id));
+    }
+    Ok(())
+}
+
+/// Sets or (with `None`) clears the app's exec wrapper template.
+#[tauri::command]
+pub fn set_exec_wrapper(id: String, wrapper: Option<String>) -> Result<(), String> {
+    let wrapper = wrapper.map(|w| w.trim().to_string()).filter(|w| !w.is_empty());
+    if let Some(template) = &wrapper {
+        launch::split_wrapper(template)?;
+    }
+    with_app_meta(&id, |m| m.exec_wrapper = wrapper)?;
+    rewrite_entries(&id, false)
+}
+
+#[tauri::command]
+pub fn clear_launch_args(id: String) -> Result<(), String> {
+    set_launch_args(id, Vec::new())
+}
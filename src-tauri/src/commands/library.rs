@@ -0,0 +1,408 @@
+//! Commands for listing the library and organising the apps in it.
+
+use crate::{
+    app_dir, app_dirs, app_entry, app_file_candidates, app_file_mode, app_id_for, appimage, desktop,
+    download, ensure_dirs, extract_embedded, extract_metadata_tree, extraction_tempdir,
+    find_app_icon, find_appimage, icons, in_flatpak_sandbox, is_executable, license,
+    make_executable, metadata, normalize_app_file, normalized_name, parse_appimage_name, plan,
+    refresh_desktop_database, screenshots_dir, settings, tray, validate_id, write_app_desktop,
+    write_app_desktop_tree, AppImageEntry, SortKey,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use super::{installed_appimage, with_app_meta};
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+/// The little `list_app_summaries` returns per app, enough to draw the list before the
+/// full entries arrive.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppSummary {
+    pub id: String,
+    pub name: String,
+    pub icon_path: Option<String>,
+}
+
+/// A stored file `list_apps` skipped because it couldn't be read.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanWarning {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppList {
+    pub apps: Vec<AppImageEntry>,
+    pub warnings: Vec<ScanWarning>,
+    /// Apps in the whole library, when `apps` is one page of it.
+    pub total: usize,
+}
+
+/// Sorts the whole library, then returns the `offset`/`limit` page of it.
+#[tauri::command]
+pub fn list_apps(sort: Option<SortKey>, offset: Option<usize>, limit: Option<usize>) -> Result<AppList, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let meta = metadata::load();
+    let mut result = Vec::new();
+    let mut warnings = Vec::new();
+    // One unreadable file becomes a warning instead of failing the whole list
+    for entry in fs::read_dir(&storage).map_err(|e| e.to_string())? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warnings.push(ScanWarning { path: storage.to_string_lossy().to_string(), message: e.to_string() });
+                continue;
+            }
+        };
+        let p = entry.path();
+        if let Some(ext) = p.extension().and_then(|s| s.to_str()) {
+            let ext_l = ext.to_ascii_lowercase();
+            if ext_l == "appimage" {
+                match app_entry(&storage, &apps_dir, &p, &meta) {
+                    Ok(app) => result.push(app),
+                    Err(e) => warnings.push(ScanWarning { path: p.to_string_lossy().to_string(), message: e.to_string() }),
+                }
+            }
+        }
+    }
+    // Apps moved into subfolders aren't in the scan above
+    for id in meta.apps.iter().filter(|(_, m)| m.subdir.is_some()).map(|(id, _)| id) {
+        let Some(p) = find_appimage(&storage, &meta, id) else { continue };
+        match app_entry(&storage, &apps_dir, &p, &meta) {
+            Ok(app) => result.push(app),
+            Err(e) => warnings.push(ScanWarning { path: p.to_string_lossy().to_string(), message: e.to_string() }),
+        }
+    }
+    // The id breaks name ties so pages never overlap or skip an app
+    result.sort_by_cached_key(|a| (a.name.to_lowercase(), a.id.clone()));
+    if let SortKey::Manual = sort.unwrap_or_default() {
+        // Stable sort keeps unordered apps alphabetical behind the ordered ones
+        result.sort_by_key(|a| a.order.unwrap_or(u32::MAX));
+    }
+    let total = result.len();
+    let apps = result.into_iter().skip(offset.unwrap_or(0)).take(limit.unwrap_or(usize::MAX)).collect();
+    Ok(AppList { apps, warnings, total })
+}
+
+/// Ids and names of every app, sorted like `list_apps`, without reading the AppImages
+/// themselves; `get_app` fills in the rest.
+#[tauri::command]
+pub fn list_app_summaries() -> Result<Vec<AppSummary>, String> {
+    let (storage, _) = ensure_dirs().map_err(|e| e.to_string())?;
+    let meta = metadata::load();
+    let is_appimage = |p: &Path| p.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("appimage"));
+    let mut paths: Vec<PathBuf> = fs::read_dir(&storage).map_err(|e| e.to_string())?.flatten().map(|e| e.path()).filter(|p| is_appimage(p)).collect();
+    paths.extend(meta.apps.iter().filter(|(_, m)| m.subdir.is_some()).filter_map(|(id, _)| find_appimage(&storage, &meta, id)));
+    let mut result: Vec<AppSummary> = paths
+        .iter()
+        .map(|p| {
+            let id = app_id_for(p, &meta);
+            let name = meta.get(&id).name.unwrap_or_else(|| parse_appimage_name(p));
+            let icon_path = find_app_icon(&storage, &meta, &id).map(|p| p.to_string_lossy().to_string());
+            AppSummary { id, name, icon_path }
+        })
+        .collect();
+    result.sort_by_cached_key(|a| (a.name.to_lowercase(), a.id.clone()));
+    Ok(result)
+}
+
+/// The full entry for one app, as `list_apps` would return it.
+#[tauri::command]
+pub fn get_app(id: String) -> Result<AppImageEntry, String> {
+    validate_id(&id)?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), &id)?;
+    app_entry(&storage, &apps_dir, &path, &metadata::load()).map_err(|e| e.to_string())
+}
+
+/// The `limit` most recently added apps, newest first.
+#[tauri::command]
+pub fn list_recent(limit: usize) -> Result<Vec<AppImageEntry>, String> {
+    let mut apps = list_apps(None, None, None)?.apps;
+    apps.retain(|a| a.added_at.is_some());
+    apps.sort_by_key(|a| std::cmp::Reverse(a.added_at));
+    apps.truncate(limit);
+    Ok(apps)
+}
+
+/// Fixes what drifts on a stored app: a non-canonical file name, a lost executable bit,
+/// a missing icon, and a stale desktop entry.
+#[tauri::command]
+pub fn repair_app(id: String, dry_run: Option<bool>) -> Result<plan::DryRun<AppImageEntry>, String> {
+    validate_id(&id)?;
+    if dry_run.unwrap_or(false) {
+        return plan_repair(&id).map(plan::DryRun::Planned);
+    }
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let meta = metadata::load();
+    let found = installed_appimage(&storage, &meta, &id)?;
+    let path = normalize_app_file(&storage, &apps_dir, &found, &meta);
+    if !is_executable(&path) {
+        make_executable(&path, app_file_mode(&settings::load(), &meta.get(&id))).map_err(|e| e.to_string())?;
+    }
+    if find_app_icon(&storage, &meta, &id).is_none() {
+        let app_type = meta.get(&id).app_type.unwrap_or(appimage::AppImageType::Unknown);
+        extract_embedded(&path, &app_dir(&storage, &meta.get(&id)), &id, app_type);
+    }
+    if !in_flatpak_sandbox() {
+        write_app_desktop(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())?;
+        refresh_desktop_database(&apps_dir);
+    }
+    app_entry(&storage, &apps_dir, &path, &meta).map(plan::DryRun::Applied).map_err(|e| e.to_string())
+}
+
+/// The steps `repair_app` would take.
+fn plan_repair(id: &str) -> Result<plan::Plan, String> {
+    let (storage, apps_dir) = app_dirs().map_err(|e| e.to_string())?;
+    let found = installed_appimage(&storage, &metadata::load(), id)?;
+    let mut out = plan::Plan::default();
+    let path = match normalized_name(&storage, &found, &metadata::load()) {
+        Some(canonical) => {
+            out.push(plan::Op::Rename, &canonical, Some(&found), 0);
+            canonical
+        }
+        None => found.clone(),
+    };
+    if !is_executable(&found) {
+        out.push(plan::Op::Chmod, &path, None, 0);
+    }
+    if find_app_icon(&storage, &metadata::load(), id).is_none() {
+        out.push(plan::Op::Extract, &storage, Some(&path), 0);
+    }
+    if !in_flatpak_sandbox() {
+        out.push(plan::Op::Write, &apps_dir.join(desktop::entry_file_name(id)), None, 0);
+    }
+    Ok(out)
+}
+
+/// Cheap membership check; any IO problem just reads as "not there".
+#[tauri::command]
+pub fn app_exists(id: String) -> bool {
+    validate_id(&id).is_ok() && ensure_dirs().is_ok_and(|(storage, _)| find_appimage(&storage, &metadata::load(), &id).is_some())
+}
+
+#[tauri::command]
+pub fn set_order(ids: Vec<String>) -> Result<(), String> {
+    ids.iter().try_for_each(|id| validate_id(id))?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    if let Some(missing) = ids.iter().find(|id| find_appimage(&storage, &metadata::load(), id).is_none()) {
+        return Err(format!("App not found: {missing}"));
+    }
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    for app in meta.apps.values_mut() {
+        app.order = None;
+    }
+    for (i, id) in ids.iter().enumerate() {
+        meta.entry(id).order = Some(i as u32);
+    }
+    meta.save().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_pinned(app: AppHandle, id: String, pinned: bool) -> Result<(), String> {
+    with_app_meta(&id, |m| m.pinned = pinned)?;
+    tray::refresh(&app).map_err(|e| e.to_string())
+}
+
+/// Replaces the user's notes on `id`; an empty string clears them.
+#[tauri::command]
+pub fn set_notes(id: String, text: String) -> Result<(), String> {
+    with_app_meta(&id, |m| m.notes = text)
+}
+
+/// Top-level storage folders Axec keeps for itself.
+const RESERVED_STORAGE_DIRS: [&str; 2] = ["blobs", "extracted"];
+
+/// Checks a `move_app` folder: relative, plain names only, and clear of Axec's own folders.
+fn validate_subdir(subdir: &str) -> Result<(), String> {
+    let path = Path::new(subdir);
+    let plain = path.components().all(|c| matches!(c, std::path::Component::Normal(n) if !n.to_string_lossy().starts_with('.')));
+    if !plain || path.components().next().is_none() {
+        return Err(format!("{subdir:?} must be a relative folder path without . or .. parts"));
+    }
+    if path.components().next().is_some_and(|c| RESERVED_STORAGE_DIRS.iter().any(|r| c.as_os_str() == *r)) {
+        return Err(format!("{subdir:?} is reserved for Axec's own files"));
+    }
+    Ok(())
+}
+
+/// Moves `id`'s AppImage and icon into `storage/{subdir}`, or back to the top with an
+/// empty `subdir`, and points its desktop entry at the new place.
+#[tauri::command]
+pub fn move_app(id: String, subdir: String) -> Result<AppImageEntry, String> {
+    validate_id(&id)?;
+    let subdir = subdir.trim().trim_matches('/').to_string();
+    let subdir = (!subdir.is_empty()).then_some(subdir);
+    subdir.as_deref().map(validate_subdir).transpose()?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let app_meta = meta.get(&id);
+    let from_dir = app_dir(&storage, &app_meta);
+    let path = app_file_candidates(&storage, &id, &app_meta).into_iter().find(|p| fs::symlink_metadata(p).is_ok()).ok_or("AppImage not found")?;
+    let to_dir = app_dir(&storage, &metadata::AppMeta { subdir: subdir.clone(), ..Default::default() });
+    if to_dir == from_dir {
+        return app_entry(&storage, &apps_dir, &path, &meta).map_err(|e| e.to_string());
+    }
+    let icons = icons::stored_icons(&from_dir, &id);
+    let moves: Vec<(PathBuf, PathBuf)> = std::iter::once(path).chain(icons).map(|p| (p.clone(), to_dir.join(p.file_name().unwrap()))).collect();
+    if let Some((_, taken)) = moves.iter().find(|(_, to)| fs::symlink_metadata(to).is_ok()) {
+        return Err(format!("{} already exists", taken.display()));
+    }
+    fs::create_dir_all(&to_dir).map_err(|e| e.to_string())?;
+    for (from, to) in &moves {
+        fs::rename(from, to).map_err(|e| e.to_string())?;
+    }
+    if from_dir != storage {
+        let _ = fs::remove_dir(&from_dir);
+    }
+    meta.entry(&id).subdir = subdir;
+    meta.save().map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        write_app_desktop(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())?;
+        refresh_desktop_database(&apps_dir);
+    }
+    app_entry(&storage, &apps_dir, &moves[0].1, &meta).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn refresh_tray(app: AppHandle) -> Result<(), String> {
+    tray::refresh(&app).map_err(|e| e.to_string())
+}
+
+/// Puts the stored AppImage's full path on the clipboard, for pasting into a terminal.
+#[tauri::command]
+pub fn copy_path_to_clipboard(app: AppHandle, id: String) -> Result<String, String> {
+    validate_id(&id)?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), &id)?.to_string_lossy().to_string();
+    app.clipboard().write_text(path.clone()).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Text of the license file `id` bundles, or `None` when it ships without one.
+#[tauri::command]
+pub async fn get_license_text(id: String) -> Result<Option<String>, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let path = installed_appimage(&storage, &metadata::load(), &id)?;
+        let app_type = metadata::load().get(&id).app_type.unwrap_or(appimage::AppImageType::Unknown);
+        let tmp_dir = extraction_tempdir(&path)?;
+        let Some(squash_root) = extract_metadata_tree(&path, tmp_dir.path(), app_type, settings::load().safe_extraction) else {
+            return Err("Could not read the AppImage's contents".into());
+        };
+        Ok(license::find_file(&squash_root).and_then(|p| license::read_text(&p)))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Links `id` to `parent` (or unlinks it with `None`), so it inherits the parent's
+/// categories wherever it has none of its own.
+#[tauri::command]
+pub fn set_parent(id: String, parent: Option<String>) -> Result<AppImageEntry, String> {
+    validate_id(&id)?;
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), &id)?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    if let Some(parent) = &parent {
+        validate_id(parent)?;
+        if find_appimage(&storage, &meta, parent).is_none() {
+            return Err(format!("App not found: {parent}"));
+        }
+        if *parent == id || meta.descendants(&id).contains(parent) {
+            return Err(format!("{parent} already inherits from {id}"));
+        }
+    }
+    meta.entry(&id).parent = parent;
+    meta.save().map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        write_app_desktop_tree(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())?;
+        refresh_desktop_database(&apps_dir);
+    }
+    app_entry(&storage, &apps_dir, &path, &meta).map_err(|e| e.to_string())
+}
+
+/// Screenshots beyond this many aren't fetched.
+const MAX_SCREENSHOTS: usize = 8;
+
+/// Larger screenshot files are skipped.
+const MAX_SCREENSHOT_BYTES: u64 = 10 << 20;
+
+/// Downloads the app's AppStream screenshots once and returns their local paths, in
+/// metadata order. Ones that fail or are too big are left out; no screenshots is an empty list.
+#[tauri::command]
+pub async fn fetch_screenshots(id: String) -> Result<Vec<String>, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let urls = metadata::load().get(&id).screenshots;
+        let dir = screenshots_dir().map_err(|e| e.to_string())?.join(&id);
+        cache_screenshots(&dir, &urls, &mut |url, name| download::download(url, &[], &dir, Some(name), Some(MAX_SCREENSHOT_BYTES)).is_ok())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Cache file name for a screenshot: a hash of its URL, so a changed list never shows a
+/// file that was fetched for another one.
+fn screenshot_file_name(url: &str) -> String {
+    let ext = Path::new(url.split(['?', '#']).next().unwrap_or_default()).extension().and_then(|e| e.to_str()).filter(|e| e.len() <= 4).unwrap_or("png");
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    format!("{}.{}", &hash[..16], ext.to_ascii_lowercase())
+}
+
+/// Makes `dir` hold the screenshots at `urls`, calling `fetch(url, name)` to download the
+/// ones it doesn't have yet into `dir`, and deletes ones no longer listed.
+fn cache_screenshots(dir: &Path, urls: &[String], fetch: &mut dyn FnMut(&str, &str) -> bool) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    let mut paths = Vec::new();
+    for url in urls.iter().take(MAX_SCREENSHOTS) {
+        let name = screenshot_file_name(url);
+        let cached = dir.join(&name);
+        names.push(name.clone());
+        if !cached.exists() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            if !fetch(url, &name) {
+                let _ = fs::remove_file(&cached);
+                continue;
+            }
+        }
+        paths.push(cached.to_string_lossy().to_string());
+    }
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        if !entry.file_name().to_str().is_some_and(|n| names.iter().any(|name| name == n)) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screenshot_cache_follows_changes_to_the_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let urls = |list: &[&str]| list.iter().map(|u| u.to_string()).collect::<Vec<_>>();
+        let mut fetched = Vec::new();
+        let mut fetch = |url: &str, name: &str| {
+            fetched.push(url.to_string());
+            fs::write(dir.path().join(name), url).is_ok()
+        };
+        let first = cache_screenshots(dir.path(), &urls(&["https://a.example/1.png", "https://a.example/2.png"]), &mut fetch).unwrap();
+        assert_eq!(first.len(), 2);
+
+        // The second screenshot is replaced: only the new one is fetched and the old file goes
+        let second = cache_screenshots(dir.path(), &urls(&["https://a.example/1.png", "https://a.example/3.jpg?w=800"]), &mut fetch).unwrap();
+        assert_eq!(second[0], first[0]);
+        assert_eq!(fs::read_to_string(&second[1]).unwrap(), "https://a.example/3.jpg?w=800");
+        assert!(second[1].ends_with(".jpg"));
+        assert!(!Path::new(&first[1]).exists());
+        assert_eq!(fetched, ["https://a.example/1.png", "https://a.example/2.png", "https://a.example/3.jpg?w=800"]);
+    }
+}
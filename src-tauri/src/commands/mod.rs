@@ -0,0 +1,54 @@
+//! The Tauri commands the frontend invokes, grouped by what they act on. The shared
+//! machinery they build on (storage layout, importing, desktop entries) is in the crate root.
+
+pub mod desktop;
+pub mod icons;
+pub mod import;
+pub mod launch;
+pub mod library;
+pub mod remove;
+pub mod settings;
+pub mod storage;
+pub mod updates;
+
+use crate::{
+    ensure_dirs, find_appimage, in_flatpak_sandbox, metadata, refresh_desktop_database, validate_id,
+    write_app_desktop, write_app_desktop_tree,
+};
+use std::path::{Path, PathBuf};
+
+/// `id`'s stored AppImage, or the error commands report for apps that aren't installed.
+fn installed_appimage(storage: &Path, meta: &metadata::Metadata, id: &str) -> Result<PathBuf, String> {
+    find_appimage(storage, meta, id).ok_or_else(|| "AppImage not found".to_string())
+}
+
+/// Runs `edit` on installed app `id`'s metadata under the write lock and saves the result.
+fn with_app_meta<T>(id: &str, edit: impl FnOnce(&mut metadata::AppMeta) -> T) -> Result<T, String> {
+    validate_id(id)?;
+    let (storage, _) = ensure_dirs().map_err(|e| e.to_string())?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    installed_appimage(&storage, &meta, id)?;
+    let edited = edit(meta.entry(id));
+    meta.save().map_err(|e| e.to_string())?;
+    Ok(edited)
+}
+
+/// Rewrites `id`'s desktop entry from its saved metadata after a `with_app_meta` edit,
+/// along with those of the apps inheriting from it when `with_children` is set. Nothing
+/// to do inside Flatpak, where the portal owns the entries.
+fn rewrite_entries(id: &str, with_children: bool) -> Result<(), String> {
+    if in_flatpak_sandbox() {
+        return Ok(());
+    }
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    // Held so a concurrent edit can't be overwritten by an entry from older metadata
+    let meta = metadata::lock().map_err(|e| e.to_string())?;
+    if with_children {
+        write_app_desktop_tree(&storage, &apps_dir, id, &meta)
+    } else {
+        write_app_desktop(&storage, &apps_dir, id, &meta).map(|_| ())
+    }
+    .map_err(|e| e.to_string())?;
+    refresh_desktop_database(&apps_dir);
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Commands that remove apps, to the trash where possible, and bring them back.
+
+use crate::{
+    app_dir, app_dirs, app_entry, app_file_candidates, desktop, ensure_dirs, find_appimage, icons,
+    in_flatpak_sandbox, is_thumbnail_of, metadata, plan, refresh_desktop_database,
+    remove_stored_app, seal_app_file, settings, storage, thumbnails_dir, trash, tray,
+    user_icons_dir, validate_id, write_app_desktop, AppImageEntry,
+};
+use serde::Serialize;
+use std::{fs, path::Path, sync::Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// The most recent trash removal, so `undo_last_remove` can bring it back.
+#[derive(Default)]
+pub struct UndoSlot(Mutex<Option<trash::TrashedApp>>);
+
+/// Copy progress `remove_app` emits while moving a file to a trash on another filesystem.
+#[derive(Debug, Clone, Serialize)]
+struct RemoveProgress {
+    id: String,
+    file: String,
+    copied: u64,
+    total: u64,
+}
+
+// Async so the (possibly long) trash copy runs off the main thread
+/// With `dry_run`, returns the files removal would move or delete instead.
+#[tauri::command]
+pub async fn remove_app(app: AppHandle, id: String, to_trash: Option<bool>, dry_run: Option<bool>) -> Result<plan::DryRun<()>, String> {
+    validate_id(&id)?;
+    let to_trash = to_trash.unwrap_or(false);
+    if dry_run.unwrap_or(false) {
+        return plan_remove(&id, to_trash).map(plan::DryRun::Planned);
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        remove_app_blocking(&app, id, to_trash)?;
+        let _ = tray::refresh(&app);
+        Ok(plan::DryRun::Applied(()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// The steps `remove_app_blocking` would take.
+fn plan_remove(id: &str, to_trash: bool) -> Result<plan::Plan, String> {
+    let (storage, apps_dir) = app_dirs().map_err(|e| e.to_string())?;
+    let trash_dir = trash::trash_root().map_err(|e| e.to_string())?.join(id);
+    let meta = metadata::load();
+    let sha256 = meta.get(id).sha256;
+    let last_user = sha256.as_ref().is_some_and(|sha| meta.references(sha) <= 1);
+    let mut out = plan::Plan::default();
+    let dispose = |out: &mut plan::Plan, p: &Path| {
+        if to_trash {
+            out.push(plan::Op::Trash, &trash_dir.join(p.file_name().unwrap_or_default()), Some(p), plan::move_cost(p, &trash_dir));
+        } else {
+            out.push(plan::Op::Remove, p, None, 0);
+        }
+    };
+    let mut found = false;
+    for p in app_file_candidates(&storage, id, &meta.get(id)) {
+        if fs::symlink_metadata(&p).is_err() {
+            continue;
+        }
+        found = true;
+        match storage::blob_for(&storage, &p) {
+            Some(blob) if last_user && to_trash => {
+                out.push(plan::Op::Trash, &trash_dir.join(p.file_name().unwrap()), Some(&blob), plan::move_cost(&blob, &trash_dir));
+                out.push(plan::Op::Remove, &p, None, 0);
+            }
+            Some(blob) if last_user => {
+                out.push(plan::Op::Remove, &p, None, 0);
+                out.push(plan::Op::Remove, &blob, None, 0);
+            }
+            _ => dispose(&mut out, &p),
+        }
+    }
+    let dir = app_dir(&storage, &meta.get(id));
+    for p in icons::stored_icons(&dir, id) {
+        dispose(&mut out, &p);
+    }
+    if let Ok(rd) = user_icons_dir().and_then(|root| fs::read_dir(root.join("hicolor"))) {
+        for size_dir in rd.flatten() {
+            for p in ["png", "svg"].iter().map(|ext| size_dir.path().join(format!("apps/axec-{id}.{ext}"))).filter(|p| p.exists()) {
+                out.push(plan::Op::Remove, &p, None, 0);
+            }
+        }
+    }
+    if let Ok(rd) = thumbnails_dir().and_then(fs::read_dir) {
+        for e in rd.flatten().filter(|e| e.file_name().to_str().is_some_and(|n| is_thumbnail_of(n, id))) {
+            out.push(plan::Op::Remove, &e.path(), None, 0);
+        }
+    }
+    let desktop = apps_dir.join(desktop::entry_file_name(id));
+    if !in_flatpak_sandbox() && desktop.exists() {
+        dispose(&mut out, &desktop);
+        found = true;
+    }
+    if !found {
+        return Err("App not found".into());
+    }
+    if meta.apps.contains_key(id) {
+        if to_trash {
+            out.push(plan::Op::Write, &trash_dir.join(trash::META_FILE), None, 0);
+        }
+        out.push(plan::Op::Write, &metadata::metadata_path().map_err(|e| e.to_string())?, None, 0);
+    }
+    Ok(out)
+}
+
+fn remove_app_blocking(app: &AppHandle, id: String, to_trash: bool) -> Result<(), String> {
+    let on_progress = |file: &str, copied, total| {
+        let _ = app.emit("axec://remove-progress", RemoveProgress { id: id.clone(), file: file.to_string(), copied, total });
+    };
+    let trashed = remove_stored_app(&id, to_trash, &on_progress)?;
+    // Any removal replaces the undo slot; only trash removals can be undone
+    *app.state::<UndoSlot>().0.lock().unwrap() = trashed;
+    Ok(())
+}
+
+/// Progress `batch_remove` emits after each app.
+#[derive(Debug, Clone, Serialize)]
+struct BatchRemoveProgress {
+    id: String,
+    done: usize,
+    total: usize,
+    error: Option<String>,
+}
+
+/// Removes several apps, continuing past failures, and returns each id's result. Shared
+/// binaries go once their last user does; `undo_last_remove` brings back the last one trashed.
+#[tauri::command]
+pub async fn batch_remove(app: AppHandle, ids: Vec<String>, to_trash: bool) -> Result<Vec<(String, Result<(), String>)>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut results = Vec::with_capacity(ids.len());
+        for (i, id) in ids.iter().enumerate() {
+            let result = validate_id(id).and_then(|()| remove_app_blocking(&app, id.clone(), to_trash));
+            let _ = app.emit("axec://batch-remove-progress", BatchRemoveProgress { id: id.clone(), done: i + 1, total: ids.len(), error: result.clone().err() });
+            results.push((id.clone(), result));
+        }
+        if let Ok((_, apps_dir)) = app_dirs() {
+            if !in_flatpak_sandbox() {
+                refresh_desktop_database(&apps_dir);
+            }
+        }
+        let _ = tray::refresh(&app);
+        Ok(results)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn restore_from_trash(app: &AppHandle, id: &str, dir: &Path) -> Result<AppImageEntry, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    if find_appimage(&storage, &metadata::load(), id).is_some() {
+        return Err(format!("An app with id {id} already exists"));
+    }
+    let app_meta = trash::restore(dir, &storage, &apps_dir).map_err(|e| e.to_string())?;
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    meta.apps.insert(id.to_string(), app_meta);
+    meta.save().map_err(|e| e.to_string())?;
+    let path = find_appimage(&storage, &meta, id).ok_or("Trashed app had no AppImage")?;
+    seal_app_file(&storage, &path, &settings::load()).map_err(|e| e.to_string())?;
+    if !in_flatpak_sandbox() {
+        // Regenerating reinstalls theme icons, which removal deleted
+        let _ = write_app_desktop(&storage, &apps_dir, id, &meta);
+        // Entries trashed under the old naming come back under it
+        let _ = fs::remove_file(apps_dir.join(desktop::legacy_entry_file_name(id)));
+        refresh_desktop_database(&apps_dir);
+    }
+    let _ = tray::refresh(app);
+    app_entry(&storage, &apps_dir, &path, &meta).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn restore_app(app: AppHandle, id: String) -> Result<AppImageEntry, String> {
+    validate_id(&id)?;
+    let dir = trash::find(&id).map_err(|e| e.to_string())?;
+    restore_from_trash(&app, &id, &dir)
+}
+
+#[tauri::command]
+pub fn undo_last_remove(app: AppHandle, undo: State<UndoSlot>) -> Result<AppImageEntry, String> {
+    let mut slot = undo.0.lock().unwrap();
+    let last = slot.clone().ok_or("Nothing to undo")?;
+    let entry = restore_from_trash(&app, &last.id, &last.dir)?;
+    *slot = None;
+    Ok(entry)
+}
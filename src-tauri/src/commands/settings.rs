@@ -0,0 +1,52 @@
+//! Commands for the global settings and the environment checks.
+
+use crate::{
+    appimage,
+    commands::desktop::regenerate_desktop_files,
+    doctor, ensure_dirs, find_appimage, in_flatpak_sandbox, launch, metadata, parse_file_mode,
+    settings::{self, Settings},
+};
+
+/// Checks the host for the usual causes of failed integration or launches.
+#[tauri::command]
+pub fn doctor() -> Result<doctor::DoctorReport, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let mut report = doctor::run(&storage, &apps_dir, in_flatpak_sandbox());
+    let meta = metadata::load();
+    let affected: Vec<String> = meta
+        .apps
+        .iter()
+        .filter(|(_, m)| m.launch_mode != launch::LaunchMode::ExtractAndRun)
+        .filter_map(|(id, m)| {
+            // Apps added before runtimes were recorded get probed now
+            let runtime = m.runtime.clone().or_else(|| appimage::runtime_info(&find_appimage(&storage, &meta, id)?).ok())?;
+            launch::runtime_broken_here(&runtime).then(|| m.name.clone().unwrap_or_else(|| id.clone()))
+        })
+        .collect();
+    report.checks.push(doctor::runtime_check(&affected));
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_settings() -> Settings {
+    settings::load()
+}
+
+#[tauri::command]
+pub fn save_settings(settings: Settings) -> Result<(), String> {
+    if settings.name_decoration.as_ref().is_some_and(|t| !t.contains("{name}")) {
+        return Err("name_decoration must contain {name}".into());
+    }
+    settings.file_mode.as_deref().map(parse_file_mode).transpose()?;
+    let previous = settings::load();
+    settings::save(&settings).map_err(|e| e.to_string())?;
+    // Every entry carries these, so a change means rewriting them all
+    let entries_changed = previous.name_decoration != settings.name_decoration
+        || previous.guess_wm_class != settings.guess_wm_class
+        || previous.icon_format != settings.icon_format
+        || previous.theme_icons != settings.theme_icons;
+    if entries_changed {
+        regenerate_desktop_files()?;
+    }
+    Ok(())
+}
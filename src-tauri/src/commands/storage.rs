@@ -0,0 +1,136 @@
+//! Commands about where and how the AppImages are stored.
+
+use crate::{
+    app_dirs, app_file_mode, appimage, compact, converted_icons_dir, data_dir, ensure_dirs,
+    extract_tree, extraction_base_candidates, in_flatpak_sandbox, logs_dir, make_executable,
+    metadata, owned_file, parse_file_mode, refresh_desktop_database, screenshots_dir,
+    seal_app_file, settings, storage, storage_quota, thumbnails_dir, trash, user_icons_dir,
+    validate_id, LayoutPath, StorageLayout,
+};
+use serde::Serialize;
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use super::installed_appimage;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub used: u64,
+    /// `None` when unlimited.
+    pub quota: Option<u64>,
+    pub remaining: Option<u64>,
+}
+
+#[tauri::command]
+pub fn disk_usage() -> Result<DiskUsage, String> {
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let used = storage::usage(&storage);
+    let quota = storage_quota(&settings::load());
+    Ok(DiskUsage { used, quota, remaining: quota.map(|q| q.saturating_sub(used)) })
+}
+
+/// How often `extract_to` reports how much it has unpacked so far.
+const EXTRACT_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress `extract_to` emits while unpacking; `done` is set on the last one.
+#[derive(Debug, Clone, Serialize)]
+struct ExtractProgress {
+    id: String,
+    extracted_bytes: u64,
+    done: bool,
+}
+
+/// Unpacks `id`'s whole image into `dest/squashfs-root` for browsing, and returns that
+/// path. An existing non-empty `squashfs-root` there is only replaced with `force`.
+#[tauri::command]
+pub async fn extract_to(app: AppHandle, id: String, dest: String, force: Option<bool>) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || extract_to_blocking(&app, &id, Path::new(&dest), force.unwrap_or(false)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn extract_to_blocking(app: &AppHandle, id: &str, dest: &Path, force: bool) -> Result<String, String> {
+    validate_id(id)?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), id)?;
+    let root = dest.join("squashfs-root");
+    if fs::read_dir(&root).is_ok_and(|mut rd| rd.next().is_some()) {
+        if !force {
+            return Err(format!("{} already exists and isn't empty", root.display()));
+        }
+        fs::remove_dir_all(&root).map_err(|e| e.to_string())?;
+    }
+    fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+    let app_type = appimage::detect_type(&path).map_err(|e| e.to_string())?;
+    let finished = AtomicBool::new(false);
+    let extracted = thread::scope(|scope| {
+        scope.spawn(|| {
+            while !finished.load(Ordering::Relaxed) {
+                thread::sleep(EXTRACT_PROGRESS_INTERVAL);
+                let extracted_bytes = storage::usage(&root);
+                let _ = app.emit("axec://extract-progress", ExtractProgress { id: id.to_string(), extracted_bytes, done: false });
+            }
+        });
+        let extracted = extract_tree(&path, dest, app_type);
+        finished.store(true, Ordering::Relaxed);
+        extracted
+    });
+    let root = extracted.ok_or("Couldn't extract the AppImage")?;
+    let _ = app.emit("axec://extract-progress", ExtractProgress { id: id.to_string(), extracted_bytes: storage::usage(&root), done: true });
+    Ok(root.to_string_lossy().to_string())
+}
+
+/// Stores `id`'s AppImage with the octal `mode`, e.g. `750` for a shared group, from now
+/// on and across updates; `None` goes back to the `file_mode` setting.
+#[tauri::command]
+pub fn set_file_permissions(id: String, mode: Option<String>) -> Result<(), String> {
+    validate_id(&id)?;
+    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let path = installed_appimage(&storage, &metadata::load(), &id)?;
+    let file = owned_file(&storage, &path).ok_or_else(|| format!("{} links to a file Axec doesn't manage", path.display()))?;
+    mode.as_deref().map(parse_file_mode).transpose()?;
+    let settings = settings::load();
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    meta.entry(&id).file_mode = mode;
+    make_executable(&file, app_file_mode(&settings, &meta.get(&id))).map_err(|e| e.to_string())?;
+    seal_app_file(&storage, &path, &settings).map_err(|e| e.to_string())?;
+    meta.save().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn compact_storage() -> Result<compact::CompactReport, String> {
+    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let thumbnails = thumbnails_dir().map_err(|e| e.to_string())?;
+    let icons_root = user_icons_dir().map_err(|e| e.to_string())?;
+    let trash = trash::trash_root().map_err(|e| e.to_string())?;
+    let converted_icons = converted_icons_dir().map_err(|e| e.to_string())?;
+    let screenshots = screenshots_dir().map_err(|e| e.to_string())?;
+    let extract_bases = extraction_base_candidates()?;
+    let dirs = compact::Dirs { storage: &storage, apps_dir: &apps_dir, thumbnails: &thumbnails, icons_root: &icons_root, converted_icons: &converted_icons, screenshots: &screenshots, trash: &trash, extract_bases: &extract_bases };
+    let report = compact::compact(&dirs, &metadata::load());
+    if !in_flatpak_sandbox() && report.removed.iter().any(|p| p.ends_with(".desktop")) {
+        refresh_desktop_database(&apps_dir);
+    }
+    Ok(report)
+}
+
+/// The resolved storage, applications, logs and config paths, and whether each is writable.
+#[tauri::command]
+pub fn get_storage_layout() -> Result<StorageLayout, String> {
+    let (storage, apps_dir) = app_dirs().map_err(|e| e.to_string())?;
+    Ok(StorageLayout {
+        data_dir: LayoutPath::new(&data_dir().map_err(|e| e.to_string())?),
+        storage: LayoutPath::new(&storage),
+        applications: LayoutPath::new(&apps_dir),
+        logs: LayoutPath::new(&logs_dir().map_err(|e| e.to_string())?),
+        config: LayoutPath::new(&settings::settings_path().map_err(|e| e.to_string())?),
+        sandboxed: in_flatpak_sandbox(),
+    })
+}
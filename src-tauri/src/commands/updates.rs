@@ -0,0 +1,249 @@
+//! Commands that check for and apply updates, by delta where the app supports it.
+
+use crate::{
+    app_dirs, appimage, desktop, download, download_budget, download_source, ensure_dirs,
+    extract_metadata_tree, extraction_tempdir, find_appimage, find_updates_blocking,
+    for_each_parallel, import_appimage, in_flatpak_sandbox, metadata, refresh_desktop_database,
+    settings, storage, stored_appimage, tray, update, validate_id, write_app_desktop, zsync,
+    AppImageEntry, AvailableUpdate, DEFAULT_PARALLEL_UPDATES, UpdateCache,
+};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Fetches the newest build of an app with no recorded source as a zsync delta against
+/// the installed one, following its embedded update information. `None` means a full
+/// download is due.
+fn delta_update(
+    storage: &Path,
+    id: &str,
+    app_meta: &metadata::AppMeta,
+    headers: &[(String, String)],
+    token: Option<&str>,
+    dir: &Path,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Option<(PathBuf, Option<String>)> {
+    if app_meta.source.is_some() {
+        return None;
+    }
+    let current = stored_appimage(storage, id, app_meta)?;
+    let info = appimage::update_info(&current).ok()??;
+    let (control_url, version) = update::zsync_control(&info, headers, token).ok()??;
+    let path = zsync::sync(&control_url, headers, &current, dir, download_budget().ok()?, on_progress).ok()?;
+    Some((path, version))
+}
+
+/// Progress `update_app` emits as it fetches a new build.
+#[derive(Debug, Clone, Serialize)]
+struct UpdateProgress {
+    id: String,
+    /// `"delta"` while fetching the changed parts, `"download"` for a whole file.
+    phase: &'static str,
+    received: u64,
+    /// 0 when the server doesn't say.
+    total: u64,
+}
+
+/// What `update_app` did.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum UpdateOutcome {
+    Updated { entry: Box<AppImageEntry>, from: Option<String>, to: Option<String> },
+    /// There's no newer build: GitHub's latest release is the installed tag, or the
+    /// download is the installed build, going by its hash or `X-AppImage-BuildId`.
+    Unchanged { id: String },
+}
+
+/// `X-AppImage-BuildId` of a not yet stored AppImage, read without running it.
+fn embedded_build_id(path: &Path) -> Option<String> {
+    let app_type = appimage::detect_type(path).ok()?;
+    let tmp_dir = extraction_tempdir(path).ok()?;
+    let squash_root = extract_metadata_tree(path, tmp_dir.path(), app_type, true)?;
+    desktop::read_embedded(&squash_root)?.get("X-AppImage-BuildId").map(String::from)
+}
+
+/// `Authorization` for downloads: `token`, else the `github_token` setting.
+fn auth_headers(token: Option<&str>) -> Vec<(String, String)> {
+    let token = token.map(String::from).or(settings::load().github_token).filter(|t| !t.is_empty());
+    token.map(|t| ("Authorization".to_string(), format!("Bearer {t}"))).into_iter().collect()
+}
+
+/// Where `id` gets new builds from: the source it was downloaded from, else the update
+/// information embedded in its image. `None` when it has neither.
+fn update_source(storage: &Path, id: &str, app_meta: &metadata::AppMeta, headers: &[(String, String)]) -> Result<Option<String>, download::DownloadError> {
+    if let Some(source) = &app_meta.source {
+        return Ok(Some(source.clone()));
+    }
+    match stored_appimage(storage, id, app_meta).map(|p| appimage::update_info(&p)).transpose()?.flatten() {
+        Some(info) => update::source_from_update_info(&info, headers),
+        None => Ok(None),
+    }
+}
+
+/// Downloads the newest build from wherever `id` was installed from and puts it in
+/// place, unless it's the build already installed. `token` overrides `github_token`.
+#[tauri::command]
+pub async fn update_app(app: AppHandle, id: String, token: Option<String>) -> Result<UpdateOutcome, download::DownloadError> {
+    validate_id(&id).map_err(|message| download::DownloadError::Import { message })?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let storage = ensure_dirs()?.0;
+        let app_meta = metadata::load().get(&id);
+        let source = update_source(&storage, &id, &app_meta, &auth_headers(token.as_deref()))?
+            .ok_or_else(|| download::DownloadError::Import { message: format!("{id} wasn't installed from a URL or GitHub release and embeds no update information") })?;
+        update_from(&app, &id, &source, token.as_deref())
+    })
+    .await
+    .map_err(|e| download::DownloadError::Io { message: e.to_string() })?
+}
+
+/// Updates `id` from `source`. A build that fails to install or doesn't check out once
+/// stored is rolled back to the previous one.
+fn update_from(app: &AppHandle, id: &str, source: &str, token: Option<&str>) -> Result<UpdateOutcome, download::DownloadError> {
+    let storage = ensure_dirs()?.0;
+    let app_meta = metadata::load().get(id);
+    // A GitHub release tag says whether there's anything new without downloading it
+    if download::is_repo_shorthand(source) && app_meta.version.is_some() && update::check_github(source, app_meta.version.as_deref(), token)?.is_none() {
+        return Ok(UpdateOutcome::Unchanged { id: id.to_string() });
+    }
+    let tmp = tempfile::Builder::new().prefix("axec-download-").tempdir()?;
+    let headers = auth_headers(token);
+    let progress = |phase: &'static str, received: u64, total: u64| {
+        let _ = app.emit("axec://update-progress", UpdateProgress { id: id.to_string(), phase, received, total });
+    };
+    // Only the changed blocks when the image supports it; anything going wrong there falls back to the whole file
+    let (path, version) = match delta_update(&storage, id, &app_meta, &headers, token, tmp.path(), &mut |r, t| progress("delta", r, t)) {
+        Some(found) => found,
+        None => download_source(source, &headers, tmp.path(), &mut |r, t| progress("download", r, t))?,
+    };
+    let sha = storage::sha256_file(&path)?;
+    // A re-published release can carry the very same build under a new tag
+    let build_id = embedded_build_id(&path);
+    if app_meta.sha256.as_deref() == Some(sha.as_str()) || build_id.is_some() && build_id == app_meta.build_id {
+        return Ok(UpdateOutcome::Unchanged { id: id.to_string() });
+    }
+    let backup = match stored_appimage(&storage, id, &app_meta) {
+        Some(current) => {
+            let backup = tmp.path().join(".previous.AppImage");
+            storage::copy_file(&current, &backup, &mut |_, _| {})?;
+            Some(backup)
+        }
+        None => None,
+    };
+    let installed = import_appimage(&path, Some(id), Some(sha.clone()), true, &mut |_, _| {}).and_then(|entry| verify_stored(&storage, id, &sha).map(|()| entry));
+    let entry = match installed {
+        Ok(entry) => entry,
+        Err(message) => {
+            if let Some(backup) = backup {
+                roll_back_update(&storage, id, &backup, app_meta);
+            }
+            return Err(download::DownloadError::Import { message });
+        }
+    };
+    let mut meta = metadata::lock()?;
+    meta.entry(id).version = version.clone();
+    meta.save()?;
+    drop(meta);
+    let _ = tray::refresh(app);
+    // Whatever was cached still lists this app as out of date
+    *app.state::<UpdateCache>().0.lock().unwrap() = None;
+    Ok(UpdateOutcome::Updated { entry: Box::new(entry), from: app_meta.version, to: version })
+}
+
+/// Checks that the stored binary is the build that was downloaded and still an AppImage.
+fn verify_stored(storage: &Path, id: &str, sha: &str) -> Result<(), String> {
+    let path = find_appimage(storage, &metadata::load(), id).ok_or("Updated AppImage is missing from storage")?;
+    if storage::sha256_file(&path).map_err(|e| e.to_string())? != sha {
+        return Err("Stored build doesn't match the download".into());
+    }
+    appimage::detect_type(&path).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Puts the build saved in `backup` back in place with the metadata it had; best-effort,
+/// since it runs while reporting another failure.
+fn roll_back_update(storage: &Path, id: &str, backup: &Path, previous: metadata::AppMeta) {
+    if import_appimage(backup, Some(id), previous.sha256.clone(), true, &mut |_, _| {}).is_err() {
+        return;
+    }
+    let Ok(mut meta) = metadata::lock() else { return };
+    meta.apps.insert(id.to_string(), previous);
+    if meta.save().is_ok() && !in_flatpak_sandbox() {
+        if let Ok((_, apps_dir)) = app_dirs() {
+            let _ = write_app_desktop(storage, &apps_dir, id, &meta);
+            refresh_desktop_database(&apps_dir);
+        }
+    }
+}
+
+/// How one app fared in `update_all`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum UpdateResult {
+    UpToDate,
+    Updated { from: Option<String>, to: Option<String> },
+    Failed { error: download::DownloadError },
+    /// Not downloaded by Axec and no update information embedded.
+    NoUpdateInfo,
+}
+
+/// Progress `update_all` emits as each app finishes.
+#[derive(Debug, Clone, Serialize)]
+struct UpdateAllProgress {
+    id: String,
+    done: usize,
+    total: usize,
+    result: UpdateResult,
+}
+
+/// Checks every app that has an update source and installs newer builds, a few at a
+/// time. One app failing, and being rolled back, doesn't stop the others.
+#[tauri::command]
+pub async fn update_all(app: AppHandle, token: Option<String>) -> Result<Vec<(String, UpdateResult)>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+        let meta = metadata::load();
+        let ids: Vec<&String> = meta.apps.keys().filter(|id| find_appimage(&storage, &meta, id).is_some()).collect();
+        let done = AtomicUsize::new(0);
+        let workers = settings::load().max_parallel_updates.unwrap_or(DEFAULT_PARALLEL_UPDATES);
+        Ok(for_each_parallel(&ids, workers, |id| {
+            let outcome = update_source(&storage, id, &meta.get(id), &auth_headers(token.as_deref()))
+                .and_then(|source| source.map(|source| update_from(&app, id, &source, token.as_deref())).transpose());
+            let result = match outcome {
+                Ok(None) => UpdateResult::NoUpdateInfo,
+                Ok(Some(UpdateOutcome::Unchanged { .. })) => UpdateResult::UpToDate,
+                Ok(Some(UpdateOutcome::Updated { from, to, .. })) => UpdateResult::Updated { from, to },
+                Err(error) => UpdateResult::Failed { error },
+            };
+            let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+            let _ = app.emit("axec://update-all-progress", UpdateAllProgress { id: id.to_string(), done, total: ids.len(), result: result.clone() });
+            (id.to_string(), result)
+        }))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Every app with a newer build available, checked a few at a time. Results are reused
+/// for half an hour unless `refresh` is set. Once GitHub's rate limit is hit the other
+/// GitHub checks are skipped, zsync ones still run, and that partial run isn't cached.
+#[tauri::command]
+pub async fn find_updates(app: AppHandle, refresh: Option<bool>, token: Option<String>) -> Result<Vec<AvailableUpdate>, String> {
+    tauri::async_runtime::spawn_blocking(move || find_updates_blocking(&app, refresh.unwrap_or(false), token)).await.map_err(|e| e.to_string())?
+}
+
+/// Checks the GitHub repo an app was installed from for a newer release. `token`
+/// overrides the `github_token` setting.
+#[tauri::command]
+pub async fn check_update_github(id: String, token: Option<String>) -> Result<Option<update::UpdateInfo>, String> {
+    validate_id(&id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let meta = metadata::load().get(&id);
+        let repo = meta.source.filter(|s| download::is_repo_shorthand(s)).ok_or_else(|| format!("{id} was not installed from a GitHub release"))?;
+        let token = token.or(settings::load().github_token);
+        update::check_github(&repo, meta.version.as_deref(), token.as_deref()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,205 @@
+//! Housekeeping for files Axec leaves behind: icons and desktop entries of apps that
+//! no longer exist, outdated thumbnails, unused blobs and abandoned extraction dirs.
+//!
+//! Everything is keyed off the AppImages actually in storage, so files belonging to an
+//! installed app are never touched.
+
+use crate::{desktop, icons, metadata::Metadata};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Leftovers younger than this may still belong to an import or probe in progress.
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompactReport {
+    pub removed: Vec<String>,
+    pub bytes_reclaimed: u64,
+}
+
+impl CompactReport {
+    fn remove(&mut self, path: &Path) {
+        let size = disk_size(path);
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+        let removed = if is_dir { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+        if removed.is_ok() {
+            self.removed.push(path.to_string_lossy().to_string());
+            self.bytes_reclaimed += size;
+        }
+    }
+}
+
+pub struct Dirs<'a> {
+    pub storage: &'a Path,
+    pub apps_dir: &'a Path,
+    pub thumbnails: &'a Path,
+    pub icons_root: &'a Path,
+    pub converted_icons: &'a Path,
+    pub screenshots: &'a Path,
+    pub trash: &'a Path,
+    /// Where extractions unpack, per `extraction_base_candidates`.
+    pub extract_bases: &'a [PathBuf],
+}
+
+fn disk_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| !m.is_dir())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn is_stale(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age > STALE_AFTER)
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir).map(|rd| rd.flatten().map(|e| e.path()).collect()).unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|s| s.to_str()).unwrap_or_default()
+}
+
+/// Ids of every AppImage in storage, including dangling blob links.
+fn installed_ids(storage: &Path, meta: &Metadata) -> HashSet<String> {
+    let moved = meta
+        .apps
+        .iter()
+        .filter(|(_, m)| m.subdir.is_some())
+        .filter(|(id, m)| crate::app_file_candidates(storage, id, m).iter().any(|p| fs::symlink_metadata(p).is_ok()))
+        .map(|(id, _)| id.clone());
+    entries(storage)
+        .into_iter()
+        .filter(|p| p.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("appimage")))
+        .filter(|p| fs::symlink_metadata(p).is_ok_and(|m| !m.is_dir()))
+        .filter_map(|p| match meta.id_for_file(file_name(&p)) {
+            Some(id) => Some(id.to_string()),
+            None => p.file_stem().and_then(|s| s.to_str()).map(String::from),
+        })
+        .chain(moved)
+        .collect()
+}
+
+/// Blob paths still pointed at by an app, or by a trashed app that may be restored.
+fn linked_blobs(storage: &Path, trash: &Path, meta: &Metadata) -> HashSet<PathBuf> {
+    let mut candidates = entries(storage);
+    for (id, m) in meta.apps.iter().filter(|(_, m)| m.subdir.is_some()) {
+        candidates.extend(crate::app_file_candidates(storage, id, m));
+    }
+    for dir in entries(trash) {
+        candidates.extend(entries(&dir));
+    }
+    candidates.into_iter().filter_map(|p| fs::read_link(p).ok()).collect()
+}
+
+pub fn compact(dirs: &Dirs, meta: &Metadata) -> CompactReport {
+    let mut report = CompactReport::default();
+    let ids = installed_ids(dirs.storage, meta);
+
+    // Icons extracted for apps that are gone
+    for p in entries(dirs.storage) {
+        let is_icon = p.extension().and_then(|s| s.to_str()).is_some_and(|e| icons::ICON_EXTS.contains(&e));
+        let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        // `{id}.alt.png` is the other-theme icon of `id`
+        let stem = stem.strip_suffix(".alt").unwrap_or(stem);
+        if is_icon && p.is_file() && !ids.contains(stem) {
+            report.remove(&p);
+        }
+    }
+
+    // Desktop entries for apps that are gone, and old-style ones a migrated entry replaced
+    for p in entries(dirs.apps_dir) {
+        let stale = match desktop::entry_id(file_name(&p)) {
+            Some((id, legacy)) => !ids.contains(id) || legacy && dirs.apps_dir.join(desktop::entry_file_name(id)).exists(),
+            None => false,
+        };
+        if stale {
+            report.remove(&p);
+        }
+    }
+
+    // Theme icons for apps that are gone
+    let mut theme_changed = false;
+    for size_dir in entries(&dirs.icons_root.join("hicolor")) {
+        for p in entries(&size_dir.join("apps")) {
+            let name = file_name(&p);
+            let id = name.strip_prefix("axec-").and_then(|n| n.strip_suffix(".png").or_else(|| n.strip_suffix(".svg")));
+            if id.is_some_and(|id| !ids.contains(id)) {
+                report.remove(&p);
+                theme_changed = true;
+            }
+        }
+    }
+    if theme_changed {
+        icons::refresh_icon_cache(dirs.icons_root);
+    }
+
+    // Converted icons for apps that are gone, or left over from another `icon_format`
+    let wanted_ext = crate::settings::load().icon_format.extension();
+    for p in entries(dirs.converted_icons) {
+        let id = p.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        if !ids.contains(id) || p.extension().and_then(|s| s.to_str()) != wanted_ext {
+            report.remove(&p);
+        }
+    }
+
+    // Extract-and-run caches of apps that are gone
+    for p in entries(&dirs.storage.join("extracted")) {
+        if !ids.contains(file_name(&p)) {
+            report.remove(&p);
+        }
+    }
+
+    // Screenshot caches of apps that are gone
+    for p in entries(dirs.screenshots) {
+        if !ids.contains(file_name(&p)) {
+            report.remove(&p);
+        }
+    }
+
+    // Thumbnails named `{id}-{size}.png` whose icon is gone or newer than the cache
+    for p in entries(dirs.thumbnails) {
+        let Some((id, size)) = file_name(&p).strip_suffix(".png").and_then(|n| n.rsplit_once('-')) else { continue };
+        if size.parse::<u32>().is_err() {
+            continue;
+        }
+        let fresh = ids.contains(id) && crate::find_app_icon(dirs.storage, meta, id).is_some_and(|icon| icons::is_fresh(&p, &icon));
+        if !fresh {
+            report.remove(&p);
+        }
+    }
+
+    // Blobs nothing references any more, and copies interrupted mid-way
+    let linked = linked_blobs(dirs.storage, dirs.trash, meta);
+    for p in entries(&dirs.storage.join("blobs")) {
+        let unused = match p.extension().and_then(|s| s.to_str()) {
+            Some("AppImage") => p.file_stem().and_then(|s| s.to_str()).is_some_and(|sha| meta.references(sha) == 0) && !linked.contains(&p),
+            Some("partial") => is_stale(&p),
+            _ => false,
+        };
+        if unused {
+            report.remove(&p);
+        }
+    }
+
+    // Extraction dirs a crash or kill left behind
+    for p in dirs.extract_bases.iter().flat_map(|base| entries(base)) {
+        if file_name(&p).starts_with("axec-extract-") && p.is_dir() && is_stale(&p) {
+            report.remove(&p);
+        }
+    }
+
+    report
+}
@@ -0,0 +1,525 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Prefix of generated desktop file ids: the app's own id, as the desktop entry spec's
+/// reverse-DNS naming and the portal's launcher API both want.
+const ENTRY_PREFIX: &str = "com.praneeshrv.Axec.";
+/// Prefix older versions named entries with.
+const LEGACY_ENTRY_PREFIX: &str = "axec-";
+
+/// File name of `id`'s generated desktop entry.
+pub fn entry_file_name(id: &str) -> String {
+    format!("{ENTRY_PREFIX}{id}.desktop")
+}
+
+/// File name `id`'s entry had before the switch to reverse-DNS names.
+pub fn legacy_entry_file_name(id: &str) -> String {
+    format!("{LEGACY_ENTRY_PREFIX}{id}.desktop")
+}
+
+/// The app id a generated entry's file name belongs to, under either naming scheme,
+/// with whether it's the legacy one.
+pub fn entry_id(file_name: &str) -> Option<(&str, bool)> {
+    let stem = file_name.strip_suffix(".desktop")?;
+    stem.strip_prefix(ENTRY_PREFIX).map(|id| (id, false)).or_else(|| stem.strip_prefix(LEGACY_ENTRY_PREFIX).map(|id| (id, true)))
+}
+
+/// Built-in presets mapping a friendly label to a valid main + additional category set.
+const CATEGORY_PRESETS: [(&str, &[&str]); 10] = [
+    ("game", &["Game"]),
+    ("developer_tool", &["Development"]),
+    ("ide", &["Development", "IDE"]),
+    ("media", &["AudioVideo", "Audio", "Video"]),
+    ("graphics", &["Graphics"]),
+    ("office", &["Office"]),
+    ("internet", &["Network"]),
+    ("education", &["Education"]),
+    ("science", &["Education", "Science"]),
+    ("system", &["System"]),
+];
+
+/// Registered main categories; the spec wants at least one per entry so menus can place it.
+const MAIN_CATEGORIES: [&str; 13] = [
+    "AudioVideo", "Audio", "Video", "Development", "Education", "Game", "Graphics", "Network", "Office", "Science", "Settings", "System", "Utility",
+];
+
+/// Checks a `Categories=` list: plain tokens, at least one of them a main category.
+pub fn validate_categories(categories: &[String]) -> Result<(), String> {
+    if let Some(bad) = categories.iter().find(|c| c.is_empty() || !c.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')) {
+        return Err(format!("Invalid category: {bad:?}"));
+    }
+    if !categories.iter().any(|c| MAIN_CATEGORIES.contains(&c.as_str())) {
+        return Err(format!("Categories need at least one of: {}", MAIN_CATEGORIES.join(", ")));
+    }
+    Ok(())
+}
+
+/// Whether `mime` is a plain `type/subtype` token, which is all a `MimeType=` list may
+/// hold; anything else could smuggle a `;` or a line break into the entry.
+pub fn is_mime_type(mime: &str) -> bool {
+    let token = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c));
+    mime.split_once('/').is_some_and(|(kind, subtype)| token(kind) && token(subtype))
+}
+
+/// `mime_types` without the entries `is_mime_type` rejects.
+pub fn valid_mime_types(mime_types: Vec<String>) -> Vec<String> {
+    mime_types.into_iter().filter(|m| is_mime_type(m)).collect()
+}
+
+/// Looks up a preset by name, accepting labels like "Developer Tool" or "developer-tool".
+pub fn category_preset(name: &str) -> Option<&'static [&'static str]> {
+    let key: String = name.trim().to_ascii_lowercase().chars().map(|c| if c == ' ' || c == '-' { '_' } else { c }).collect();
+    CATEGORY_PRESETS.iter().find(|(k, _)| *k == key).map(|(_, cats)| *cats)
+}
+
+/// Everything Axec writes into a generated desktop entry.
+pub struct DesktopFile<'a> {
+    pub name: &'a str,
+    pub exec_path: &'a Path,
+    /// Either an absolute path or a theme icon name.
+    pub icon: Option<&'a str>,
+    pub comment: Option<&'a str>,
+    /// Inserted between the binary and the field code.
+    pub args: &'a [String],
+    /// How the launcher passes files or URLs, e.g. `U` for `%U`; `None` for apps that take neither.
+    pub field_code: Option<char>,
+    /// `MimeType=` values, so the app shows up under "Open with".
+    pub mime_types: &'a [String],
+    /// `exec_wrapper` template; the binary, args and field code replace its `%command%`.
+    pub wrapper: Option<&'a str>,
+    pub categories: &'a [String],
+    /// `Keywords=` search terms from the embedded entry.
+    pub keywords: &'a [String],
+    /// Localized keys like `Name[de]` copied from the embedded entry.
+    pub localized: &'a BTreeMap<String, String>,
+    /// Run in a terminal, as the embedded entry's `Terminal=true` asks.
+    pub terminal: bool,
+    /// Keep it out of menus, for entries marked `NoDisplay` or `Hidden`.
+    pub no_display: bool,
+    /// Lets the shell match the app's windows to this entry.
+    pub startup_wm_class: Option<&'a str>,
+    /// `TryExec=`: launchers hide the entry once this file is gone.
+    pub try_exec: Option<&'a Path>,
+}
+
+impl DesktopFile<'_> {
+    /// Writes the entry unless the file already holds exactly this content, so unchanged
+    /// entries keep their mtime. Returns whether it wrote.
+    pub fn write(&self, desktop_path: &Path) -> io::Result<bool> {
+        let content = self.render()?;
+        if fs::read(desktop_path).is_ok_and(|old| old == content.as_bytes()) {
+            return Ok(false);
+        }
+        crate::write_atomic(desktop_path, content.as_bytes())?;
+        Ok(true)
+    }
+
+    /// The entry's text.
+    pub fn render(&self) -> io::Result<String> {
+        let mut command = vec![quote_exec_arg(&self.exec_path.to_string_lossy(), true)];
+        command.extend(self.args.iter().map(|a| quote_exec_arg(a, false)));
+        command.extend(self.field_code.map(|code| format!("%{code}")));
+        let exec = match self.wrapper.map(crate::launch::split_wrapper).transpose().map_err(io::Error::other)? {
+            Some(words) => words
+                .iter()
+                .map(|w| if w == crate::launch::COMMAND_PLACEHOLDER { command.join(" ") } else { quote_exec_arg(w, false) })
+                .collect(),
+            None => command,
+        };
+        let mut content = format!(
+            "[Desktop Entry]\nType=Application\nName={name}\nExec={exec}\nTerminal={terminal}\nCategories={categories};\n",
+            name = escape(self.name),
+            exec = escape(&exec.join(" ")),
+            terminal = self.terminal,
+            categories = self.categories.join(";"),
+        );
+        if let Some(comment) = self.comment {
+            content.push_str(&format!("Comment={}\n", escape(comment)));
+        }
+        if !self.keywords.is_empty() {
+            content.push_str(&format!("Keywords={};\n", escape(&self.keywords.join(";"))));
+        }
+        for (key, value) in self.localized {
+            content.push_str(&format!("{key}={}\n", escape(value)));
+        }
+        if let Some(path) = self.try_exec {
+            content.push_str(&format!("TryExec={}\n", escape(&path.to_string_lossy())));
+        }
+        if let Some(icon) = self.icon {
+            content.push_str(&format!("Icon={icon}\n"));
+        }
+        // Checked again here, for metadata saved before imports filtered the list
+        let mime_types: Vec<&str> = self.mime_types.iter().map(String::as_str).filter(|m| is_mime_type(m)).collect();
+        if !mime_types.is_empty() {
+            content.push_str(&format!("MimeType={};\n", mime_types.join(";")));
+        }
+        if self.no_display {
+            content.push_str("NoDisplay=true\n");
+        }
+        if let Some(class) = self.startup_wm_class {
+            content.push_str(&format!("StartupWMClass={}\n", escape(class)));
+        }
+        content.push_str("X-AppImage-Version=1\nX-AppImage-Integrate=false\n");
+        Ok(content)
+    }
+}
+
+/// Locale variants to try for localized keys, most specific first, per the desktop
+/// entry spec's `lang_COUNTRY@MODIFIER` matching rules.
+pub fn current_locales() -> Vec<String> {
+    let raw = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|v| !v.is_empty())
+        .unwrap_or_default();
+    let (rest, modifier) = match raw.split_once('@') {
+        Some((rest, m)) => (rest, Some(m)),
+        None => (raw.as_str(), None),
+    };
+    let base = rest.split('.').next().unwrap_or_default();
+    if base.is_empty() || base == "C" || base == "POSIX" {
+        return Vec::new();
+    }
+    let lang = base.split('_').next().unwrap_or(base);
+    let country = base.contains('_').then_some(base);
+    let mut out = Vec::new();
+    if let (Some(c), Some(m)) = (country, modifier) {
+        out.push(format!("{c}@{m}"));
+    }
+    out.extend(country.map(String::from));
+    out.extend(modifier.map(|m| format!("{lang}@{m}")));
+    out.push(lang.to_string());
+    out
+}
+
+/// The `[Desktop Entry]` group of a desktop file, in file order.
+#[derive(Debug, Clone, Default)]
+pub struct DesktopEntry {
+    fields: Vec<(String, String)>,
+}
+
+impl DesktopEntry {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// A boolean key; the spec only allows `true` and `false`.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// A `;`-separated list key like `MimeType`, without empty items.
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        self.get(key).map(|v| v.split(';').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect()).unwrap_or_default()
+    }
+
+    /// `key` in the first of `locales` that has a translation, else the plain `key`.
+    pub fn get_localized(&self, key: &str, locales: &[String]) -> Option<&str> {
+        locales.iter().find_map(|l| self.get(&format!("{key}[{l}]"))).or_else(|| self.get(key))
+    }
+
+    /// Every `key[locale]` variant, keyed by the full `key[locale]` name.
+    pub fn translations(&self, key: &str) -> BTreeMap<String, String> {
+        let prefix = format!("{key}[");
+        self.fields.iter().filter(|(k, _)| k.starts_with(&prefix) && k.ends_with(']')).cloned().collect()
+    }
+}
+
+/// Quotes one `Exec=` argument per the desktop entry spec; a literal `%` must be doubled
+/// so it isn't read as a field code.
+fn quote_exec_arg(arg: &str, always: bool) -> String {
+    const RESERVED: &str = " \t\n\"'\\><~|&;$*?#()`";
+    let arg = arg.replace('%', "%%");
+    if !always && !arg.is_empty() && !arg.chars().any(|c| RESERVED.contains(c)) {
+        return arg;
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Field codes like `%U` that the launcher expands.
+const FIELD_CODES: &str = "fFuUdDnNickvm";
+
+/// One word of an `Exec=` command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecWord {
+    Arg(String),
+    FieldCode(char),
+}
+
+/// Splits an (already unescaped) `Exec=` value into words, undoing `quote_exec_arg`.
+pub fn split_exec(exec: &str) -> Vec<ExecWord> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            '\\' if quoted => word.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() && !quoted => words.extend(word.take().map(ExecWord::Arg)),
+            '%' => match chars.next() {
+                Some('%') => word.get_or_insert_with(String::new).push('%'),
+                Some(code) if FIELD_CODES.contains(code) && word.is_none() && !quoted => words.push(ExecWord::FieldCode(code)),
+                // Deprecated or unknown codes expand to nothing
+                _ => {}
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word.map(ExecWord::Arg));
+    words
+}
+
+/// Field codes that pass files or URLs; the rest carry no per-launch input.
+const FILE_FIELD_CODES: &str = "fFuU";
+
+/// The args an embedded entry's `Exec=` passes to its own binary, and the file/URL field
+/// code it takes, if any. The first word is the binary inside the image, which Axec
+/// replaces with the AppImage, so it's dropped.
+pub fn embedded_exec_args(exec: &str) -> (Vec<String>, Option<char>) {
+    let mut args = Vec::new();
+    let mut field_code = None;
+    for word in split_exec(exec).into_iter().skip(1) {
+        match word {
+            ExecWord::Arg(arg) => args.push(arg),
+            ExecWord::FieldCode(code) if FILE_FIELD_CODES.contains(code) => {
+                field_code.get_or_insert(code);
+            }
+            // `%i`, `%c` and `%k` only make sense for the entry that held them
+            ExecWord::FieldCode(_) => {}
+        }
+    }
+    (args, field_code)
+}
+
+/// Keys an embedded entry may use for args its AppImage needs beyond those in `Exec=`.
+const ARG_HINT_KEYS: [&str; 2] = ["X-AppImage-Arguments", "X-AppImage-Args"];
+
+/// The args an AppImage should be launched with by default: those its entry's `Exec=`
+/// passes, then any from an `X-AppImage-Arguments` hint, plus `Exec=`'s field code.
+pub fn recommended_args(entry: &DesktopEntry) -> (Vec<String>, Option<char>) {
+    let (mut args, field_code) = entry.get("Exec").map(embedded_exec_args).unwrap_or_default();
+    if let Some(hint) = ARG_HINT_KEYS.iter().find_map(|k| entry.get(k)) {
+        for word in split_exec(hint) {
+            if let ExecWord::Arg(arg) = word {
+                if !args.contains(&arg) {
+                    args.push(arg);
+                }
+            }
+        }
+    }
+    (args, field_code)
+}
+
+/// The field code a generated entry passes files with: the embedded `Exec=`'s own, else
+/// `%U` for apps that declare file types, else none.
+pub fn file_field_code(embedded: Option<char>, mime_types: &[String]) -> Option<char> {
+    embedded.or_else(|| (!mime_types.is_empty()).then_some('U'))
+}
+
+/// `categories` minus malformed values, if a main category remains.
+pub fn usable_categories(categories: Vec<String>) -> Option<Vec<String>> {
+    let categories: Vec<String> = categories.into_iter().filter(|c| !c.is_empty() && c.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')).collect();
+    validate_categories(&categories).is_ok().then_some(categories)
+}
+
+/// The entry's `Categories=`, if usable for a generated entry.
+pub fn embedded_categories(entry: &DesktopEntry) -> Option<Vec<String>> {
+    usable_categories(entry.get_list("Categories"))
+}
+
+/// Inverse of `unescape` for the characters a single-line value can't hold; other
+/// control characters (e.g. NUL from a crafted embedded entry) are dropped.
+fn escape(value: &str) -> String {
+    value.replace(|c: char| c.is_control() && !matches!(c, '\n' | '\t' | '\r'), "").replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t").replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            // `\;` must survive so list values still split correctly
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+pub fn parse(content: &str) -> DesktopEntry {
+    let mut fields = Vec::new();
+    let mut in_entry = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        if let Some((k, v)) = line.split_once('=') {
+            fields.push((k.trim().to_string(), unescape(v.trim())));
+        }
+    }
+    DesktopEntry { fields }
+}
+
+/// The file `AppRun` links to, which is the app's real binary in most images.
+fn apprun_target(squash_root: &Path) -> Option<String> {
+    let target = fs::read_link(squash_root.join("AppRun")).ok()?;
+    target.file_name().and_then(|n| n.to_str()).filter(|n| !n.is_empty() && *n != "AppRun").map(String::from)
+}
+
+/// Whether `entry`'s `Exec=` runs `AppRun` or the binary it links to.
+fn runs_apprun(entry: &DesktopEntry, target: Option<&str>) -> bool {
+    let Some(ExecWord::Arg(command)) = entry.get("Exec").and_then(|e| split_exec(e).into_iter().next()) else {
+        return false;
+    };
+    let name = Path::new(&command).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    name == "AppRun" || Some(name) == target
+}
+
+/// Finds the desktop file the AppImage spec requires at the top of the extracted tree.
+/// Images shipping several (e.g. a helper's) get the one whose `Exec=` runs the app itself.
+pub fn find_embedded(squash_root: &Path) -> Option<PathBuf> {
+    let mut found: Vec<PathBuf> = fs::read_dir(squash_root)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("desktop"))
+        .collect();
+    found.sort();
+    if found.len() > 1 {
+        let target = apprun_target(squash_root);
+        if let Some(at) = found.iter().position(|p| fs::read_to_string(p).is_ok_and(|s| runs_apprun(&parse(&s), target.as_deref()))) {
+            return Some(found.swap_remove(at));
+        }
+    }
+    found.into_iter().next()
+}
+
+/// A `StartupWMClass` for images whose entry sets none: the binary `AppRun` links to,
+/// else the command `Exec=` runs. Many toolkits name windows after the binary, so this
+/// is right more often than not, but only a guess.
+pub fn guess_wm_class(squash_root: &Path, entry: Option<&DesktopEntry>) -> Option<String> {
+    if let Some(class) = apprun_target(squash_root) {
+        return Some(class);
+    }
+    let basename = |p: &Path| p.file_name().and_then(|n| n.to_str()).filter(|n| !n.is_empty() && *n != "AppRun").map(String::from);
+    match split_exec(entry?.get("Exec")?).into_iter().next()? {
+        ExecWord::Arg(command) => basename(Path::new(&command)),
+        ExecWord::FieldCode(_) => None,
+    }
+}
+
+pub fn read_embedded(squash_root: &Path) -> Option<DesktopEntry> {
+    let path = find_embedded(squash_root)?;
+    fs::read_to_string(path).ok().map(|s| parse(&s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_for(field_code: Option<char>, mime_types: &[String]) -> String {
+        let localized = BTreeMap::new();
+        DesktopFile {
+            name: "Tool",
+            exec_path: Path::new("/apps/tool.AppImage"),
+            icon: None,
+            comment: None,
+            args: &[],
+            field_code,
+            mime_types,
+            wrapper: None,
+            categories: &["Utility".to_string()],
+            keywords: &[],
+            localized: &localized,
+            terminal: false,
+            no_display: false,
+            startup_wm_class: None,
+            try_exec: None,
+        }
+        .render()
+        .unwrap()
+    }
+
+    #[test]
+    fn mime_types_that_could_break_the_entry_are_dropped() {
+        let mime = ["text/plain", "image/png\nExec=/bin/evil", "a;b/c", "noslash", "/x", "x/", "a/b/c", "image/svg+xml"].map(String::from);
+        assert_eq!(valid_mime_types(mime.to_vec()), ["text/plain", "image/svg+xml"]);
+        let content = entry_for(None, &mime);
+        assert!(content.contains("MimeType=text/plain;image/svg+xml;\n"));
+        assert_eq!(content.matches("Exec=").count(), 1);
+    }
+
+    #[test]
+    fn field_code_is_omitted_for_apps_without_mime_types() {
+        let (args, code) = recommended_args(&parse("[Desktop Entry]\nExec=tool --quiet\n"));
+        assert_eq!(args, ["--quiet"]);
+        assert_eq!(file_field_code(code, &[]), None);
+        let content = entry_for(file_field_code(code, &[]), &[]);
+        assert!(content.contains("Exec=\"/apps/tool.AppImage\"\n"), "{content}");
+        assert!(!content.contains('%'));
+        assert!(!content.contains("MimeType="));
+    }
+
+    #[test]
+    fn wm_class_is_guessed_from_apprun_then_exec() {
+        let root = tempfile::tempdir().unwrap();
+        let entry = parse("[Desktop Entry]\nName=Tool\nExec=/usr/bin/tool-bin --flag %F\n");
+        assert_eq!(entry.get("StartupWMClass"), None);
+        assert_eq!(guess_wm_class(root.path(), Some(&entry)).as_deref(), Some("tool-bin"));
+        assert_eq!(guess_wm_class(root.path(), Some(&parse("[Desktop Entry]\nExec=AppRun\n"))), None);
+        std::os::unix::fs::symlink("usr/bin/real-tool", root.path().join("AppRun")).unwrap();
+        assert_eq!(guess_wm_class(root.path(), Some(&entry)).as_deref(), Some("real-tool"));
+    }
+
+    #[test]
+    fn field_code_follows_the_embedded_exec_then_mime_types() {
+        let mime = ["text/plain".to_string()];
+        let (_, code) = embedded_exec_args("tool %f");
+        assert_eq!(file_field_code(code, &[]), Some('f'));
+        assert_eq!(file_field_code(code, &mime), Some('f'));
+        assert_eq!(file_field_code(None, &mime), Some('U'));
+        assert!(entry_for(Some('U'), &mime).contains("Exec=\"/apps/tool.AppImage\" %U\n"));
+        // `%i` and friends aren't file codes and don't count
+        assert_eq!(embedded_exec_args("tool %i %k").1, None);
+    }
+}
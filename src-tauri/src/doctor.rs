@@ -0,0 +1,252 @@
+//! Environment checks behind the `doctor` command, each with a suggested fix, so the
+//! usual reasons integration or launching fails show up in one place.
+
+use crate::desktop::{self, ExecWord};
+use serde::Serialize;
+use std::{
+    ffi::CString,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Below this much free space in storage, imports are likely to fail.
+const LOW_SPACE: u64 = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+fn check(name: &str, status: Status, detail: impl Into<String>, fix: Option<&str>) -> Check {
+    Check { name: name.to_string(), status, detail: detail.into(), fix: fix.map(String::from) }
+}
+
+fn on_path(tool: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|d| d.join(tool).is_file()))
+}
+
+/// Dirs whose `applications` subdir desktop menus scan, per the XDG base dir spec.
+fn xdg_data_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/share")));
+    let system = std::env::var("XDG_DATA_DIRS").ok().filter(|v| !v.is_empty()).unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    home.into_iter().chain(std::env::split_paths(&system)).collect()
+}
+
+/// Whether desktop menus scan `apps_dir`, i.e. it's `applications` under an XDG data dir.
+pub fn on_menu_path(apps_dir: &Path) -> bool {
+    xdg_data_dirs().into_iter().any(|d| d.join("applications") == apps_dir)
+}
+
+/// The fix for an `apps_dir` menus don't scan: put its data dir on `XDG_DATA_DIRS`.
+pub fn menu_path_fix(apps_dir: &Path) -> String {
+    let data_dir = apps_dir.parent().unwrap_or(apps_dir);
+    format!("Add {} to XDG_DATA_DIRS for your session, e.g. in ~/.config/environment.d/, then log in again", data_dir.display())
+}
+
+fn menu_path_check(apps_dir: &Path) -> Check {
+    if on_menu_path(apps_dir) {
+        check("Menu path", Status::Pass, format!("{} is scanned by desktop menus", apps_dir.display()), None)
+    } else {
+        check(
+            "Menu path",
+            Status::Warn,
+            format!("{} is not under XDG_DATA_HOME or XDG_DATA_DIRS, so its entries won't appear in menus", apps_dir.display()),
+            Some(&menu_path_fix(apps_dir)),
+        )
+    }
+}
+
+/// Where a theme icon `name` is installed, searching hicolor in every XDG data dir and pixmaps.
+fn find_theme_icon(name: &str) -> Option<PathBuf> {
+    let roots = xdg_data_dirs().into_iter().map(|d| d.join("icons/hicolor"));
+    for root in roots {
+        let Ok(sizes) = fs::read_dir(&root) else { continue };
+        for size in sizes.flatten() {
+            for ext in ["png", "svg", "xpm"] {
+                let p = size.path().join(format!("apps/{name}.{ext}"));
+                if p.is_file() {
+                    return Some(p);
+                }
+            }
+        }
+    }
+    ["png", "svg", "xpm"].iter().map(|ext| PathBuf::from(format!("/usr/share/pixmaps/{name}.{ext}"))).find(|p| p.is_file())
+}
+
+fn is_newer(a: &Path, b: &Path) -> bool {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    matches!((mtime(a), mtime(b)), (Some(a), Some(b)) if a > b)
+}
+
+/// Whether the menu entry at `desktop_path` should actually show up: written, valid, on a scanned path,
+/// pointing at a real binary and icon, and not hidden behind stale caches.
+pub fn verify_integration(desktop_path: &Path, apps_dir: &Path, integrated: bool, sandboxed: bool) -> DoctorReport {
+    let mut checks = Vec::new();
+    if !integrated {
+        checks.push(check("Integration", Status::Fail, "Menu integration is turned off for this app", Some("Choose Add to menu")));
+        return DoctorReport { checks };
+    }
+    if sandboxed {
+        checks.push(check("Sandbox", Status::Fail, "Running inside Flatpak; no menu entry is written for the host", Some("Use the native package to integrate with the host menu")));
+        return DoctorReport { checks };
+    }
+    let Ok(content) = fs::read_to_string(desktop_path) else {
+        checks.push(check("Desktop entry", Status::Fail, format!("{} is missing", desktop_path.display()), Some("Run Repair to write it again")));
+        return DoctorReport { checks };
+    };
+    checks.push(check("Desktop entry", Status::Pass, format!("{} exists", desktop_path.display()), None));
+    checks.push(menu_path_check(apps_dir));
+
+    let entry = desktop::parse(&content);
+    let mut problems: Vec<String> = ["Type", "Name", "Exec"].iter().filter(|k| entry.get(k).is_none()).map(|k| format!("no {k}= key")).collect();
+    if entry.get("Type").is_some_and(|t| t != "Application") {
+        problems.push("Type= is not Application".to_string());
+    }
+    if on_path("desktop-file-validate") {
+        if let Ok(out) = Command::new("desktop-file-validate").arg(desktop_path).output() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            problems.extend(stdout.lines().filter(|l| l.contains("error:")).map(|l| l.rsplit("error:").next().unwrap_or(l).trim().to_string()));
+        }
+    }
+    checks.push(if problems.is_empty() {
+        check("Valid entry", Status::Pass, "The entry parses and has the required keys", None)
+    } else {
+        check("Valid entry", Status::Fail, problems.join("; "), Some("Run Repair, or fix the file by hand and choose Reload from desktop file"))
+    });
+
+    let program = entry.get("Exec").map(desktop::split_exec).and_then(|words| {
+        words.into_iter().find_map(|w| match w {
+            ExecWord::Arg(a) if a.starts_with('/') => Some(PathBuf::from(a)),
+            _ => None,
+        })
+    });
+    checks.push(match program {
+        Some(p) if fs::metadata(&p).is_ok_and(|m| m.permissions().mode() & 0o111 != 0) => check("Exec", Status::Pass, format!("Runs {}", p.display()), None),
+        Some(p) => check("Exec", Status::Fail, format!("{} is missing or not executable", p.display()), Some("Run Repair")),
+        None => check("Exec", Status::Warn, "Exec= doesn't name the AppImage by absolute path", Some("Run Repair to rewrite it")),
+    });
+
+    match entry.get("Icon") {
+        None => checks.push(check("Icon", Status::Warn, "The entry has no Icon=", Some("Run Repair to extract the icon again"))),
+        Some(icon) if icon.starts_with('/') => checks.push(if Path::new(icon).is_file() {
+            check("Icon", Status::Pass, format!("{icon} exists"), None)
+        } else {
+            check("Icon", Status::Fail, format!("{icon} is missing"), Some("Run Repair to extract the icon again"))
+        }),
+        Some(icon) => match find_theme_icon(icon) {
+            Some(found) => {
+                checks.push(check("Icon", Status::Pass, format!("{icon} resolves to {}", found.display()), None));
+                let cache = found.ancestors().nth(3).map(|hicolor| hicolor.join("icon-theme.cache"));
+                if let Some(cache) = cache.filter(|c| c.exists() && is_newer(&found, c)) {
+                    checks.push(check("Icon cache", Status::Warn, format!("{} predates the icon", cache.display()), Some("Run gtk-update-icon-cache -f -t on that hicolor directory")));
+                }
+            }
+            None => checks.push(check("Icon", Status::Fail, format!("No theme icon named {icon}"), Some("Run Repair to install the icon again"))),
+        },
+    }
+
+    let mime_cache = apps_dir.join("mimeinfo.cache");
+    if entry.get("MimeType").is_some() && (!mime_cache.exists() || is_newer(desktop_path, &mime_cache)) {
+        checks.push(check("Menu cache", Status::Warn, "mimeinfo.cache is older than the entry", Some("Run update-desktop-database on the applications directory")));
+    }
+    DoctorReport { checks }
+}
+
+pub fn free_space(path: &Path) -> Option<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Whether this user could write to `path`, or create it inside its nearest existing
+/// ancestor, judged with `access(2)` so nothing is written.
+pub fn is_writable(path: &Path) -> bool {
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else { return false };
+    let Ok(c_path) = CString::new(existing.as_os_str().as_bytes()) else { return false };
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Warns about apps (given by name) whose libfuse2-only runtime can't mount on this host.
+pub fn runtime_check(affected: &[String]) -> Check {
+    if affected.is_empty() {
+        return check("Runtimes", Status::Pass, "Every app's runtime can mount here", None);
+    }
+    check(
+        "Runtimes",
+        Status::Warn,
+        format!("{} need libfuse2, which isn't installed: {}", affected.len(), affected.join(", ")),
+        Some("Set their launch mode to extract-and-run, or install libfuse2"),
+    )
+}
+
+pub fn run(storage: &Path, apps_dir: &Path, sandboxed: bool) -> DoctorReport {
+    let mut checks = Vec::new();
+
+    checks.push(if crate::launch::fuse_available() {
+        check("FUSE", Status::Pass, "/dev/fuse and fusermount are available", None)
+    } else {
+        check("FUSE", Status::Warn, "AppImages can't be mounted", Some("Install fuse/libfuse2, or turn on extract-and-run"))
+    });
+
+    for (tool, purpose, fix) in [
+        ("update-desktop-database", "menus may not pick up new apps until re-login", "Install desktop-file-utils"),
+        ("gtk-update-icon-cache", "theme icons may show up late", "Install your distro's GTK icon cache tools"),
+        ("desktop-file-validate", "desktop entries can't be validated", "Install desktop-file-utils"),
+    ] {
+        checks.push(if on_path(tool) {
+            check(tool, Status::Pass, "Found on PATH", None)
+        } else {
+            check(tool, Status::Warn, format!("Not found; {purpose}"), Some(fix))
+        });
+    }
+
+    checks.push(menu_path_check(apps_dir));
+
+    checks.push(if sandboxed {
+        check("Sandbox", Status::Warn, "Running inside Flatpak; menu entries are only visible to the sandbox", Some("Use the native package to integrate with the host menu"))
+    } else {
+        check("Sandbox", Status::Pass, "Not sandboxed", None)
+    });
+
+    let probe = storage.join(".axec-doctor");
+    checks.push(match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            check("Storage", Status::Pass, format!("{} is writable", storage.display()), None)
+        }
+        Err(e) => check("Storage", Status::Fail, format!("Can't write to {}: {e}", storage.display()), Some("Check the directory's permissions and that its filesystem isn't read-only")),
+    });
+
+    checks.push(match free_space(storage) {
+        Some(free) if free < LOW_SPACE => check("Free space", Status::Warn, format!("Only {} MiB free in storage", free >> 20), Some("Free up space or run Clean up")),
+        Some(free) => check("Free space", Status::Pass, format!("{} MiB free in storage", free >> 20), None),
+        None => check("Free space", Status::Warn, "Couldn't determine free space", None),
+    });
+
+    DoctorReport { checks }
+}
@@ -0,0 +1,325 @@
+//! Fetches AppImages over HTTP, either from a plain (possibly signed) URL or from the
+//! latest release of a GitHub repo given as `owner/repo`.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use ureq::{http::Response, Agent, Body};
+
+const GITHUB_API: &str = "https://api.github.com";
+/// No timeout covers the whole transfer, since AppImages can be hundreds of MiB.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Unix seconds until which GitHub told us to stop calling its API.
+static RATE_LIMITED_UNTIL: AtomicU64 = AtomicU64::new(0);
+
+/// Why a download failed; auth problems are kept apart from network ones so the UI can
+/// ask for a token instead of suggesting a retry.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DownloadError {
+    /// 401/403: missing, wrong or insufficiently scoped credentials.
+    Unauthorized { status: u16, url: String },
+    NotFound { url: String },
+    /// GitHub's API quota is used up; `reset_at` is Unix seconds.
+    RateLimited { reset_at: Option<u64> },
+    Http { status: u16, url: String },
+    Network { message: String },
+    /// The release has no AppImage asset.
+    NoAsset { repo: String, tag: String },
+    Io { message: String },
+    /// Storing the file would take Axec past `max_storage_bytes`.
+    QuotaExceeded { over_by: u64 },
+    /// The download worked but the file couldn't be added.
+    Import { message: String },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Unauthorized { status, url } => write!(f, "Access denied ({status}) for {url}; check the token or headers"),
+            DownloadError::NotFound { url } => write!(f, "Not found: {url}"),
+            DownloadError::RateLimited { reset_at: Some(at) } => write!(f, "GitHub rate limit reached; resets at {at} (Unix time). A token raises the limit"),
+            DownloadError::RateLimited { reset_at: None } => write!(f, "GitHub rate limit reached; a token raises the limit"),
+            DownloadError::Http { status, url } => write!(f, "Server returned {status} for {url}"),
+            DownloadError::Network { message } => write!(f, "Network error: {message}"),
+            DownloadError::NoAsset { repo, tag } => write!(f, "Release {tag} of {repo} has no AppImage"),
+            DownloadError::Io { message } => write!(f, "{message}"),
+            DownloadError::QuotaExceeded { over_by } => write!(f, "Storage quota exceeded by {over_by} bytes"),
+            DownloadError::Import { message } => write!(f, "Downloaded, but could not add it: {message}"),
+        }
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(e: io::Error) -> Self {
+        DownloadError::Io { message: e.to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    /// API URL; with `Accept: application/octet-stream` it serves private assets too.
+    pub url: String,
+    /// Public download URL, which unlike `url` serves byte ranges and relative paths.
+    pub browser_download_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub assets: Vec<Asset>,
+}
+
+/// Whether `source` is an `owner/repo` shorthand rather than a URL.
+pub fn is_repo_shorthand(source: &str) -> bool {
+    let valid = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    matches!(source.split_once('/'), Some((owner, repo)) if valid(owner) && valid(repo))
+}
+
+fn agent() -> Agent {
+    Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .user_agent(concat!("axec/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .new_agent()
+}
+
+fn header_u64(response: &Response<Body>, name: &str) -> Option<u64> {
+    response.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// GETs `url` with `headers`, turning error statuses into `DownloadError`s.
+fn get(url: &str, headers: &[(String, String)]) -> Result<Response<Body>, DownloadError> {
+    let mut request = agent().get(url);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let response = request.call().map_err(|e| DownloadError::Network { message: e.to_string() })?;
+    let status = response.status().as_u16();
+    let url = url.to_string();
+    match status {
+        200..=299 => Ok(response),
+        403 | 429 if header_u64(&response, "x-ratelimit-remaining") == Some(0) => Err(DownloadError::RateLimited { reset_at: header_u64(&response, "x-ratelimit-reset") }),
+        401 | 403 => Err(DownloadError::Unauthorized { status, url }),
+        404 => Err(DownloadError::NotFound { url }),
+        _ => Err(DownloadError::Http { status, url }),
+    }
+}
+
+/// Headers for GitHub API calls: the caller's, plus a bearer `token` if given.
+pub fn github_headers(headers: &[(String, String)], token: Option<&str>) -> Vec<(String, String)> {
+    let mut out = vec![("Accept".to_string(), "application/vnd.github+json".to_string())];
+    out.extend(headers.iter().cloned());
+    if let Some(token) = token.filter(|t| !t.is_empty()) {
+        out.push(("Authorization".to_string(), format!("Bearer {token}")));
+    }
+    out
+}
+
+/// Fetches `repo`'s latest release; once rate limited, fails locally until the quota resets.
+pub fn latest_release(repo: &str, headers: &[(String, String)]) -> Result<Release, DownloadError> {
+    let until = RATE_LIMITED_UNTIL.load(Ordering::Relaxed);
+    if until > crate::now_millis() / 1000 {
+        return Err(DownloadError::RateLimited { reset_at: Some(until) });
+    }
+    let url = format!("{GITHUB_API}/repos/{repo}/releases/latest");
+    let result = get(&url, headers);
+    if let Err(DownloadError::RateLimited { reset_at: Some(at) }) = &result {
+        RATE_LIMITED_UNTIL.store(*at, Ordering::Relaxed);
+    }
+    let mut response = result?;
+    let body = response.body_mut().read_to_string().map_err(|e| DownloadError::Network { message: e.to_string() })?;
+    serde_json::from_str(&body).map_err(|e| DownloadError::Io { message: format!("Unexpected GitHub response: {e}") })
+}
+
+/// Names the running architecture goes by in asset file names.
+fn arch_aliases(arch: &str) -> &'static [&'static str] {
+    match arch {
+        "x86_64" => &["x86_64", "amd64", "x64"],
+        "aarch64" => &["aarch64", "arm64"],
+        "arm" => &["armhf", "armv7", "arm32"],
+        "x86" => &["i386", "i686", "x86"],
+        _ => &[],
+    }
+}
+
+/// Picks the release's AppImage for this machine: one naming our arch, else one naming no arch at all.
+pub fn pick_asset(release: &Release) -> Option<&Asset> {
+    let appimages: Vec<&Asset> = release.assets.iter().filter(|a| a.name.to_ascii_lowercase().ends_with(".appimage")).collect();
+    let ours = arch_aliases(std::env::consts::ARCH);
+    let all: Vec<&str> = ["x86_64", "aarch64", "arm", "x86"].iter().flat_map(|a| arch_aliases(a).iter().copied()).collect();
+    let mentions = |a: &Asset, names: &[&str]| {
+        let lower = a.name.to_ascii_lowercase();
+        names.iter().any(|n| lower.contains(n))
+    };
+    appimages
+        .iter()
+        .find(|a| mentions(a, ours))
+        .or_else(|| appimages.iter().find(|a| !mentions(a, &all)))
+        .copied()
+}
+
+/// File name a URL's last path segment gives, ignoring any signed-URL query string.
+fn file_name_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    if name.is_empty() {
+        "download.AppImage".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Streams `url` into `dir` as `name` (or the URL's file name) and returns the path.
+/// Stops early with `QuotaExceeded` once the file grows past `max_bytes`.
+pub fn download(url: &str, headers: &[(String, String)], dir: &Path, name: Option<&str>, max_bytes: Option<u64>) -> Result<PathBuf, DownloadError> {
+    download_with_progress(url, headers, dir, name, max_bytes, &mut |_, _| {})
+}
+
+/// `download`, calling `on_progress(received, total)` as data arrives; `total` is 0 when
+/// the server doesn't say.
+pub fn download_with_progress(
+    url: &str,
+    headers: &[(String, String)],
+    dir: &Path,
+    name: Option<&str>,
+    max_bytes: Option<u64>,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<PathBuf, DownloadError> {
+    let name = name.map(String::from).unwrap_or_else(|| file_name_from_url(url));
+    // Only the base name, so a crafted URL can't write outside `dir`
+    let name = Path::new(&name).file_name().map(|n| n.to_os_string()).unwrap_or_else(|| "download.AppImage".into());
+    let response = get(url, headers)?;
+    let limit = max_bytes.unwrap_or(u64::MAX);
+    let total = header_u64(&response, "content-length");
+    if let Some(len) = total.filter(|len| *len > limit) {
+        return Err(DownloadError::QuotaExceeded { over_by: len - limit });
+    }
+    let dest = dir.join(name);
+    let mut out = fs::File::create(&dest)?;
+    let mut body = response.into_body().into_reader().take(limit.saturating_add(1));
+    let mut buf = vec![0u8; 256 << 10];
+    let mut copied = 0u64;
+    loop {
+        let n = body.read(&mut buf).map_err(|e| DownloadError::Network { message: e.to_string() })?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        copied += n as u64;
+        on_progress(copied, total.unwrap_or(0));
+    }
+    if copied > limit {
+        return Err(DownloadError::QuotaExceeded { over_by: copied - limit });
+    }
+    out.sync_all()?;
+    Ok(dest)
+}
+
+/// The whole body of `url`, refusing more than `max_bytes`.
+pub fn fetch(url: &str, headers: &[(String, String)], max_bytes: u64) -> Result<Vec<u8>, DownloadError> {
+    let mut data = Vec::new();
+    get(url, headers)?
+        .into_body()
+        .into_reader()
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|e| DownloadError::Network { message: e.to_string() })?;
+    if data.len() as u64 > max_bytes {
+        return Err(DownloadError::Io { message: format!("{url} is larger than expected") });
+    }
+    Ok(data)
+}
+
+/// Bytes `start..=end` of `url`. Servers that ignore the `Range` header and send the
+/// whole file are an error, so callers can fall back to a plain download.
+pub fn fetch_range(url: &str, headers: &[(String, String)], start: u64, end: u64) -> Result<Vec<u8>, DownloadError> {
+    let mut headers = headers.to_vec();
+    headers.push(("Range".to_string(), format!("bytes={start}-{end}")));
+    let response = get(url, &headers)?;
+    if response.status().as_u16() != 206 {
+        return Err(DownloadError::Io { message: format!("{url} doesn't serve byte ranges") });
+    }
+    let mut data = Vec::new();
+    response
+        .into_body()
+        .into_reader()
+        .take(end - start + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| DownloadError::Network { message: e.to_string() })?;
+    if data.len() as u64 != end - start + 1 {
+        return Err(DownloadError::Network { message: format!("Range {start}-{end} of {url} ended early") });
+    }
+    Ok(data)
+}
+
+/// Most of a `.zsync` file read for its header; the block checksums after it aren't needed.
+const ZSYNC_HEADER_MAX: u64 = 64 << 10;
+
+/// What a `.zsync` control file's header says about the file it describes.
+#[derive(Debug, Clone)]
+pub struct ZsyncHeader {
+    pub url: String,
+    /// `Length:`, the file's size in bytes.
+    pub length: Option<u64>,
+}
+
+/// The file a `.zsync` control file describes, from its `URL:` header; a relative URL
+/// resolves against the control file's own.
+pub fn zsync_target(url: &str, headers: &[(String, String)]) -> Result<String, DownloadError> {
+    zsync_header(url, headers).map(|h| h.url)
+}
+
+/// Fetches and reads the header of the `.zsync` control file at `url`.
+pub fn zsync_header(url: &str, headers: &[(String, String)]) -> Result<ZsyncHeader, DownloadError> {
+    let mut head = Vec::new();
+    get(url, headers)?
+        .into_body()
+        .into_reader()
+        .take(ZSYNC_HEADER_MAX)
+        .read_to_end(&mut head)
+        .map_err(|e| DownloadError::Network { message: e.to_string() })?;
+    let text = String::from_utf8_lossy(&head);
+    let field = |name: &str| text.lines().take_while(|l| !l.is_empty()).find_map(|l| l.strip_prefix(name)).map(str::trim);
+    let target = field("URL:").ok_or_else(|| DownloadError::Io { message: format!("{url} doesn't name the file it describes") })?;
+    let length = field("Length:").and_then(|l| l.parse().ok());
+    Ok(ZsyncHeader { url: resolve_url(url, target), length })
+}
+
+/// `target` as a full URL, resolving a relative one against the URL of the file naming it.
+pub fn resolve_url(base_url: &str, target: &str) -> String {
+    if target.contains("://") {
+        return target.to_string();
+    }
+    let base = match target.strip_prefix('/') {
+        // Scheme and host only
+        Some(_) => base_url.match_indices('/').nth(2).map_or(base_url, |(i, _)| &base_url[..i]),
+        None => base_url.rsplit_once('/').map_or(base_url, |(dir, _)| dir),
+    };
+    format!("{base}/{}", target.trim_start_matches('/'))
+}
+
+/// Downloads the AppImage asset of `repo`'s latest release; returns it with the release tag.
+pub fn download_latest(
+    repo: &str,
+    headers: &[(String, String)],
+    dir: &Path,
+    max_bytes: Option<u64>,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<(PathBuf, String), DownloadError> {
+    let api_headers = github_headers(headers, None);
+    let release = latest_release(repo, &api_headers)?;
+    let asset = pick_asset(&release).ok_or_else(|| DownloadError::NoAsset { repo: repo.to_string(), tag: release.tag_name.clone() })?;
+    let mut asset_headers: Vec<(String, String)> = headers.to_vec();
+    asset_headers.push(("Accept".to_string(), "application/octet-stream".to_string()));
+    let path = download_with_progress(&asset.url, &asset_headers, dir, Some(&asset.name), max_bytes, on_progress)?;
+    Ok((path, release.tag_name))
+}
@@ -0,0 +1,507 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use crate::settings::IconFormat;
+use image::{imageops::FilterType, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fmt::Write as _,
+    fs,
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Icon extensions Axec stores next to the AppImages, in lookup order.
+pub const ICON_EXTS: [&str; 4] = ["png", "svg", "ico", "xpm"];
+
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+/// Largest frame an ICO can hold.
+const MAX_ICO_SIZE: u32 = 256;
+/// XPM output is scaled down to this, which also keeps its palette small.
+const XPM_SIZE: u32 = 48;
+/// Characters XPM pixels are spelled with; a space is reserved for transparency.
+const XPM_CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.#";
+
+/// Sizes the base hicolor `index.theme` declares; icons elsewhere aren't found by lookups.
+const HICOLOR_SIZES: [u32; 10] = [16, 22, 24, 32, 48, 64, 96, 128, 256, 512];
+
+pub fn find_icon(storage: &Path, id: &str) -> Option<PathBuf> {
+    ICON_EXTS.iter().map(|e| storage.join(format!("{id}.{e}"))).find(|p| p.exists())
+}
+
+/// The stored other-theme variant of `id`'s icon, `{id}.alt.{ext}`, if the image had one.
+pub fn find_alternate(storage: &Path, id: &str) -> Option<PathBuf> {
+    ICON_EXTS.iter().map(|e| storage.join(format!("{id}.alt.{e}"))).find(|p| p.exists())
+}
+
+/// Every icon file stored for `id`: the active one and any alternate.
+pub fn stored_icons(storage: &Path, id: &str) -> Vec<PathBuf> {
+    ICON_EXTS.iter().flat_map(|e| [storage.join(format!("{id}.{e}")), storage.join(format!("{id}.alt.{e}"))]).filter(|p| p.exists()).collect()
+}
+
+/// Which desktop theme an icon is drawn for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeVariant {
+    Light,
+    Dark,
+}
+
+/// Whether the desktop is dark, from `GTK_THEME` (e.g. `Adwaita:dark`), else GNOME's
+/// `color-scheme`. Light when neither says.
+pub fn system_variant() -> ThemeVariant {
+    if let Ok(theme) = std::env::var("GTK_THEME") {
+        let theme = theme.to_ascii_lowercase();
+        return if theme.ends_with(":dark") || theme.ends_with("-dark") { ThemeVariant::Dark } else { ThemeVariant::Light };
+    }
+    let scheme = Command::new("gsettings").args(["get", "org.gnome.desktop.interface", "color-scheme"]).stderr(Stdio::null()).output();
+    match scheme {
+        Ok(out) if String::from_utf8_lossy(&out.stdout).contains("prefer-dark") => ThemeVariant::Dark,
+        _ => ThemeVariant::Light,
+    }
+}
+
+/// The light and dark versions of `icon` when the image ships both side by side, as
+/// `app-dark.png` next to `app-light.png` or a plain `app.png`.
+pub fn theme_variants(root: &Path, icon: &Path) -> Option<(PathBuf, PathBuf)> {
+    let dir = icon.parent()?;
+    let stem = icon.file_stem()?.to_str()?;
+    let ext = icon.extension()?.to_str()?;
+    let base = stem.strip_suffix("-dark").or_else(|| stem.strip_suffix("-light")).unwrap_or(stem);
+    let sibling = |name: String| resolve_in_tree(root, &dir.join(name));
+    let dark = sibling(format!("{base}-dark.{ext}"))?;
+    let light = sibling(format!("{base}-light.{ext}")).or_else(|| sibling(format!("{base}.{ext}")))?;
+    (light != dark).then_some((light, dark))
+}
+
+/// Swaps `id`'s active icon with its alternate. Returns the new active icon, or `None`
+/// when there's no alternate.
+pub fn swap_alternate(storage: &Path, id: &str) -> std::io::Result<Option<PathBuf>> {
+    let (Some(active), Some(alternate)) = (find_icon(storage, id), find_alternate(storage, id)) else { return Ok(None) };
+    let ext = |p: &Path| p.extension().and_then(|e| e.to_str()).unwrap_or_default().to_string();
+    let parked = storage.join(format!(".{id}.icon.swap"));
+    let new_active = storage.join(format!("{id}.{}", ext(&alternate)));
+    fs::rename(&active, &parked)?;
+    fs::rename(&alternate, &new_active)?;
+    fs::rename(&parked, storage.join(format!("{id}.alt.{}", ext(&active))))?;
+    Ok(Some(new_active))
+}
+
+/// Symlink hops followed when resolving `.DirIcon`, so a link loop can't hang extraction.
+const MAX_LINK_HOPS: usize = 16;
+
+/// Queues `path`'s components on a stack, so the first pops first.
+fn push_components(pending: &mut Vec<OsString>, path: &Path) {
+    pending.extend(path.components().rev().filter_map(|c| match c {
+        Component::Normal(name) => Some(name.to_os_string()),
+        Component::ParentDir => Some("..".into()),
+        _ => None,
+    }));
+}
+
+/// Resolves `path` through any symlinks as if `root` were `/`: absolute targets point
+/// into the image, not the host, and nothing may climb out of `root`. Every component is
+/// resolved in turn, so a symlinked directory can't lead out of the tree either.
+fn resolve_in_tree(root: &Path, path: &Path) -> Option<PathBuf> {
+    // Components still to walk, last first
+    let mut pending = Vec::new();
+    push_components(&mut pending, path.strip_prefix(root).ok()?);
+    let mut resolved = root.to_path_buf();
+    let mut hops = 0;
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            if resolved == root {
+                return None;
+            }
+            resolved.pop();
+            continue;
+        }
+        let next = resolved.join(&name);
+        let Ok(target) = fs::read_link(&next) else {
+            resolved = next;
+            continue;
+        };
+        hops += 1;
+        if hops > MAX_LINK_HOPS {
+            return None;
+        }
+        if target.is_absolute() {
+            resolved = root.to_path_buf();
+        }
+        push_components(&mut pending, &target);
+    }
+    resolved.is_file().then_some(resolved)
+}
+
+/// The largest hicolor PNG named like `svg`, for launchers that render SVGs poorly.
+fn png_for_svg(root: &Path, svg: &Path) -> Option<PathBuf> {
+    let stem = svg.file_stem()?.to_str()?;
+    HICOLOR_SIZES.iter().rev().map(|size| root.join(format!("usr/share/icons/hicolor/{size}x{size}/apps/{stem}.png"))).find_map(|p| resolve_in_tree(root, &p))
+}
+
+/// Picks the best icon in an extracted AppImage tree: `.DirIcon`, then the largest hicolor size.
+/// `.DirIcon` is often a symlink into `usr/share/icons`; it is followed within the tree, and
+/// when it lands on an SVG a PNG of the same icon is preferred.
+pub fn find_in_tree(squash_root: &Path) -> Option<PathBuf> {
+    if let Some(dir_icon) = resolve_in_tree(squash_root, &squash_root.join(".DirIcon")) {
+        let is_svg = fs::read(&dir_icon).is_ok_and(|data| mime_type(&dir_icon, &data) == "image/svg+xml");
+        return Some(if is_svg { png_for_svg(squash_root, &dir_icon).unwrap_or(dir_icon) } else { dir_icon });
+    }
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for sub in [
+        "usr/share/icons/hicolor/256x256/apps",
+        "usr/share/icons/hicolor/128x128/apps",
+        "usr/share/icons/hicolor/64x64/apps",
+        "usr/share/pixmaps",
+    ] {
+        let dir = squash_root.join(sub);
+        if dir.is_dir() {
+            if let Ok(rd) = fs::read_dir(&dir) {
+                for e in rd.flatten() {
+                    let p = e.path();
+                    if let Some(ext) = p.extension().and_then(|s| s.to_str()) {
+                        if ICON_EXTS.contains(&ext.to_ascii_lowercase().as_str()) {
+                            candidates.push(p);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // Pick first existing candidate
+    candidates.into_iter().find_map(|p| resolve_in_tree(squash_root, &p))
+}
+
+/// MIME type of an icon, sniffed from its contents since `.DirIcon` has no extension.
+pub fn mime_type(path: &Path, data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        return "image/png";
+    }
+    if data.starts_with(&[0, 0, 1, 0]) {
+        return "image/x-icon";
+    }
+    if data.starts_with(b"/* XPM */") {
+        return "image/x-xpixmap";
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(512)]);
+    if head.contains("<svg") {
+        return "image/svg+xml";
+    }
+    match path.extension().and_then(|s| s.to_str()).map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("svg") => "image/svg+xml",
+        Some("ico") => "image/x-icon",
+        Some("xpm") => "image/x-xpixmap",
+        _ => "image/png",
+    }
+}
+
+/// File extension matching an icon's sniffed content.
+pub fn extension_for(path: &Path, data: &[u8]) -> &'static str {
+    match mime_type(path, data) {
+        "image/svg+xml" => "svg",
+        "image/x-icon" => "ico",
+        "image/x-xpixmap" => "xpm",
+        _ => "png",
+    }
+}
+
+pub fn data_uri(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    Some(encode_data_uri(path, &data))
+}
+
+pub fn encode_data_uri(path: &Path, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type(path, data), STANDARD.encode(data))
+}
+
+/// Whether the cached file is at least as new as its source.
+pub fn is_fresh(cached: &Path, source: &Path) -> bool {
+    let mtime = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    match (mtime(cached), mtime(source)) {
+        (Some(c), Some(s)) => c >= s,
+        _ => false,
+    }
+}
+
+fn render_svg(data: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let tree = resvg::usvg::Tree::from_data(data, &resvg::usvg::Options::default()).map_err(|e| e.to_string())?;
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(size, size).ok_or("Invalid thumbnail size")?;
+    let svg_size = tree.size();
+    let scale = size as f32 / svg_size.width().max(svg_size.height());
+    // Center the drawing when the SVG isn't square
+    let dx = (size as f32 - svg_size.width() * scale) / 2.0;
+    let dy = (size as f32 - svg_size.height() * scale) / 2.0;
+    let transform = resvg::tiny_skia::Transform::from_scale(scale, scale).post_translate(dx, dy);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+fn render_raster(data: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(data).map_err(|e| format!("Unsupported icon format: {e}"))?;
+    let scaled = if img.width() == size && img.height() == size { img } else { img.resize(size, size, FilterType::Lanczos3) };
+    let mut out = Cursor::new(Vec::new());
+    scaled.write_to(&mut out, ImageFormat::Png).map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+/// Re-encodes a Windows-style ICO as PNG, keeping its largest, deepest frame.
+pub fn ico_to_png(data: &[u8]) -> Result<Vec<u8>, String> {
+    // The ICO decoder picks the best entry of a multi-resolution file
+    let img = image::load_from_memory_with_format(data, ImageFormat::Ico).map_err(|e| format!("Unsupported icon format: {e}"))?;
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png).map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+/// Renders any stored icon, SVG included, to RGBA no larger than `max` on either side.
+fn load_rgba(path: &Path, max: u32) -> Result<RgbaImage, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let png = if mime_type(path, &data) == "image/svg+xml" { render_svg(&data, max)? } else { data };
+    let img = image::load_from_memory(&png).map_err(|e| format!("Unsupported icon format: {e}"))?;
+    let img = if img.width() > max || img.height() > max { img.resize(max, max, FilterType::Lanczos3) } else { img };
+    Ok(img.to_rgba8())
+}
+
+/// Writes `img` as an XPM; colors are rounded to 5 bits per channel and mostly transparent pixels become `None`.
+fn encode_xpm(img: &RgbaImage, name: &str) -> String {
+    let key = |p: &image::Rgba<u8>| (p[3] >= 128).then(|| [p[0] & 0xf8, p[1] & 0xf8, p[2] & 0xf8]);
+    let mut palette: HashMap<[u8; 3], usize> = HashMap::new();
+    for p in img.pixels().filter_map(key) {
+        let next = palette.len();
+        palette.entry(p).or_insert(next);
+    }
+    let cpp = if palette.len() < XPM_CHARS.len() { 1 } else { 2 };
+    let code = |i: usize| -> String {
+        if cpp == 1 {
+            (XPM_CHARS[i] as char).to_string()
+        } else {
+            format!("{}{}", XPM_CHARS[i / XPM_CHARS.len()] as char, XPM_CHARS[i % XPM_CHARS.len()] as char)
+        }
+    };
+    let blank = " ".repeat(cpp);
+    let ident: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let mut out = format!("/* XPM */\nstatic char *{ident}[] = {{\n\"{} {} {} {cpp}\",\n", img.width(), img.height(), palette.len() + 1);
+    let _ = writeln!(out, "\"{blank} c None\",");
+    let mut colors: Vec<(&[u8; 3], &usize)> = palette.iter().collect();
+    colors.sort_by_key(|(_, i)| **i);
+    for ([r, g, b], i) in colors {
+        let _ = writeln!(out, "\"{} c #{r:02X}{g:02X}{b:02X}\",", code(*i));
+    }
+    for (y, row) in img.rows().enumerate() {
+        let line: String = row.map(|p| key(p).map(|k| code(palette[&k])).unwrap_or_else(|| blank.clone())).collect();
+        let sep = if y + 1 == img.height() as usize { "" } else { "," };
+        let _ = writeln!(out, "\"{line}\"{sep}");
+    }
+    out.push_str("};\n");
+    out
+}
+
+/// Converts the icon at `src` to `format` and writes it to `dest`.
+pub fn convert(src: &Path, dest: &Path, format: IconFormat) -> Result<(), String> {
+    let bytes = match format {
+        IconFormat::Native => fs::read(src).map_err(|e| e.to_string())?,
+        IconFormat::Png | IconFormat::Ico => {
+            let img = image::DynamicImage::ImageRgba8(load_rgba(src, if format == IconFormat::Ico { MAX_ICO_SIZE } else { MAX_THUMBNAIL_SIZE })?);
+            let mut out = Cursor::new(Vec::new());
+            img.write_to(&mut out, if format == IconFormat::Ico { ImageFormat::Ico } else { ImageFormat::Png }).map_err(|e| e.to_string())?;
+            out.into_inner()
+        }
+        IconFormat::Xpm => {
+            let name = dest.file_stem().and_then(|s| s.to_str()).unwrap_or("icon");
+            encode_xpm(&load_rgba(src, XPM_SIZE)?, name).into_bytes()
+        }
+    };
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(dest, bytes).map_err(|e| e.to_string())
+}
+
+/// Returns `icon` scaled to `size` as a PNG data URI, cached as `{id}-{size}.png` in `cache_dir`.
+pub fn thumbnail(icon: &Path, cache_dir: &Path, id: &str, size: u32) -> Result<String, String> {
+    if size == 0 || size > MAX_THUMBNAIL_SIZE {
+        return Err(format!("Thumbnail size must be between 1 and {MAX_THUMBNAIL_SIZE}"));
+    }
+    let cached = cache_dir.join(format!("{id}-{size}.png"));
+    let png = if is_fresh(&cached, icon) {
+        fs::read(&cached).map_err(|e| e.to_string())?
+    } else {
+        let data = fs::read(icon).map_err(|e| e.to_string())?;
+        let is_svg = icon.extension().and_then(|s| s.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+        let png = if is_svg { render_svg(&data, size)? } else { render_raster(&data, size)? };
+        fs::create_dir_all(cache_dir).map_err(|e| e.to_string())?;
+        // A failed cache write only costs a re-render next time
+        let _ = fs::write(&cached, &png);
+        png
+    };
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+/// `icon` as a PNG no larger than `size`, for handing to the launcher portal.
+pub fn png_bytes(icon: &Path, size: u32) -> Result<Vec<u8>, String> {
+    let img = load_rgba(icon, size)?;
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png).map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+/// Installs `src` into the hicolor theme under `icons_root` as `name`, so desktop
+/// entries can reference it by name, and removes any other size of it. SVGs go to
+/// `scalable`; rasters are scaled to the nearest standard size not above their own.
+/// Returns whether anything on disk changed, so callers can skip rebuilding the icon cache.
+pub fn install_theme_icon(src: &Path, icons_root: &Path, name: &str) -> Result<bool, String> {
+    let data = fs::read(src).map_err(|e| e.to_string())?;
+    let (dest, content) = if mime_type(src, &data) == "image/svg+xml" {
+        (icons_root.join(format!("hicolor/scalable/apps/{name}.svg")), data)
+    } else {
+        let img = image::load_from_memory(&data).map_err(|e| format!("Unsupported icon format: {e}"))?;
+        let own = img.width().max(img.height());
+        let size = HICOLOR_SIZES.iter().rev().copied().find(|s| *s <= own).unwrap_or(HICOLOR_SIZES[0]);
+        (icons_root.join(format!("hicolor/{size}x{size}/apps/{name}.png")), render_raster(&data, size)?)
+    };
+    let stale = remove_theme_icons_except(icons_root, name, Some(&dest));
+    if fs::read(&dest).is_ok_and(|old| old == content) {
+        return Ok(stale);
+    }
+    fs::create_dir_all(dest.parent().unwrap()).map_err(|e| e.to_string())?;
+    fs::write(&dest, content).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Removes every installed size of the theme icon `name`. Returns whether any was there.
+pub fn remove_theme_icons(icons_root: &Path, name: &str) -> bool {
+    remove_theme_icons_except(icons_root, name, None)
+}
+
+fn remove_theme_icons_except(icons_root: &Path, name: &str, keep: Option<&Path>) -> bool {
+    let Ok(rd) = fs::read_dir(icons_root.join("hicolor")) else { return false };
+    let mut removed = false;
+    for size_dir in rd.flatten() {
+        for ext in ["png", "svg"] {
+            let path = size_dir.path().join(format!("apps/{name}.{ext}"));
+            if keep != Some(path.as_path()) && fs::remove_file(&path).is_ok() {
+                removed = true;
+            }
+        }
+    }
+    removed
+}
+
+/// Rebuilds the hicolor icon cache; best-effort since the tool may be absent.
+pub fn refresh_icon_cache(icons_root: &Path) {
+    let _ = Command::new("gtk-update-icon-cache")
+        .args(["-f", "-t"])
+        .arg(icons_root.join("hicolor"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::ico::{IcoEncoder, IcoFrame}, ExtendedColorType, Rgba};
+
+    const RED: Rgba<u8> = Rgba([200, 40, 40, 255]);
+
+    /// A `size`-square PNG, opaque red on the left half and transparent on the right.
+    fn half_red_png(size: u32) -> Vec<u8> {
+        let img = RgbaImage::from_fn(size, size, |x, _| if x < size / 2 { RED } else { Rgba([0, 0, 0, 0]) });
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn png_round_trips_through_ico() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.png");
+        fs::write(&src, half_red_png(32)).unwrap();
+        let ico = dir.path().join("app.ico");
+        convert(&src, &ico, IconFormat::Ico).unwrap();
+        let data = fs::read(&ico).unwrap();
+        assert_eq!(extension_for(&ico, &data), "ico");
+        let back = image::load_from_memory(&ico_to_png(&data).unwrap()).unwrap().to_rgba8();
+        assert_eq!(back.dimensions(), (32, 32));
+        assert_eq!(back.get_pixel(4, 4), &RED);
+        assert_eq!(back.get_pixel(28, 4)[3], 0);
+    }
+
+    #[test]
+    fn ico_to_png_keeps_the_largest_frame() {
+        let frame = |size: u32| {
+            let img = RgbaImage::from_pixel(size, size, RED);
+            IcoFrame::as_png(img.as_raw(), size, size, ExtendedColorType::Rgba8).unwrap()
+        };
+        let mut ico = Vec::new();
+        IcoEncoder::new(&mut ico).encode_images(&[frame(16), frame(48), frame(32)]).unwrap();
+        let png = ico_to_png(&ico).unwrap();
+        assert_eq!(mime_type(Path::new("icon"), &png), "image/png");
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8().dimensions(), (48, 48));
+    }
+
+    #[test]
+    fn png_converts_to_xpm_with_colors_and_transparency() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.png");
+        fs::write(&src, half_red_png(32)).unwrap();
+        let xpm = dir.path().join("my-app.xpm");
+        convert(&src, &xpm, IconFormat::Xpm).unwrap();
+        let data = fs::read(&xpm).unwrap();
+        assert_eq!(extension_for(&xpm, &data), "xpm");
+        let text = String::from_utf8(data).unwrap();
+        assert!(text.contains("static char *my_app[]"));
+        // Width, height, colors (red plus None) and characters per pixel
+        assert!(text.contains("\"32 32 2 1\""));
+        assert!(text.contains("\"  c None\""));
+        assert!(text.contains("\"a c #C82828\""));
+        assert!(text.contains(&format!("\"{}{}\"", "a".repeat(16), " ".repeat(16))));
+    }
+
+    #[test]
+    fn svg_converts_to_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.svg");
+        fs::write(&src, r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16"><rect width="16" height="16" fill="#c82828"/></svg>"##).unwrap();
+        let png = dir.path().join("app.png");
+        convert(&src, &png, IconFormat::Png).unwrap();
+        let img = image::load_from_memory(&fs::read(&png).unwrap()).unwrap().to_rgba8();
+        assert_eq!(img.width(), img.height());
+        assert_eq!(img.get_pixel(img.width() / 2, img.height() / 2), &RED);
+    }
+
+    #[test]
+    fn dir_icon_links_resolve_inside_the_tree_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("squashfs-root");
+        let apps = root.join("usr/share/icons/hicolor/256x256/apps");
+        fs::create_dir_all(&apps).unwrap();
+        fs::write(apps.join("app.png"), half_red_png(16)).unwrap();
+        let outside = dir.path().join("host");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("app.png"), half_red_png(16)).unwrap();
+
+        // A relative link, and one through a symlinked directory inside the tree
+        std::os::unix::fs::symlink("usr/share/icons/hicolor/256x256/apps/app.png", root.join(".DirIcon")).unwrap();
+        assert_eq!(find_in_tree(&root), Some(apps.join("app.png")));
+        std::os::unix::fs::symlink("icons/hicolor/256x256/apps", root.join("usr/share/pixmaps")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join("usr/share/pixmaps/app.png")), Some(apps.join("app.png")));
+        std::os::unix::fs::symlink("/usr/share/pixmaps", root.join("usr/share/linked")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join("usr/share/linked/app.png")), Some(apps.join("app.png")));
+
+        // Directory links that lead to the host, relative or absolute, resolve to nothing
+        std::os::unix::fs::symlink("../../../host", root.join("usr/share/escape")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join("usr/share/escape/app.png")), None);
+        std::os::unix::fs::symlink(&outside, root.join("usr/share/absolute")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join("usr/share/absolute/app.png")), None);
+        fs::remove_file(root.join(".DirIcon")).unwrap();
+        std::os::unix::fs::symlink("usr/share/escape/app.png", root.join(".DirIcon")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join(".DirIcon")), None);
+
+        // Link loops give up
+        std::os::unix::fs::symlink("loop", root.join("loop")).unwrap();
+        assert_eq!(resolve_in_tree(&root, &root.join("loop")), None);
+    }
+}
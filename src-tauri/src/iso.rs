@@ -0,0 +1,356 @@
+//! Minimal read-only ISO 9660 reader for type 1 AppImages.
+//!
+//! Understands the Rock Ridge extensions type 1 images are built with (long names,
+//! symlinks) and zisofs-compressed files, which is enough to unpack the tree.
+
+use flate2::read::ZlibDecoder;
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::fs::symlink,
+    path::Path,
+};
+
+const SECTOR: u64 = 2048;
+const ZISOFS_MAGIC: [u8; 8] = [0x37, 0xE4, 0x53, 0x96, 0xC9, 0xDB, 0xD6, 0x07];
+/// Nesting limit so a crafted image with looping directory extents can't recurse forever.
+const MAX_DEPTH: usize = 32;
+/// How many `CE` continuation areas one record may chain.
+const MAX_CONTINUATIONS: usize = 8;
+/// Largest directory extent read in one go; real ones are a few sectors.
+const MAX_DIR_SIZE: u32 = 16 << 20;
+/// Largest file unpacked, stored or after zisofs inflation.
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Reads the little-endian `u32` at `at`, failing on short input.
+fn le32(b: &[u8], at: usize) -> io::Result<u32> {
+    let bytes = b.get(at..at + 4).ok_or_else(|| invalid("Truncated ISO field"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+struct Record {
+    name: String,
+    extent: u32,
+    size: u32,
+    is_dir: bool,
+    symlink: Option<String>,
+    zisofs: bool,
+}
+
+struct Image {
+    file: File,
+    /// Size of the image, so no read reaches past its end.
+    len: u64,
+    /// Bytes to skip at the start of each record's system use area (from the `SP` entry).
+    susp_skip: usize,
+}
+
+impl Image {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        if offset.checked_add(len as u64).is_none_or(|end| end > self.len) {
+            return Err(invalid("ISO extent runs past the end of the image"));
+        }
+        let mut buf = vec![0u8; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Walks the SUSP entries of a record, following `CE` continuation areas.
+    fn susp_entries(&mut self, mut area: Vec<u8>) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut out = Vec::new();
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+            let mut i = 0;
+            while i + 4 <= area.len() {
+                let len = area[i + 2] as usize;
+                if len < 4 || i + len > area.len() {
+                    break;
+                }
+                let sig = String::from_utf8_lossy(&area[i..i + 2]).to_string();
+                let data = area[i + 4..i + len].to_vec();
+                if sig == "CE" && data.len() >= 24 {
+                    let len = le32(&data, 16)?;
+                    if len as u64 > SECTOR {
+                        return Err(invalid("ISO continuation area too large"));
+                    }
+                    continuation = Some((le32(&data, 0)? as u64 * SECTOR + le32(&data, 8)? as u64, len as usize));
+                } else if sig == "ST" {
+                    break;
+                } else {
+                    out.push((sig, data));
+                }
+                i += len;
+            }
+            match continuation {
+                Some((offset, len)) => area = self.read_at(offset, len)?,
+                None => break,
+            }
+        }
+        Ok(out)
+    }
+
+    fn parse_record(&mut self, raw: &[u8]) -> io::Result<Record> {
+        let extent = le32(raw, 2)?;
+        let size = le32(raw, 10)?;
+        let is_dir = raw.get(25).ok_or_else(|| invalid("Truncated ISO record"))? & 0x02 != 0;
+        let name_len = *raw.get(32).ok_or_else(|| invalid("Truncated ISO record"))? as usize;
+        let iso_name = raw.get(33..33 + name_len).ok_or_else(|| invalid("ISO record name runs past the record"))?;
+        let su_start = 33 + name_len + (1 - name_len % 2) + self.susp_skip;
+
+        let mut rr_name = String::new();
+        let mut symlink_target: Option<String> = None;
+        let mut zisofs = false;
+        if su_start < raw.len() {
+            for (sig, data) in self.susp_entries(raw[su_start..].to_vec())? {
+                match sig.as_str() {
+                    "NM" if !data.is_empty() => rr_name.push_str(&String::from_utf8_lossy(&data[1..])),
+                    "SL" if !data.is_empty() => {
+                        let target = symlink_target.get_or_insert_with(String::new);
+                        let mut j = 1;
+                        let mut continues = false;
+                        while j + 2 <= data.len() {
+                            let (flags, len) = (data[j], data[j + 1] as usize);
+                            let content = data.get(j + 2..j + 2 + len).unwrap_or_default();
+                            if !continues && !target.is_empty() && !target.ends_with('/') {
+                                target.push('/');
+                            }
+                            // Bit 0 means this component carries on in the next one
+                            continues = flags & 0x01 != 0;
+                            match flags & 0x0E {
+                                0x02 => target.push('.'),
+                                0x04 => target.push_str(".."),
+                                0x08 => target.push('/'),
+                                _ => target.push_str(&String::from_utf8_lossy(content)),
+                            }
+                            j += 2 + len;
+                        }
+                    }
+                    "ZF" if data.len() >= 2 && &data[..2] == b"pz" => zisofs = true,
+                    _ => {}
+                }
+            }
+        }
+        let name = if rr_name.is_empty() {
+            // Plain ISO names look like `FOO.TXT;1`
+            let plain = String::from_utf8_lossy(iso_name);
+            plain.split(';').next().unwrap_or_default().trim_end_matches('.').to_ascii_lowercase()
+        } else {
+            rr_name
+        };
+        Ok(Record { name, extent, size, is_dir, symlink: symlink_target, zisofs })
+    }
+
+    fn read_dir(&mut self, extent: u32, size: u32) -> io::Result<Vec<Record>> {
+        if size > MAX_DIR_SIZE {
+            return Err(invalid("ISO directory too large"));
+        }
+        let data = self.read_at(extent as u64 * SECTOR, size as usize)?;
+        let mut records = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let len = data[i] as usize;
+            if len == 0 {
+                // Records never straddle sectors; zero padding means "next sector"
+                i = (i / SECTOR as usize + 1) * SECTOR as usize;
+                continue;
+            }
+            if len < 34 || i + len > data.len() {
+                break;
+            }
+            let raw = &data[i..i + len];
+            // Skip the `.` and `..` entries
+            if !(raw[32] == 1 && (raw[33] == 0 || raw[33] == 1)) {
+                records.push(self.parse_record(raw)?);
+            }
+            i += len;
+        }
+        Ok(records)
+    }
+
+    fn read_file(&mut self, rec: &Record) -> io::Result<Vec<u8>> {
+        if rec.size as u64 > MAX_FILE_SIZE {
+            return Err(invalid("ISO file too large"));
+        }
+        let data = self.read_at(rec.extent as u64 * SECTOR, rec.size as usize)?;
+        if rec.zisofs && data.len() >= 16 && data[..8] == ZISOFS_MAGIC {
+            return unzisofs(&data);
+        }
+        Ok(data)
+    }
+
+    fn extract_dir(&mut self, extent: u32, size: u32, dest: &Path, depth: usize) -> io::Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("ISO directory tree too deep"));
+        }
+        fs::create_dir_all(dest)?;
+        for rec in self.read_dir(extent, size)? {
+            if rec.name.is_empty() || rec.name.contains('/') || rec.name == "." || rec.name == ".." {
+                continue;
+            }
+            let target = dest.join(&rec.name);
+            if let Some(link) = &rec.symlink {
+                let _ = symlink(link, &target);
+            } else if rec.is_dir {
+                self.extract_dir(rec.extent, rec.size, &target, depth + 1)?;
+            } else {
+                let data = self.read_file(&rec)?;
+                fs::write(&target, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decompresses a zisofs file: a header, a table of block pointers, then zlib blocks.
+fn unzisofs(data: &[u8]) -> io::Result<Vec<u8>> {
+    let total = le32(data, 8)? as usize;
+    if total as u64 > MAX_FILE_SIZE {
+        return Err(invalid("zisofs file too large"));
+    }
+    let (header_len, shift) = match data.get(12..14) {
+        Some(&[words, shift]) => (words as usize * 4, shift),
+        _ => return Err(invalid("Truncated zisofs header")),
+    };
+    // The format only defines 32, 64 and 128 KiB blocks
+    if !(15..=17).contains(&shift) {
+        return Err(invalid("Bad zisofs block size"));
+    }
+    let block_size = 1usize << shift;
+    let blocks = total.div_ceil(block_size);
+    let mut out = Vec::with_capacity(total);
+    for b in 0..blocks {
+        let ptr = header_len + b * 4;
+        let (start, end) = match (data.get(ptr..ptr + 4), data.get(ptr + 4..ptr + 8)) {
+            (Some(s), Some(e)) => (le32(s, 0)? as usize, le32(e, 0)? as usize),
+            _ => return Err(invalid("Truncated zisofs block table")),
+        };
+        let want = block_size.min(total - out.len());
+        if start == end {
+            // Empty blocks stand for zeros
+            out.resize(out.len() + want, 0);
+            continue;
+        }
+        let chunk = data.get(start..end).ok_or_else(|| invalid("Truncated zisofs block"))?;
+        // A block never holds more than its share of the file, however far it inflates
+        let mut block = Vec::with_capacity(want);
+        ZlibDecoder::new(chunk).take(want as u64).read_to_end(&mut block)?;
+        out.extend_from_slice(&block);
+    }
+    Ok(out)
+}
+
+/// Unpacks the whole ISO 9660 filesystem at `path` into `dest`.
+pub fn extract(path: &Path, dest: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut image = Image { file, len, susp_skip: 0 };
+    let pvd = image.read_at(16 * SECTOR, SECTOR as usize).map_err(|_| invalid("No ISO 9660 primary volume descriptor"))?;
+    if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return Err(invalid("No ISO 9660 primary volume descriptor"));
+    }
+    let root = &pvd[156..190];
+    let (extent, size) = (le32(root, 2)?, le32(root, 10)?);
+
+    // The root's `.` record carries the SUSP `SP` marker with the skip length
+    let first = image.read_at(extent as u64 * SECTOR, (size as usize).min(255))?;
+    let su = first.first().and_then(|&len| first.get(34..len as usize)).unwrap_or_default();
+    if su.len() >= 7 && &su[..2] == b"SP" {
+        image.susp_skip = su[6] as usize;
+    }
+    image.extract_dir(extent, size, dest, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn record(name: &[u8], extent: u32, size: u32, flags: u8) -> Vec<u8> {
+        let len = 33 + name.len() + (1 - name.len() % 2);
+        let mut rec = vec![0u8; len];
+        rec[0] = len as u8;
+        rec[2..6].copy_from_slice(&extent.to_le_bytes());
+        rec[6..10].copy_from_slice(&extent.to_be_bytes());
+        rec[10..14].copy_from_slice(&size.to_le_bytes());
+        rec[14..18].copy_from_slice(&size.to_be_bytes());
+        rec[25] = flags;
+        rec[32] = name.len() as u8;
+        rec[33..33 + name.len()].copy_from_slice(name);
+        rec
+    }
+
+    /// A plain ISO 9660 image holding just `HELLO.TXT` with `content`.
+    fn tiny_image(content: &[u8]) -> Vec<u8> {
+        let mut iso = vec![0u8; 19 * SECTOR as usize];
+        let pvd = &mut iso[16 * SECTOR as usize..];
+        pvd[0] = 1;
+        pvd[1..6].copy_from_slice(b"CD001");
+        pvd[6] = 1;
+        pvd[156..190].copy_from_slice(&record(&[0], 18, SECTOR as u32, 0x02));
+        let mut dir = record(&[0], 18, SECTOR as u32, 0x02);
+        dir.extend(record(&[1], 18, SECTOR as u32, 0x02));
+        dir.extend(record(b"HELLO.TXT;1", 19, content.len() as u32, 0));
+        iso[18 * SECTOR as usize..][..dir.len()].copy_from_slice(&dir);
+        iso.extend_from_slice(content);
+        iso
+    }
+
+    #[test]
+    fn type1_images_unpack() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.iso");
+        fs::write(&path, tiny_image(b"hello")).unwrap();
+        extract(&path, &dir.path().join("out")).unwrap();
+        assert_eq!(fs::read(dir.path().join("out/hello.txt")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn truncated_or_inconsistent_images_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.iso");
+        let iso = tiny_image(b"hello");
+        for cut in (0..iso.len()).step_by(512) {
+            fs::write(&path, &iso[..cut]).unwrap();
+            let err = extract(&path, &dir.path().join("out")).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "cut at {cut}");
+        }
+        // A file name running past its record
+        let mut bad = iso.clone();
+        let name_len = 18 * SECTOR as usize + 34 + 34 + 32;
+        assert_eq!(bad[name_len], 11);
+        bad[name_len] = 200;
+        fs::write(&path, &bad).unwrap();
+        assert!(extract(&path, &dir.path().join("bad")).is_err());
+        // A file claiming more data than the image has
+        let mut bad = iso;
+        bad[18 * SECTOR as usize + 34 + 34 + 10..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bad).unwrap();
+        assert!(extract(&path, &dir.path().join("big")).is_err());
+    }
+
+    #[test]
+    fn zisofs_headers_and_blocks_are_bounded() {
+        let mut chunk = ZlibEncoder::new(Vec::new(), Compression::default());
+        chunk.write_all(&[b'a'; 100]).unwrap();
+        let chunk = chunk.finish().unwrap();
+        let mut data = ZISOFS_MAGIC.to_vec();
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(&[4, 15, 0, 0]);
+        data.extend_from_slice(&24u32.to_le_bytes());
+        data.extend_from_slice(&(24 + chunk.len() as u32).to_le_bytes());
+        data.extend_from_slice(&chunk);
+        // The block inflates to 100 bytes but the file is only 10
+        assert_eq!(unzisofs(&data).unwrap(), [b'a'; 10]);
+        let mut bad = data.clone();
+        bad[13] = 64;
+        assert_eq!(unzisofs(&bad).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(unzisofs(&data[..12]).is_err());
+        assert!(unzisofs(&data[..20]).is_err());
+    }
+}
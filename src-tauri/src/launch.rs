@@ -0,0 +1,537 @@
+use crate::{
+    appimage::{self, RuntimeInfo, RuntimeKind},
+    settings::{self, Settings},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long `launch_app` watches a fresh child for an immediate runtime failure.
+const LAUNCH_WATCH_WINDOW: Duration = Duration::from_millis(500);
+
+/// How much of the start of the log an early exit reports.
+const STDERR_KEEP: u64 = 64 << 10;
+
+/// Messages the AppImage runtime prints when it can't set up its FUSE mount.
+const MOUNT_FAILURE_MARKERS: [&str; 4] = ["cannot mount", "fuse: ", "failed to open /dev/fuse", "libfuse.so"];
+
+/// Why a launch failed, with enough detail for the UI to suggest the right fix.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LaunchError {
+    /// The id has characters no stored app id can contain (e.g. `../`).
+    InvalidId { id: String },
+    NotFound { id: String },
+    NotExecutable { path: String },
+    /// The file has no valid ELF/AppImage header (corrupt or partial download).
+    InvalidAppImage { path: String, reason: String },
+    /// The runtime couldn't mount and the host has no usable FUSE.
+    FuseUnavailable { stderr: String },
+    /// FUSE exists but mounting still failed (e.g. read-only `/tmp`).
+    MountFailed { stderr: String },
+    /// The runtime couldn't locate its own file, usually because of a wrapper or a stripped environment.
+    RuntimeLostSelf { stderr: String },
+    /// The runtime needs libfuse2, which the host lacks (common on FUSE 3-only distros).
+    IncompatibleRuntime { version: Option<String>, stderr: String },
+    ExitedEarly { code: Option<i32>, stderr: String },
+    SpawnFailed { message: String },
+}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchError::InvalidId { id } => write!(f, "Invalid app id: {id:?}"),
+            LaunchError::NotFound { id } => write!(f, "AppImage not found: {id}"),
+            LaunchError::NotExecutable { path } => write!(f, "{path} is not executable"),
+            LaunchError::InvalidAppImage { path, reason } => write!(f, "{path} is not a valid AppImage: {reason}"),
+            LaunchError::FuseUnavailable { stderr } => write!(f, "FUSE is not available; install libfuse2 or enable extract-and-run ({stderr})"),
+            LaunchError::MountFailed { stderr } => write!(f, "The AppImage could not be mounted; set a writable mount directory or enable extract-and-run ({stderr})"),
+            LaunchError::RuntimeLostSelf { stderr } => write!(f, "The AppImage runtime could not find its own file; try without an exec wrapper or with clean_env off ({stderr})"),
+            LaunchError::IncompatibleRuntime { version, stderr } => write!(
+                f,
+                "This AppImage's runtime{} needs libfuse2, which isn't installed; install libfuse2 or set its launch mode to extract-and-run ({stderr})",
+                version.as_ref().map(|v| format!(" ({v})")).unwrap_or_default()
+            ),
+            LaunchError::ExitedEarly { code: Some(code), stderr } => write!(f, "App exited with code {code}: {stderr}"),
+            LaunchError::ExitedEarly { code: None, stderr } => write!(f, "App was killed by a signal: {stderr}"),
+            LaunchError::SpawnFailed { message } => write!(f, "Failed to start app: {message}"),
+        }
+    }
+}
+
+/// How an app's files get mounted when it launches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchMode {
+    /// FUSE, falling back to extract-and-run when mounting fails.
+    #[default]
+    Auto,
+    /// Only ever mount; ignores the `extract_and_run` setting.
+    Fuse,
+    /// Run from a cached extraction, never mounting.
+    ExtractAndRun,
+}
+
+/// How to run an app, beyond which file it is.
+pub struct LaunchOptions<'a> {
+    pub args: &'a [String],
+    pub wrapper: Option<&'a str>,
+    pub terminal: bool,
+    pub scope: Option<&'a Scope>,
+    /// Run `AppRun` from this extracted tree instead of the AppImage itself.
+    pub extracted: Option<&'a Path>,
+    /// Extra variables, set after everything else.
+    pub env: &'a [(&'static str, String)],
+    /// A test run: watch the app this long instead of `LAUNCH_WATCH_WINDOW`, then stop it.
+    pub trial: Option<Duration>,
+}
+
+/// Placeholder an `exec_wrapper` template uses for the AppImage and its args.
+pub const COMMAND_PLACEHOLDER: &str = "%command%";
+
+/// Splits a wrapper template into words, honouring single and double quotes.
+pub fn split_wrapper(template: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote in exec wrapper".into());
+    }
+    words.extend(word);
+    if words.iter().filter(|w| *w == COMMAND_PLACEHOLDER).count() != 1 {
+        return Err(format!("Exec wrapper must contain {COMMAND_PLACEHOLDER} exactly once"));
+    }
+    Ok(words)
+}
+
+/// Terminal emulators tried for `Terminal=true` apps, with the flag that runs a command.
+const TERMINALS: [(&str, &str); 8] = [
+    ("x-terminal-emulator", "-e"),
+    ("gnome-terminal", "--"),
+    ("konsole", "-e"),
+    ("xfce4-terminal", "-x"),
+    ("kitty", "--"),
+    ("alacritty", "-e"),
+    ("foot", "--"),
+    ("xterm", "-e"),
+];
+
+/// The command prefix that runs a program in a new terminal window: `$TERMINAL` if set,
+/// else the first known emulator on `PATH`.
+fn terminal_prefix() -> Option<Vec<OsString>> {
+    if let Some(term) = std::env::var_os("TERMINAL").filter(|t| !t.is_empty()) {
+        return Some(vec![term, "-e".into()]);
+    }
+    let paths = std::env::var_os("PATH")?;
+    TERMINALS
+        .iter()
+        .find_map(|(bin, flag)| std::env::split_paths(&paths).map(|d| d.join(bin)).find(|p| p.is_file()).map(|p| vec![p.into_os_string(), (*flag).into()]))
+}
+
+/// What the runtime prints when it can't resolve the path of its own AppImage.
+const SELF_LOOKUP_MARKERS: [&str; 4] = ["/proc/self/exe", "cannot find itself", "path to self", "failed to get fs offset"];
+
+/// Variables that belong to some other AppImage's runtime, Axec's own included when it
+/// runs as one; a child that inherits them looks for files in the wrong image.
+const FOREIGN_RUNTIME_VARS: [&str; 4] = ["APPIMAGE", "APPDIR", "OWD", "ARGV0"];
+
+/// What a `clean_env` launch keeps: enough for a desktop session, nothing more.
+const SAFE_ENV_VARS: [&str; 16] = [
+    "HOME", "USER", "LOGNAME", "SHELL", "PATH", "LANG", "LANGUAGE", "TERM", "DISPLAY", "WAYLAND_DISPLAY", "XAUTHORITY",
+    "DBUS_SESSION_BUS_ADDRESS", "PULSE_SERVER", "DESKTOP_SESSION", "TZ", "TMPDIR",
+];
+
+fn is_safe_env_var(key: &str) -> bool {
+    SAFE_ENV_VARS.contains(&key) || key.starts_with("LC_") || key.starts_with("XDG_")
+}
+
+/// The variables of `vars` a `clean_env` launch passes on.
+fn clean_env(vars: impl Iterator<Item = (OsString, OsString)>) -> impl Iterator<Item = (OsString, OsString)> {
+    vars.filter(|(k, _)| k.to_str().is_some_and(is_safe_env_var))
+}
+
+/// Sets up the environment the runtime expects: `ARGV0` naming the AppImage, and none
+/// of another image's runtime variables.
+fn apply_app_env(cmd: &mut Command, settings: &Settings, app_path: &Path) {
+    if settings.clean_env {
+        cmd.env_clear();
+        cmd.envs(clean_env(std::env::vars_os()));
+    } else {
+        for var in FOREIGN_RUNTIME_VARS {
+            cmd.env_remove(var);
+        }
+        // Our own AppImage's bundled libraries would shadow the child's
+        if std::env::var_os("APPDIR").is_some() {
+            cmd.env_remove("LD_LIBRARY_PATH");
+        }
+    }
+    cmd.env("ARGV0", app_path);
+}
+
+/// A transient systemd scope to run the app in, with optional resource limits.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    /// Unit name, including the `.scope` suffix.
+    pub unit: String,
+    pub memory_max: Option<String>,
+    pub cpu_quota: Option<String>,
+}
+
+/// Whether `systemd-run --user --scope` can work: systemd is PID 1 and the user
+/// manager is reachable.
+pub fn systemd_scope_available() -> bool {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(std::path::PathBuf::from);
+    let on_path = std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|d| d.join("systemd-run").is_file()));
+    on_path && Path::new("/run/systemd/system").is_dir() && runtime_dir.is_some_and(|d| d.join("systemd").is_dir())
+}
+
+/// Checks a `MemoryMax=` value: bytes with an optional K/M/G/T suffix, a percentage, or `infinity`.
+pub fn validate_memory_max(value: &str) -> Result<(), String> {
+    let digits = value.strip_suffix(['K', 'M', 'G', 'T', '%']).unwrap_or(value);
+    if value == "infinity" || (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())) {
+        Ok(())
+    } else {
+        Err(format!("Invalid memory limit {value:?}; use e.g. 2G, 512M or 50%"))
+    }
+}
+
+/// Checks a `CPUQuota=` value, a percentage of one CPU such as `150%`.
+pub fn validate_cpu_quota(value: &str) -> Result<(), String> {
+    match value.strip_suffix('%') {
+        Some(n) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => Ok(()),
+        _ => Err(format!("Invalid CPU quota {value:?}; use a percentage like 50% or 200%")),
+    }
+}
+
+/// The GUI toolkit an AppImage bundles, which decides how it's told to scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Toolkit {
+    Gtk,
+    Qt,
+}
+
+/// Library directories searched by `detect_toolkit`, and how deep.
+const BUNDLED_LIB_DIRS: [&str; 3] = ["usr/lib", "usr/lib64", "lib"];
+const LIB_SCAN_DEPTH: usize = 2;
+
+/// Guesses the toolkit from the libraries bundled in an extracted tree: `libQt*` or
+/// `libgtk-*`. `None` when it has neither, or both.
+pub fn detect_toolkit(squash_root: &Path) -> Option<Toolkit> {
+    fn scan(dir: &Path, depth: usize, found: &mut (bool, bool)) {
+        let Ok(rd) = fs::read_dir(dir) else { return };
+        for entry in rd.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            found.0 |= name.starts_with("libgtk-");
+            found.1 |= name.starts_with("libQt");
+            if depth > 0 && entry.file_type().is_ok_and(|t| t.is_dir()) {
+                scan(&entry.path(), depth - 1, found);
+            }
+        }
+    }
+    let mut found = (false, false);
+    for dir in BUNDLED_LIB_DIRS {
+        scan(&squash_root.join(dir), LIB_SCAN_DEPTH, &mut found);
+    }
+    match found {
+        (true, false) => Some(Toolkit::Gtk),
+        (false, true) => Some(Toolkit::Qt),
+        _ => None,
+    }
+}
+
+pub fn validate_scale_factor(value: f32) -> Result<(), String> {
+    if value.is_finite() && (0.5..=4.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("Invalid scale factor {value}; use a value from 0.5 to 4"))
+    }
+}
+
+/// Variables that make `toolkit` scale by `scale`; both families when it's unknown.
+/// GTK only scales by whole numbers, so the fraction goes to `GDK_DPI_SCALE`.
+pub fn scale_env(scale: f32, toolkit: Option<Toolkit>) -> Vec<(&'static str, String)> {
+    let mut env = Vec::new();
+    if toolkit != Some(Toolkit::Qt) {
+        let whole = scale.round().max(1.0);
+        env.push(("GDK_SCALE", format!("{whole}")));
+        env.push(("GDK_DPI_SCALE", format!("{}", scale / whole)));
+    }
+    if toolkit != Some(Toolkit::Gtk) {
+        env.push(("QT_AUTO_SCREEN_SCALE_FACTOR", "0".to_string()));
+        env.push(("QT_SCALE_FACTOR", format!("{scale}")));
+    }
+    env
+}
+
+fn scope_prefix(scope: &Scope) -> Vec<OsString> {
+    let mut prefix: Vec<OsString> = ["systemd-run", "--user", "--scope", "--quiet", "--collect"].map(OsString::from).to_vec();
+    prefix.push(format!("--unit={}", scope.unit).into());
+    if let Some(max) = &scope.memory_max {
+        prefix.push(format!("--property=MemoryMax={max}").into());
+    }
+    if let Some(quota) = &scope.cpu_quota {
+        prefix.push(format!("--property=CPUQuota={quota}").into());
+    }
+    prefix.push("--".into());
+    prefix
+}
+
+fn is_mount_failure(stderr: &str) -> bool {
+    let lower = stderr.to_ascii_lowercase();
+    MOUNT_FAILURE_MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// Whether the host can mount AppImages: a FUSE device plus a `fusermount` helper.
+pub fn fuse_available() -> bool {
+    let has_helper = std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|d| d.join("fusermount").exists() || d.join("fusermount3").exists()))
+        .unwrap_or(false);
+    Path::new("/dev/fuse").exists() && has_helper
+}
+
+/// Directories the dynamic loader searches for `libfuse.so.2` on common distros.
+const LIB_DIRS: [&str; 4] = ["/usr/lib", "/usr/lib64", "/lib", "/lib64"];
+
+/// Whether `libfuse.so.2` is installed, which libfuse2 runtimes need to mount at all.
+pub fn libfuse2_available() -> bool {
+    LIB_DIRS.iter().any(|dir| {
+        let dir = Path::new(dir);
+        // Debian-style multiarch dirs, e.g. /usr/lib/x86_64-linux-gnu
+        let multiarch = fs::read_dir(dir).into_iter().flatten().flatten().map(|e| e.path()).filter(|p| p.to_string_lossy().ends_with("-linux-gnu"));
+        std::iter::once(dir.to_path_buf()).chain(multiarch).any(|d| d.join("libfuse.so.2").exists())
+    })
+}
+
+/// Whether `runtime` is known not to mount on this host, so only extract-and-run works.
+pub fn runtime_broken_here(runtime: &RuntimeInfo) -> bool {
+    runtime.kind == RuntimeKind::Libfuse2 && !libfuse2_available()
+}
+
+fn apply_runtime_env(cmd: &mut Command, settings: &Settings, id: &str) -> io::Result<()> {
+    // The runtime mounts under $TMPDIR, so point it at a writable dir when /tmp is unusable
+    if let Some(mount_dir) = settings::mount_dir_for(settings, id) {
+        fs::create_dir_all(&mount_dir)?;
+        cmd.env("TMPDIR", mount_dir);
+    }
+    if settings.extract_and_run {
+        cmd.env("APPIMAGE_EXTRACT_AND_RUN", "1");
+    }
+    Ok(())
+}
+
+/// Checks the things that make a launch fail before anything is spawned.
+fn preflight(app_path: &Path, id: &str) -> Result<(), LaunchError> {
+    let path = app_path.to_string_lossy().to_string();
+    if fs::metadata(app_path).is_err() {
+        return Err(LaunchError::NotFound { id: id.to_string() });
+    }
+    if !crate::is_executable(app_path) {
+        return Err(LaunchError::NotExecutable { path });
+    }
+    appimage::detect_type(app_path).map_err(|e| LaunchError::InvalidAppImage { path, reason: e.to_string() })?;
+    Ok(())
+}
+
+fn spawn_error(app_path: &Path, err: io::Error) -> LaunchError {
+    let path = app_path.to_string_lossy().to_string();
+    match (err.kind(), err.raw_os_error()) {
+        (io::ErrorKind::PermissionDenied, _) => LaunchError::NotExecutable { path },
+        // ENOEXEC: typically a runtime for another architecture
+        (_, Some(8)) => LaunchError::InvalidAppImage { path, reason: err.to_string() },
+        _ => LaunchError::SpawnFailed { message: err.to_string() },
+    }
+}
+
+/// Spawns the AppImage in its own process group, watching it briefly so early runtime
+/// failures are reported. Returns the child unless it already exited cleanly.
+///
+/// Stdout and stderr both go to `log_path`, which is truncated on each launch. With
+/// `terminal`, the whole command runs inside a terminal emulator instead, and with
+/// `scope` all of it runs in that systemd scope; `systemd-run --scope` execs in place,
+/// so the child is still the app's process group leader.
+///
+/// With `trial`, an app still running at the end of the window is stopped, and the
+/// child returned has already exited.
+pub fn launch(app_path: &Path, id: &str, settings: &Settings, options: &LaunchOptions, log_path: &Path) -> Result<Option<Child>, LaunchError> {
+    let LaunchOptions { args, wrapper, terminal, scope, extracted, env, trial } = *options;
+    let program = match extracted {
+        Some(root) => {
+            let app_run = root.join("AppRun");
+            if !crate::is_executable(&app_run) {
+                return Err(LaunchError::NotExecutable { path: app_run.to_string_lossy().to_string() });
+            }
+            app_run
+        }
+        None => {
+            preflight(app_path, id)?;
+            app_path.to_path_buf()
+        }
+    };
+    let mut argv: Vec<OsString> = vec![program.into()];
+    argv.extend(args.iter().map(OsString::from));
+    if let Some(template) = wrapper {
+        let words = split_wrapper(template).map_err(|message| LaunchError::SpawnFailed { message })?;
+        argv = words.into_iter().flat_map(|w| if w == COMMAND_PLACEHOLDER { argv.clone() } else { vec![w.into()] }).collect();
+    }
+    if terminal {
+        let mut prefix = terminal_prefix().ok_or_else(|| LaunchError::SpawnFailed { message: "This is a terminal app, but no terminal emulator was found; set $TERMINAL".to_string() })?;
+        prefix.append(&mut argv);
+        argv = prefix;
+    }
+    if let Some(scope) = scope {
+        let mut prefix = scope_prefix(scope);
+        prefix.append(&mut argv);
+        argv = prefix;
+    }
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    cmd.process_group(0);
+    apply_app_env(&mut cmd, settings, app_path);
+    if let Some(root) = extracted {
+        // What the runtime would have set up for AppRun
+        cmd.env("APPDIR", root).env("APPIMAGE", app_path);
+    }
+    apply_runtime_env(&mut cmd, settings, id).map_err(|e| LaunchError::SpawnFailed { message: format!("Failed to prepare mount directory: {e}") })?;
+    cmd.envs(env.iter().cloned());
+    let log_err = |e: io::Error| LaunchError::SpawnFailed { message: format!("Failed to open log file: {e}") };
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent).map_err(log_err)?;
+    }
+    // A file rather than a pipe, so the app keeps its stderr after Axec quits
+    let log = File::create(log_path).map_err(log_err)?;
+    let mut child = cmd
+        .stdout(log.try_clone().map_err(log_err)?)
+        .stderr(log)
+        .spawn()
+        .map_err(|e| spawn_error(app_path, e))?;
+
+    let started = Instant::now();
+    while started.elapsed() < trial.unwrap_or(LAUNCH_WATCH_WINDOW) {
+        let status = child.try_wait().map_err(|e| LaunchError::SpawnFailed { message: e.to_string() })?;
+        if let Some(status) = status {
+            if status.success() {
+                return Ok(None);
+            }
+            let stderr = log_start(log_path);
+            let lower = stderr.to_ascii_lowercase();
+            if SELF_LOOKUP_MARKERS.iter().any(|m| lower.contains(m)) {
+                return Err(LaunchError::RuntimeLostSelf { stderr });
+            }
+            if is_mount_failure(&stderr) && !settings.extract_and_run {
+                return Err(if fuse_available() { LaunchError::MountFailed { stderr } } else { LaunchError::FuseUnavailable { stderr } });
+            }
+            return Err(LaunchError::ExitedEarly { code: status.code(), stderr });
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    if trial.is_some() {
+        crate::process::terminate(&mut child, scope.map(|s| s.unit.as_str()));
+    }
+    Ok(Some(child))
+}
+
+/// The start of what the app wrote to `log_path`, for diagnosing an early exit.
+fn log_start(log_path: &Path) -> String {
+    let mut start = Vec::new();
+    if let Ok(file) = File::open(log_path) {
+        let _ = file.take(STDERR_KEEP).read_to_end(&mut start);
+    }
+    String::from_utf8_lossy(&start).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const NO_OPTIONS: LaunchOptions<'static> = LaunchOptions { args: &[], wrapper: None, terminal: false, scope: None, extracted: None, env: &[], trial: None };
+
+    #[test]
+    fn clean_env_keeps_only_session_variables() {
+        let vars = [
+            "HOME", "PATH", "DISPLAY", "WAYLAND_DISPLAY", "DBUS_SESSION_BUS_ADDRESS", "LANG", "LC_ALL", "LC_TIME", "XDG_RUNTIME_DIR", "XDG_DATA_DIRS",
+            "LD_LIBRARY_PATH", "LD_PRELOAD", "APPIMAGE", "APPDIR", "OWD", "ARGV0", "PYTHONPATH", "GTK_PATH", "QT_PLUGIN_PATH", "SSH_AUTH_SOCK", "home",
+        ];
+        let kept: Vec<OsString> = clean_env(vars.iter().map(|k| (OsString::from(k), OsString::from("x")))).map(|(k, _)| k).collect();
+        assert_eq!(kept, ["HOME", "PATH", "DISPLAY", "WAYLAND_DISPLAY", "DBUS_SESSION_BUS_ADDRESS", "LANG", "LC_ALL", "LC_TIME", "XDG_RUNTIME_DIR", "XDG_DATA_DIRS"].map(OsString::from));
+    }
+
+    #[test]
+    fn clean_env_launch_still_names_the_appimage() {
+        let settings = Settings { clean_env: true, ..Settings::default() };
+        let mut cmd = Command::new("true");
+        apply_app_env(&mut cmd, &settings, Path::new("/apps/tool.AppImage"));
+        let envs: Vec<(&std::ffi::OsStr, Option<&std::ffi::OsStr>)> = cmd.get_envs().collect();
+        assert!(envs.contains(&("ARGV0".as_ref(), Some("/apps/tool.AppImage".as_ref()))));
+        assert!(envs.iter().all(|(k, _)| *k == "ARGV0" || k.to_str().is_some_and(is_safe_env_var)));
+    }
+
+    #[test]
+    fn missing_file_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = launch(&dir.path().join("gone.AppImage"), "gone", &Settings::default(), &NO_OPTIONS, &dir.path().join("gone.log"));
+        assert!(matches!(result, Err(LaunchError::NotFound { id }) if id == "gone"));
+    }
+
+    #[test]
+    fn non_executable_file_is_rejected_before_spawning() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = dir.path().join("app.AppImage");
+        fs::write(&app, b"\x7fELF").unwrap();
+        fs::set_permissions(&app, fs::Permissions::from_mode(0o644)).unwrap();
+        let log = dir.path().join("app.log");
+        let result = launch(&app, "app", &Settings::default(), &NO_OPTIONS, &log);
+        assert!(matches!(result, Err(LaunchError::NotExecutable { path }) if path == app.to_string_lossy()));
+        // Nothing ran, so nothing was logged
+        assert!(!log.exists());
+    }
+
+    #[test]
+    fn early_exits_report_the_start_of_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_run = dir.path().join("AppRun");
+        fs::write(&app_run, "#!/bin/sh\necho starting\necho 'missing libfoo.so' >&2\nexit 3\n").unwrap();
+        fs::set_permissions(&app_run, fs::Permissions::from_mode(0o755)).unwrap();
+        let log = dir.path().join("app.log");
+        let options = LaunchOptions { extracted: Some(dir.path()), ..NO_OPTIONS };
+        let result = launch(&dir.path().join("app.AppImage"), "app", &Settings::default(), &options, &log);
+        assert!(matches!(result, Err(LaunchError::ExitedEarly { code: Some(3), stderr }) if stderr == "starting\nmissing libfoo.so"));
+        assert_eq!(fs::read_to_string(&log).unwrap(), "starting\nmissing libfoo.so\n");
+    }
+
+    #[test]
+    fn trial_launches_are_stopped_at_the_end_of_the_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_run = dir.path().join("AppRun");
+        fs::write(&app_run, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        fs::set_permissions(&app_run, fs::Permissions::from_mode(0o755)).unwrap();
+        let options = LaunchOptions { extracted: Some(dir.path()), trial: Some(Duration::from_millis(300)), ..NO_OPTIONS };
+        let started = Instant::now();
+        let mut child = launch(&dir.path().join("app.AppImage"), "app", &Settings::default(), &options, &dir.path().join("app.log")).unwrap().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
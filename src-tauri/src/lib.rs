@@ -1,17 +1,76 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{self, Write},
-    os::unix::fs::PermissionsExt,
+    fs, io,
+    os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt},
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tauri::AppHandle;
 
+mod appimage;
+mod appstream;
+mod bundle;
+mod commands;
+mod compact;
+mod desktop;
+mod doctor;
+mod download;
+mod icons;
+mod iso;
+mod launch;
+mod license;
+mod metadata;
+mod plan;
+mod portal;
+mod process;
+mod rpc;
+mod settings;
+mod squashfs;
+mod storage;
+mod trash;
+mod tray;
+mod update;
+mod zsync;
+
+use process::Supervisor;
+use settings::Settings;
+use tauri::{AppHandle, Emitter, Manager};
 
-const STORAGE_DIR: &str = ".local/share/axec/appimages";
 const APPLICATIONS_DIR: &str = ".local/share/applications";
 
+/// Files `import_directory` and icon rescans work on at once when `max_parallel_imports`
+/// is unset.
+const DEFAULT_PARALLEL_IMPORTS: usize = 2;
+
+/// Apps update checks and `update_all` work on at once when `max_parallel_updates` is
+/// unset. Checks mostly wait on the network, so they can overlap more than imports.
+const DEFAULT_PARALLEL_UPDATES: usize = 4;
+
+/// Runs `f` over `items` on up to `workers` threads, each taking the next unclaimed item,
+/// and returns the results in item order.
+fn for_each_parallel<T: Sync, R: Send>(items: &[T], workers: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(i) else { break };
+                let result = f(item);
+                results.lock().unwrap().push((i, result));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
 fn in_flatpak_sandbox() -> bool {
     std::env::var("FLATPAK_ID").is_ok() || std::env::var("container").map(|v| v == "flatpak").unwrap_or(false)
 }
@@ -23,31 +82,158 @@ pub struct AppImageEntry {
     pub path: String,
     pub icon_path: Option<String>,
     pub desktop_file: String,
+    pub size: u64,
+    /// Resolved, so inherited categories show up here too.
+    pub categories: Vec<String>,
+    pub parent: Option<String>,
+    pub order: Option<u32>,
+    pub pinned: bool,
+    /// Whether the app has a desktop menu entry.
+    pub integrated: bool,
+    /// Unix millis of the first import.
+    pub added_at: Option<u64>,
+    /// The embedded runtime, for apps added since Axec started recording it.
+    pub runtime: Option<appimage::RuntimeInfo>,
+    /// SPDX license id, when the app declares or bundles one.
+    pub license: Option<String>,
+    /// The embedded `X-AppImage-BuildId`, which tells rebuilds of one version apart.
+    pub build_id: Option<String>,
+    pub notes: String,
+    /// The app's own version, so apps whose names collide can be told apart.
+    pub version: Option<String>,
+    /// From the embedded entry or AppStream summary.
+    pub comment: Option<String>,
+    /// File types the app opens, from its embedded entry or AppStream.
+    pub mime_types: Vec<String>,
 }
 
-fn ensure_dirs() -> io::Result<(PathBuf, PathBuf)> {
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Name,
+    /// The order saved with `set_order`; apps without a position go last.
+    Manual,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Last resort data dir for sessions with neither `$XDG_DATA_HOME` nor `$HOME`. Its name
+/// is predictable and `/var/tmp` is world-writable, so it's only used when it is a real
+/// dir owned by us and closed to everyone else.
+fn fallback_data_dir() -> Option<PathBuf> {
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    let path = PathBuf::from(format!("/var/tmp/axec-{uid}"));
+    let _ = fs::DirBuilder::new().mode(0o700).create(&path);
+    is_private_dir(&path, uid).then_some(path)
+}
+
+/// Whether `path` is a directory, not a link to one, owned by `uid` with mode `0700`.
+fn is_private_dir(path: &Path, uid: u32) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_dir() && m.uid() == uid && m.mode() & 0o777 == 0o700)
+}
+
+fn is_usable_dir(path: &Path) -> bool {
+    fs::create_dir_all(path).is_ok() && fs::metadata(path).is_ok_and(|m| m.is_dir() && !m.permissions().readonly())
+}
+
+/// Where the data dir may be, best first: `$XDG_DATA_HOME`, what `dirs` infers, then
+/// `$HOME/.local/share`. Relative values are ignored, as the XDG spec says.
+fn data_dir_candidates() -> [Option<PathBuf>; 3] {
+    let absolute_var = |name: &str| std::env::var_os(name).map(PathBuf::from).filter(|p| p.is_absolute());
+    [absolute_var("XDG_DATA_HOME"), dirs::data_dir(), absolute_var("HOME").map(|h| h.join(".local/share"))]
+}
+
+fn first_usable_dir(candidates: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    candidates.into_iter().find(|p| is_usable_dir(p))
+}
+
+/// The first of `data_dir_candidates` that exists or can be created, else
+/// `/var/tmp/axec-<uid>`.
+fn data_dir() -> io::Result<PathBuf> {
+    first_usable_dir(data_dir_candidates().into_iter().flatten().chain(std::iter::once_with(fallback_data_dir).flatten()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No usable data dir: $XDG_DATA_HOME, $HOME/.local/share and /var/tmp/axec-<uid> are all unavailable"))
+}
+
+/// The user's icon theme root, `~/.local/share/icons`.
+fn user_icons_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("icons"))
+}
+
+fn thumbnails_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("axec/thumbnails"))
+}
+
+/// Icons converted to the `icon_format` setting, named `{id}.{ext}`.
+fn converted_icons_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("axec/converted-icons"))
+}
+
+/// Downloaded AppStream screenshots, in one `{id}` dir per app.
+fn screenshots_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("axec/screenshots"))
+}
+
+/// Per-app `{id}.log` files holding the output of the latest launch.
+fn logs_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("axec/logs"))
+}
+
+/// Where `id`'s latest launch logs to.
+fn app_log_path(id: &str) -> io::Result<PathBuf> {
+    Ok(logs_dir()?.join(format!("{id}.log")))
+}
+
+/// Storage and applications dirs, without creating them.
+fn app_dirs() -> io::Result<(PathBuf, PathBuf)> {
     // When sandboxed, prefer XDG data dir; avoid writing system applications outside sandbox
-    let data_dir = dirs::data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG data dir not found"))?;
+    let data_dir = data_dir()?;
     let storage = data_dir.join("axec/appimages");
     let apps = if in_flatpak_sandbox() {
         // inside Flatpak, write desktop files under XDG data dir; they will only be visible to the sandbox
         data_dir.join("applications")
     } else {
-        dirs::home_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME not found"))?.join(APPLICATIONS_DIR)
+        // Without a home dir the entries at least land where XDG-aware menus look
+        dirs::home_dir().map(|h| h.join(APPLICATIONS_DIR)).unwrap_or_else(|| data_dir.join("applications"))
     };
+    Ok((storage, apps))
+}
+
+fn ensure_dirs() -> io::Result<(PathBuf, PathBuf)> {
+    let (storage, apps) = app_dirs()?;
     fs::create_dir_all(&storage)?;
     fs::create_dir_all(&apps)?;
     Ok((storage, apps))
 }
 
+/// Leaves room for the desktop entry prefix and `.desktop` suffix within the 255-byte name limit.
+const MAX_ID_LEN: usize = 200;
+
+/// Derives an id from a display name: control characters (NUL, newlines) are dropped and
+/// everything else outside `[A-Za-z0-9_-]`, separators included, becomes `-`.
 fn sanitize_filename(name: &str) -> String {
     let filtered: String = name
         .chars()
+        .filter(|c| !c.is_control())
         .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .take(MAX_ID_LEN)
         .collect();
     filtered.trim_matches('-').to_lowercase()
 }
 
+/// Rejects ids that could escape the storage dir once joined into a path; real ids
+/// only ever come from `sanitize_filename`.
+fn validate_id(id: &str) -> Result<(), String> {
+    if !id.is_empty() && id.len() <= MAX_ID_LEN && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(format!("Invalid app id: {id:?}"))
+    }
+}
+
 fn parse_appimage_name(path: &Path) -> String {
     let fname = path.file_stem().and_then(|s| s.to_str()).unwrap_or("appimage");
     // Remove common suffixes like ".x86_64" or version numbers
@@ -59,124 +245,1195 @@ fn parse_appimage_name(path: &Path) -> String {
     base.trim().to_string()
 }
 
-fn write_desktop_file(name: &str, exec_path: &Path, icon_path: Option<&Path>, desktop_path: &Path) -> io::Result<()> {
-    let exec_str = exec_path.to_string_lossy();
-    let icon_line = icon_path.map(|p| format!("Icon={}", p.to_string_lossy())).unwrap_or_default();
-    let content = format!(
-        "[Desktop Entry]\nType=Application\nName={name}\nExec=\"{exec}\" %U\nTerminal=false\nCategories=Utility;\n{icon}\nX-AppImage-Version=1\nX-AppImage-Integrate=false\n",
-        name = name,
-        exec = exec_str,
-        icon = icon_line
-    );
-    let mut f = fs::File::create(desktop_path)?;
-    f.write_all(content.as_bytes())
+/// Architectures release file names carry, which don't belong in a display name.
+const ARCH_WORDS: [&str; 8] = ["x86_64", "amd64", "x64", "i386", "i686", "aarch64", "arm64", "armhf"];
+
+/// A readable name from the file name alone, for images with no embedded one:
+/// `Krita-5.2.2-x86_64.AppImage` becomes "Krita". Ids still come from `parse_appimage_name`.
+fn display_name_from_file(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let words: Vec<&str> = stem
+        .split(['-', ' '])
+        .filter(|w| !w.is_empty() && !ARCH_WORDS.contains(&w.to_ascii_lowercase().as_str()))
+        .filter(|w| {
+            let digits = w.strip_prefix(['v', 'V']).unwrap_or(w);
+            !(digits.starts_with(|c: char| c.is_ascii_digit()) && digits.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        })
+        .collect();
+    let name = words.join(" ").replace('_', " ");
+    if name.trim().is_empty() { parse_appimage_name(path) } else { name.trim().to_string() }
+}
+
+/// A version like `5.4` or `v1.2.3` in an AppImage's file name, for images that don't
+/// declare one.
+fn version_in_filename(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.split(['-', '_', ' '])
+        .map(|word| word.strip_prefix(['v', 'V']).unwrap_or(word))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.') && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '.'))
+        .map(String::from)
+}
+
+/// The running app, for events from code that isn't handed it; set during setup.
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+/// The menu path is the session's, not any one app's, so it's reported once per run.
+static MENU_PATH_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Sent the first time an entry is written somewhere desktop menus don't look.
+#[derive(Debug, Clone, Serialize)]
+struct MenuPathWarning {
+    apps_dir: String,
+    message: String,
+    fix: String,
+}
+
+fn warn_if_off_menu_path(apps_dir: &Path) {
+    if doctor::on_menu_path(apps_dir) || MENU_PATH_WARNED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    if let Some(app) = APP_HANDLE.get() {
+        let _ = app.emit(
+            "axec://menu-path-warning",
+            MenuPathWarning {
+                apps_dir: apps_dir.to_string_lossy().to_string(),
+                message: format!("Menu entries go to {}, which desktop menus don't scan, so apps won't show up there", apps_dir.display()),
+                fix: doctor::menu_path_fix(apps_dir),
+            },
+        );
+    }
+}
+
+/// Rewrites `id`'s desktop entry from the app's stored metadata, or removes it for apps
+/// kept out of the menu. Returns whether the entry changed; an entry that already has the
+/// generated content is left untouched.
+fn write_app_desktop(storage: &Path, apps_dir: &Path, id: &str, meta: &metadata::Metadata) -> io::Result<bool> {
+    let exec_path = find_appimage(storage, meta, id).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "AppImage not found"))?;
+    let app_meta = meta.get(id);
+    let desktop_path = apps_dir.join(desktop::entry_file_name(id));
+    if !app_meta.integrated() {
+        if let Ok(icons_root) = user_icons_dir() {
+            icons::remove_theme_icons(&icons_root, &format!("axec-{id}"));
+        }
+        return match fs::remove_file(&desktop_path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        };
+    }
+    let name = app_meta.name.clone().unwrap_or_else(|| parse_appimage_name(&exec_path));
+    let icon_path = find_app_icon(storage, meta, id);
+    let mut icon = icon_path.as_ref().map(|p| p.to_string_lossy().to_string());
+    let settings = settings::load();
+    let wanted_ext = settings.icon_format.extension();
+    let needs_conversion = |src: &PathBuf| wanted_ext.is_some_and(|ext| src.extension().and_then(|e| e.to_str()) != Some(ext));
+    if let Some(src) = icon_path.as_ref().filter(|src| needs_conversion(src)) {
+        let dest = converted_icons_dir()?.join(format!("{id}.{}", wanted_ext.unwrap_or_default()));
+        if icons::is_fresh(&dest, src) || icons::convert(src, &dest, settings.icon_format).is_ok() {
+            icon = Some(dest.to_string_lossy().to_string());
+        }
+    }
+    let icons_root = user_icons_dir()?;
+    let theme_name = format!("axec-{id}");
+    let themed = match icon_path.as_ref().filter(|_| settings.theme_icons && wanted_ext.is_none()) {
+        // Formats the theme can't hold (e.g. XPM) keep the absolute path
+        Some(src) => match icons::install_theme_icon(src, &icons_root, &theme_name) {
+            Ok(changed) => {
+                if changed {
+                    icons::refresh_icon_cache(&icons_root);
+                }
+                icon = Some(theme_name.clone());
+                true
+            }
+            Err(_) => false,
+        },
+        None => false,
+    };
+    // Turning `theme_icons` off, or converting instead, takes the theme copies back out
+    if !themed && icons::remove_theme_icons(&icons_root, &theme_name) {
+        icons::refresh_icon_cache(&icons_root);
+    }
+    // Copies in a format the entry no longer uses would only linger
+    if let Ok(dir) = converted_icons_dir() {
+        for ext in ["png", "ico", "xpm"] {
+            let copy = dir.join(format!("{id}.{ext}"));
+            if icon.as_deref() != copy.to_str() {
+                let _ = fs::remove_file(copy);
+            }
+        }
+    }
+    let name = settings::decorate_name(&settings, &name);
+    let mut localized = app_meta.localized.clone();
+    for (_, value) in localized.iter_mut().filter(|(key, _)| key.starts_with("Name[")) {
+        *value = settings::decorate_name(&settings, value);
+    }
+    let file = desktop::DesktopFile {
+        name: &name,
+        exec_path: &exec_path,
+        icon: icon.as_deref(),
+        comment: app_meta.comment.as_deref(),
+        args: &app_meta.default_args,
+        field_code: app_meta.field_code,
+        mime_types: &app_meta.mime_types,
+        wrapper: app_meta.exec_wrapper.as_deref(),
+        categories: &meta.resolved_categories(id),
+        keywords: &app_meta.keywords,
+        localized: &localized,
+        terminal: app_meta.terminal,
+        no_display: app_meta.no_display,
+        // The embedded entry's own TryExec names a path inside the image; the stored file is what can go missing
+        try_exec: Some(&exec_path),
+        startup_wm_class: app_meta.startup_wm_class.as_deref().or(app_meta.wm_class_guess.as_deref().filter(|_| settings.guess_wm_class)),
+    };
+    let changed = storage::retry_transient(|| file.write(&desktop_path))?;
+    warn_if_off_menu_path(apps_dir);
+    Ok(changed)
+}
+
+/// Rewrites `id`'s entry and those of apps inheriting from it, stopping at the first error.
+fn write_app_desktop_tree(storage: &Path, apps_dir: &Path, id: &str, meta: &metadata::Metadata) -> io::Result<()> {
+    write_app_desktop(storage, apps_dir, id, meta)?;
+    for child in meta.descendants(id).iter().filter(|c| find_appimage(storage, meta, c).is_some()) {
+        write_app_desktop(storage, apps_dir, child, meta)?;
+    }
+    Ok(())
+}
+
+/// How adding and removing apps put entries in the host's menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DesktopIntegrator {
+    /// Write into the applications dir ourselves.
+    Direct,
+    /// Inside Flatpak, where that dir isn't the host's: go through the DynamicLauncher portal.
+    Portal,
+}
+
+/// Icon for portal launchers of apps that ship none; the portal requires one.
+const FALLBACK_ICON: &[u8] = include_bytes!("../icons/64x64.png");
+/// The portal's icon goes over D-Bus as text, several bytes per byte, so it stays small.
+const PORTAL_ICON_SIZE: u32 = 64;
+
+impl DesktopIntegrator {
+    fn current() -> Self {
+        if in_flatpak_sandbox() { DesktopIntegrator::Portal } else { DesktopIntegrator::Direct }
+    }
+
+    /// Creates or refreshes `id`'s menu entry. Through the portal that means a dialog, so
+    /// launchers it already has are left alone and a session without the portal just
+    /// leaves the app out of the menu. Returns false when the user declined the dialog.
+    ///
+    /// The dialog may stay open indefinitely: never call this holding `metadata::lock`.
+    fn install(self, storage: &Path, apps_dir: &Path, id: &str, meta: &metadata::Metadata) -> io::Result<bool> {
+        match self {
+            DesktopIntegrator::Direct => {
+                write_app_desktop(storage, apps_dir, id, meta)?;
+                refresh_desktop_database(apps_dir);
+                Ok(true)
+            }
+            DesktopIntegrator::Portal => {
+                let file_id = desktop::entry_file_name(id);
+                if !meta.get(id).integrated() || !portal::available() || portal::is_installed(&file_id) {
+                    return Ok(true);
+                }
+                let (name, entry) = portal_entry(storage, id, meta)?;
+                let icon = find_app_icon(storage, meta, id).and_then(|p| icons::png_bytes(&p, PORTAL_ICON_SIZE).ok()).unwrap_or_else(|| FALLBACK_ICON.to_vec());
+                portal::install(&file_id, &name, &icon, &entry)
+            }
+        }
+    }
+
+    /// Removes `id`'s menu entry, passing files to `dispose` so they can go to the trash.
+    /// Returns whether there was one.
+    fn uninstall(self, apps_dir: &Path, id: &str, dispose: &dyn Fn(&Path) -> io::Result<()>) -> bool {
+        match self {
+            DesktopIntegrator::Direct => {
+                let desktop = apps_dir.join(desktop::entry_file_name(id));
+                let found = desktop.exists();
+                if found {
+                    let _ = dispose(&desktop);
+                }
+                // One left over from before `migrate_desktop_naming`
+                let _ = fs::remove_file(apps_dir.join(desktop::legacy_entry_file_name(id)));
+                found
+            }
+            DesktopIntegrator::Portal => {
+                let file_id = desktop::entry_file_name(id);
+                portal::is_installed(&file_id) && portal::uninstall(&file_id).is_ok()
+            }
+        }
+    }
+}
+
+/// The name and text of `id`'s portal launcher. It runs `axec --launch`, since the portal
+/// points `Exec=` into our sandbox, and leaves the icon to the portal.
+fn portal_entry(storage: &Path, id: &str, meta: &metadata::Metadata) -> io::Result<(String, String)> {
+    let exec_path = find_appimage(storage, meta, id).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "AppImage not found"))?;
+    let app_meta = meta.get(id);
+    let settings = settings::load();
+    let name = settings::decorate_name(&settings, &app_meta.name.clone().unwrap_or_else(|| parse_appimage_name(&exec_path)));
+    let mut localized = app_meta.localized.clone();
+    for (_, value) in localized.iter_mut().filter(|(key, _)| key.starts_with("Name[")) {
+        *value = settings::decorate_name(&settings, value);
+    }
+    let file = desktop::DesktopFile {
+        name: &name,
+        exec_path: Path::new("axec"),
+        icon: None,
+        comment: app_meta.comment.as_deref(),
+        // Default args and the wrapper are applied by the launch itself
+        args: &["--launch".to_string(), id.to_string()],
+        field_code: app_meta.field_code,
+        mime_types: &app_meta.mime_types,
+        wrapper: None,
+        categories: &meta.resolved_categories(id),
+        keywords: &app_meta.keywords,
+        localized: &localized,
+        terminal: app_meta.terminal,
+        no_display: app_meta.no_display,
+        try_exec: None,
+        startup_wm_class: app_meta.startup_wm_class.as_deref().or(app_meta.wm_class_guess.as_deref().filter(|_| settings.guess_wm_class)),
+    };
+    Ok((name.clone(), file.render()?))
+}
+
+/// Asks the menu to pick up changed entries; best-effort since the tool may be absent.
+fn refresh_desktop_database(apps_dir: &Path) {
+    let _ = Command::new("update-desktop-database")
+        .arg(apps_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Replaces `path` with `contents` via a synced temp file and rename, so readers only
+/// ever see the old or the new file, never a truncated one.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, |file| io::Write::write_all(file, contents))
+}
+
+/// `write_atomic` with the contents coming from `write`; if it fails, `path` is untouched.
+fn write_atomic_with(path: &Path, write: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let dir = path.parent().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no parent directory"))?;
+    let mut tmp = tempfile::Builder::new().prefix(".axec-tmp-").tempfile_in(dir)?;
+    write(tmp.as_file_mut())?;
+    // NamedTempFile is created 0600; these files are meant to be world-readable
+    tmp.as_file().set_permissions(fs::Permissions::from_mode(0o644))?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    // Persist the rename itself
+    fs::File::open(dir)?.sync_all()
+}
+
+/// The folder holding an app's AppImage and icon: `storage`, or the subfolder `move_app` put it in.
+fn app_dir(storage: &Path, app_meta: &metadata::AppMeta) -> PathBuf {
+    match &app_meta.subdir {
+        Some(subdir) => storage.join(subdir),
+        None => storage.to_path_buf(),
+    }
+}
+
+/// Where `id`'s AppImage may be stored: under its recorded original name, else as `{id}.AppImage`.
+fn app_file_candidates(storage: &Path, id: &str, app_meta: &metadata::AppMeta) -> Vec<PathBuf> {
+    let dir = app_dir(storage, app_meta);
+    let mut out: Vec<PathBuf> = app_meta.file_name.as_deref().map(|n| dir.join(n)).into_iter().collect();
+    out.extend(["AppImage", "appimage"].map(|e| dir.join(format!("{id}.{e}"))));
+    out
+}
+
+/// `id`'s stored AppImage, per `meta`. Callers going over many apps pass one snapshot
+/// rather than reading metadata.json for each.
+fn find_appimage(storage: &Path, meta: &metadata::Metadata, id: &str) -> Option<PathBuf> {
+    stored_appimage(storage, id, &meta.get(id))
+}
+
+/// `find_appimage` for callers that already hold `id`'s entry.
+fn stored_appimage(storage: &Path, id: &str, app_meta: &metadata::AppMeta) -> Option<PathBuf> {
+    app_file_candidates(storage, id, app_meta).into_iter().find(|p| p.exists())
 }
 
-fn make_executable(path: &Path) -> io::Result<()> {
+fn find_app_icon(storage: &Path, meta: &metadata::Metadata, id: &str) -> Option<PathBuf> {
+    icons::find_icon(&app_dir(storage, &meta.get(id)), id)
+}
+
+/// The id of the app stored at `path`: the one recorded for its file name, else the id
+/// the name sanitizes to.
+fn app_id_for(path: &Path, meta: &metadata::Metadata) -> String {
+    let recorded = path.file_name().and_then(|n| n.to_str()).and_then(|n| meta.id_for_file(n));
+    recorded.map(String::from).unwrap_or_else(|| sanitize_filename(&parse_appimage_name(path)))
+}
+
+/// Mode AppImages are stored with when neither the app nor `file_mode` picks one.
+const DEFAULT_FILE_MODE: u32 = 0o755;
+
+fn make_executable(path: &Path, mode: u32) -> io::Result<()> {
     let mut perms = fs::metadata(path)?.permissions();
-    perms.set_mode(0o755);
+    perms.set_mode(mode);
     fs::set_permissions(path, perms)
 }
 
-fn extract_icon(appimage_path: &Path, target_dir: &Path, base_id: &str) -> Option<PathBuf> {
-    // Try --appimage-extract . Try to locate .DirIcon or usr/share/icons
-    // Fallback: None
-    let tmp_dir = tempfile::Builder::new().prefix("axec-extract-").tempdir().ok()?;
-    let status = Command::new(appimage_path)
-        .arg("--appimage-extract")
-        .current_dir(tmp_dir.path())
-        .status()
-        .ok()?;
-    if !status.success() {
-        return None;
+/// Parses an octal mode like `750` or `0o750` for a stored AppImage. The owner must
+/// keep read and execute, or the app couldn't start; setuid and the like aren't allowed.
+fn parse_file_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.trim().trim_start_matches("0o");
+    let parsed = u32::from_str_radix(digits, 8).ok().filter(|m| *m <= 0o777).ok_or_else(|| format!("{mode} is not an octal mode between 000 and 777"))?;
+    if parsed & 0o500 != 0o500 {
+        return Err(format!("{mode} takes read or execute away from the owner, so the app couldn't launch"));
     }
-    let squash_root = tmp_dir.path().join("squashfs-root");
-    let mut candidates: Vec<PathBuf> = vec![squash_root.join(".DirIcon")];
-    for sub in [
-        "usr/share/icons/hicolor/256x256/apps",
-        "usr/share/icons/hicolor/128x128/apps",
-        "usr/share/icons/hicolor/64x64/apps",
-        "usr/share/pixmaps",
-    ] {
-        let dir = squash_root.join(sub);
-        if dir.is_dir() {
-            if let Ok(rd) = fs::read_dir(&dir) {
-                for e in rd.flatten() {
-                    let p = e.path();
-                    if let Some(ext) = p.extension().and_then(|s| s.to_str()) {
-                        if matches!(ext.to_ascii_lowercase().as_str(), "png" | "svg" | "xpm" | "ico") {
-                            candidates.push(p);
-                        }
-                    }
-                }
+    Ok(parsed)
+}
+
+/// The mode `app_meta`'s AppImage is stored with: its own `file_mode`, else the setting's.
+fn app_file_mode(settings: &Settings, app_meta: &metadata::AppMeta) -> u32 {
+    [app_meta.file_mode.as_deref(), settings.file_mode.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(|m| parse_file_mode(m).ok())
+        .unwrap_or(DEFAULT_FILE_MODE)
+}
+
+/// The file `path` stores its bytes in: itself, or the blob it links to. User files
+/// linked in with `SymlinkMode::Symlink` are none of Axec's business.
+fn owned_file(storage: &Path, path: &Path) -> Option<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.file_type().is_symlink() => storage::blob_for(storage, path),
+        Ok(_) => Some(path.to_path_buf()),
+        Err(_) => None,
+    }
+}
+
+/// Takes every write bit off a stored app (`0755` becomes `0555`) when `read_only_storage` is on.
+fn seal_app_file(storage: &Path, path: &Path, settings: &Settings) -> io::Result<()> {
+    match owned_file(storage, path) {
+        Some(file) if settings.read_only_storage => {
+            let mode = fs::metadata(&file)?.permissions().mode();
+            fs::set_permissions(file, fs::Permissions::from_mode(mode & 0o7555))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Gives a sealed app its write bit back before Axec replaces, moves or deletes it.
+fn unseal_app_file(storage: &Path, path: &Path) -> io::Result<()> {
+    let Some(file) = owned_file(storage, path) else { return Ok(()) };
+    let mode = fs::metadata(&file)?.permissions().mode();
+    if mode & 0o200 == 0 {
+        fs::set_permissions(file, fs::Permissions::from_mode(mode | 0o200))?;
+    }
+    Ok(())
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+/// Unpacks the AppImage's filesystem into `work_dir/squashfs-root`.
+///
+/// Prefers the runtime's own `--appimage-extract`; when the file isn't executable (or the
+/// runtime refuses), reads the embedded squashfs with `unsquashfs`, else in-process.
+fn extract_squashfs(appimage_path: &Path, work_dir: &Path) -> Option<PathBuf> {
+    let squash_root = work_dir.join("squashfs-root");
+    if is_executable(appimage_path) {
+        let extracted = Command::new(appimage_path)
+            .arg("--appimage-extract")
+            .current_dir(work_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if extracted && squash_root.is_dir() {
+            return Some(squash_root);
+        }
+    }
+    let offset = appimage::squashfs_offset(appimage_path).ok()?;
+    let _ = fs::remove_dir_all(&squash_root);
+    let status = Command::new("unsquashfs")
+        .arg("-o")
+        .arg(offset.to_string())
+        .arg("-d")
+        .arg(&squash_root)
+        .arg(appimage_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if status.is_ok_and(|s| s.success()) && squash_root.is_dir() {
+        return Some(squash_root);
+    }
+    let _ = fs::remove_dir_all(&squash_root);
+    squashfs::extract_all(appimage_path, &squash_root).ok()
+}
+
+/// Unpacks any AppImage type into `work_dir/squashfs-root`.
+///
+/// Type 1 images are read as ISO 9660 directly, falling back to the runtime's own extract.
+fn extract_tree(appimage_path: &Path, work_dir: &Path, app_type: appimage::AppImageType) -> Option<PathBuf> {
+    if app_type == appimage::AppImageType::Type1 {
+        let root = work_dir.join("squashfs-root");
+        if iso::extract(appimage_path, &root).is_ok() {
+            return Some(root);
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+    extract_squashfs(appimage_path, work_dir)
+}
+
+/// Unpacks just the files integration needs, into `work_dir/squashfs-root`.
+///
+/// With `safe` set, type 2 images are read in-process and never executed; the full
+/// extraction is only the fallback when that fails. Otherwise the in-process read is the
+/// fallback, so an image whose runtime won't run here still yields its name and icon.
+fn extract_metadata_tree(appimage_path: &Path, work_dir: &Path, app_type: appimage::AppImageType, safe: bool) -> Option<PathBuf> {
+    let read_squashfs = || {
+        let root = work_dir.join("squashfs-root");
+        let _ = fs::remove_dir_all(&root);
+        squashfs::extract_metadata(appimage_path, &root).inspect_err(|_| {
+            let _ = fs::remove_dir_all(&root);
+        }).ok()
+    };
+    let read_first = safe && app_type == appimage::AppImageType::Type2;
+    if read_first {
+        if let Some(root) = read_squashfs() {
+            return Some(root);
+        }
+    }
+    extract_tree(appimage_path, work_dir, app_type).or_else(|| if read_first || app_type == appimage::AppImageType::Type1 { None } else { read_squashfs() })
+}
+
+/// Storage subfolder extractions work in when `extract_temp_dir` is unset.
+const EXTRACT_TMP_DIR: &str = ".extract-tmp";
+
+/// The dirs extractions may work in, in order of preference: `extract_temp_dir` (or
+/// storage), then the system temp dir.
+fn extraction_base_candidates() -> Result<[PathBuf; 2], String> {
+    let configured = match settings::extract_temp_dir(&settings::load()) {
+        Some(dir) => dir,
+        None => app_dirs().map_err(|e| e.to_string())?.0.join(EXTRACT_TMP_DIR),
+    };
+    Ok([configured, std::env::temp_dir()])
+}
+
+/// Where to unpack `needed` bytes: the first of `extraction_base_candidates` with room.
+/// The AppImage's size stands in for what it unpacks to.
+fn extraction_base(needed: u64) -> Result<PathBuf, String> {
+    let mut short = Vec::new();
+    for dir in extraction_base_candidates()? {
+        if fs::create_dir_all(&dir).is_err() {
+            continue;
+        }
+        match doctor::free_space(&dir) {
+            Some(free) if free < needed => short.push(format!("{} has {} MiB", dir.display(), free >> 20)),
+            _ => return Ok(dir),
+        }
+    }
+    Err(format!("Not enough space to extract the AppImage: it needs about {} MiB, but {}. Set extract_temp_dir to a larger disk", needed >> 20, short.join(" and ")))
+}
+
+/// A fresh temp dir for unpacking `appimage_path`, per `extraction_base`.
+fn extraction_tempdir(appimage_path: &Path) -> Result<tempfile::TempDir, String> {
+    let needed = fs::metadata(appimage_path).map(|m| m.len()).unwrap_or(0);
+    tempfile::Builder::new().prefix("axec-extract-").tempdir_in(extraction_base(needed)?).map_err(|e| e.to_string())
+}
+
+/// What `add_appimage` takes from the AppImage's own files.
+#[derive(Default)]
+struct Embedded {
+    icon: Option<PathBuf>,
+    /// Set when the image has light and dark icons; the other one is stored as `{id}.alt.*`.
+    icon_variant: Option<icons::ThemeVariant>,
+    entry: Option<desktop::DesktopEntry>,
+    wm_class_guess: Option<String>,
+    toolkit: Option<launch::Toolkit>,
+    appstream: Option<appstream::AppStream>,
+    /// SPDX id from AppStream, else guessed from a bundled license file.
+    license: Option<String>,
+}
+
+fn extract_embedded(appimage_path: &Path, target_dir: &Path, base_id: &str, app_type: appimage::AppImageType) -> Embedded {
+    // Extract the filesystem, then locate .DirIcon or usr/share/icons
+    if !is_executable(appimage_path) {
+        let _ = make_executable(appimage_path, app_file_mode(&settings::load(), &metadata::AppMeta::default()));
+    }
+    let Ok(tmp_dir) = extraction_tempdir(appimage_path) else { return Embedded::default() };
+    let safe = settings::load().safe_extraction;
+    let Some(squash_root) = extract_metadata_tree(appimage_path, tmp_dir.path(), app_type, safe) else { return Embedded::default() };
+    read_embedded_tree(&squash_root, target_dir, base_id)
+}
+
+/// `extract_embedded`'s findings from a metadata tree already unpacked to `squash_root`,
+/// with the icon copied into `target_dir`.
+fn read_embedded_tree(squash_root: &Path, target_dir: &Path, base_id: &str) -> Embedded {
+    let store_icon = |icon_src: &Path, stem: &str| {
+        let mut data = fs::read(icon_src).ok()?;
+        let mut ext = icons::extension_for(icon_src, &data);
+        // Few launchers render ICO, so keep its best frame as a PNG
+        if ext == "ico" {
+            if let Ok(png) = icons::ico_to_png(&data) {
+                (data, ext) = (png, "png");
             }
         }
+        let icon_dest = target_dir.join(format!("{stem}.{ext}"));
+        fs::write(&icon_dest, data).ok().map(|_| icon_dest)
+    };
+    let icon_src = icons::find_in_tree(squash_root);
+    let mut icon_variant = None;
+    let icon = match icon_src.as_deref().and_then(|src| icons::theme_variants(squash_root, src)) {
+        // Both themes shipped: the one matching the desktop is active, the other kept aside
+        Some((light, dark)) => {
+            let variant = icons::system_variant();
+            let (active, alternate) = if variant == icons::ThemeVariant::Dark { (dark, light) } else { (light, dark) };
+            let _ = store_icon(&alternate, &format!("{base_id}.alt"));
+            icon_variant = Some(variant);
+            store_icon(&active, base_id)
+        }
+        None => icon_src.as_deref().and_then(|src| store_icon(src, base_id)),
+    };
+    let appstream = appstream::read_embedded(squash_root);
+    let license = appstream.as_ref().and_then(|s| s.license.clone()).or_else(|| {
+        let text = license::read_text(&license::find_file(squash_root)?)?;
+        license::guess_spdx(&text).map(String::from)
+    });
+    let entry = desktop::read_embedded(squash_root);
+    let wm_class_guess = desktop::guess_wm_class(squash_root, entry.as_ref());
+    let toolkit = launch::detect_toolkit(squash_root);
+    Embedded { icon, icon_variant, entry, wm_class_guess, toolkit, appstream, license }
+}
+
+/// Where `normalize_app_file` would move `path`, if anywhere; files kept under their
+/// original name stay put.
+fn normalized_name(storage: &Path, path: &Path, meta: &metadata::Metadata) -> Option<PathBuf> {
+    if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| meta.id_for_file(n).is_some()) {
+        return None;
+    }
+    let id = sanitize_filename(&parse_appimage_name(path));
+    let canonical = storage.join(format!("{id}.AppImage"));
+    (!id.is_empty() && canonical != path && fs::symlink_metadata(&canonical).is_err()).then_some(canonical)
+}
+
+/// Renames a stored AppImage to the canonical `{id}.AppImage` (e.g. a manual drop with a
+/// lowercase extension) and points its desktop entry at the new path. Returns the path
+/// to use from now on, which is the old one if renaming isn't possible.
+///
+/// Only `repair_app` calls this; listing never renames anything.
+fn normalize_app_file(storage: &Path, apps_dir: &Path, path: &Path, meta: &metadata::Metadata) -> PathBuf {
+    let id = sanitize_filename(&parse_appimage_name(path));
+    let Some(canonical) = normalized_name(storage, path, meta).filter(|c| fs::rename(path, c).is_ok()) else {
+        return path.to_path_buf();
+    };
+    if !in_flatpak_sandbox() && apps_dir.join(desktop::entry_file_name(&id)).exists() {
+        let _ = write_app_desktop(storage, apps_dir, &id, meta);
+        refresh_desktop_database(apps_dir);
+    }
+    canonical
+}
+
+fn app_entry(storage: &Path, apps_dir: &Path, path: &Path, meta: &metadata::Metadata) -> io::Result<AppImageEntry> {
+    let file_meta = fs::metadata(path)?;
+    if !file_meta.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not a regular file"));
     }
-    // Pick first existing candidate
-    let icon_src = candidates.into_iter().find(|p| p.exists())?;
-    let ext = icon_src
-        .extension()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_ascii_lowercase())
-        .unwrap_or_else(|| "png".to_string());
-    let icon_dest = target_dir.join(format!("{base_id}.{ext}"));
-    if fs::copy(&icon_src, &icon_dest).is_ok() {
-        Some(icon_dest)
+    let id = app_id_for(path, meta);
+    let app_meta = meta.get(&id);
+    let name = app_meta.name.clone().unwrap_or_else(|| parse_appimage_name(path));
+    let desktop_file = apps_dir.join(desktop::entry_file_name(&id));
+    // find icon with id.* in storage
+    let icon_path = find_app_icon(storage, meta, &id);
+    Ok(AppImageEntry {
+        id: id.clone(),
+        name,
+        path: path.to_string_lossy().to_string(),
+        icon_path: icon_path.map(|p| p.to_string_lossy().to_string()),
+        desktop_file: desktop_file.to_string_lossy().to_string(),
+        size: file_meta.len(),
+        categories: meta.resolved_categories(&id),
+        parent: app_meta.parent.clone(),
+        order: app_meta.order,
+        pinned: app_meta.pinned,
+        integrated: app_meta.integrated(),
+        added_at: app_meta.added_at,
+        runtime: app_meta.runtime,
+        license: app_meta.license,
+        build_id: app_meta.build_id,
+        notes: app_meta.notes,
+        version: app_meta.app_version.or(app_meta.version),
+        comment: app_meta.comment,
+        mime_types: app_meta.mime_types,
+    })
+}
+
+/// How `add_appimage` gets the file into storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallMode {
+    /// Copy it, or link it per `symlink_mode` and `dedup_binaries`.
+    #[default]
+    Copy,
+    /// Move it into storage; a rename when both are on one filesystem.
+    Move,
+    /// Leave it where it is and store a symlink to it.
+    #[serde(alias = "keep-in-place")]
+    KeepInPlace,
+}
+
+/// Fetches `source` (a URL or `owner/repo`) into `dir`; returns the file and, for
+/// GitHub, the release tag.
+fn download_source(
+    source: &str,
+    headers: &[(String, String)],
+    dir: &Path,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<(PathBuf, Option<String>), download::DownloadError> {
+    let max_bytes = download_budget()?;
+    if download::is_repo_shorthand(source) {
+        let (path, tag) = download::download_latest(source, headers, dir, max_bytes, on_progress)?;
+        Ok((path, Some(tag)))
     } else {
-        None
+        Ok((download::download_with_progress(source, headers, dir, None, max_bytes, on_progress)?, None))
     }
 }
 
-#[tauri::command]
-fn list_apps() -> Result<Vec<AppImageEntry>, String> {
-    let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
-    let mut result = Vec::new();
-    if let Ok(rd) = fs::read_dir(&storage) {
-        for entry in rd.flatten() {
-            let p = entry.path();
-            if let Some(ext) = p.extension().and_then(|s| s.to_str()) {
-                let ext_l = ext.to_ascii_lowercase();
-                if ext_l == "appimage" {
-                    let name = parse_appimage_name(&p);
-                    let id = sanitize_filename(&name);
-                    let desktop_file = apps_dir.join(format!("axec-{id}.desktop"));
-                    // find icon with id.* in storage
-                    let icon_path = ["png", "svg", "ico", "xpm"].iter().map(|e| storage.join(format!("{id}.{e}"))).find(|x| x.exists());
-                    result.push(AppImageEntry {
-                        id: id.clone(),
-                        name,
-                        path: p.to_string_lossy().to_string(),
-                        icon_path: icon_path.map(|p| p.to_string_lossy().to_string()),
-                        desktop_file: desktop_file.to_string_lossy().to_string(),
-                    });
-                }
+/// How much a download may add before `max_storage_bytes` is reached, so ones that could
+/// never fit are given up on before they finish.
+fn download_budget() -> io::Result<Option<u64>> {
+    match storage_quota(&settings::load()) {
+        Some(quota) => Ok(Some(quota.saturating_sub(ensure_dirs().map(|(storage, _)| storage::usage(&storage))?))),
+        None => Ok(None),
+    }
+}
+
+/// How long `find_updates` answers from its last run.
+const UPDATE_CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// An app `find_updates` found a newer build for.
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailableUpdate {
+    pub id: String,
+    pub current_version: Option<String>,
+    pub latest_version: String,
+    pub download_url: String,
+}
+
+/// The last complete `find_updates` run.
+#[derive(Default)]
+struct UpdateCache(Mutex<Option<(Instant, Vec<AvailableUpdate>)>>);
+
+/// Whether `id` has a newer build, from its GitHub source or embedded update information.
+/// Apps installed from a plain URL can't tell without downloading, so they never do.
+/// Without `github`, checks that would query GitHub's API come back empty.
+fn check_for_update(storage: &Path, id: &str, app_meta: &metadata::AppMeta, token: Option<&str>, github: bool) -> Result<Option<update::UpdateInfo>, download::DownloadError> {
+    let current = app_meta.version.as_deref().or(app_meta.app_version.as_deref());
+    match &app_meta.source {
+        Some(source) if github && download::is_repo_shorthand(source) => update::check_github(source, current, token),
+        // Plain URLs, and GitHub sources while GitHub is off limits
+        Some(_) => Ok(None),
+        None => {
+            let Some(path) = stored_appimage(storage, id, app_meta) else { return Ok(None) };
+            let Some(info) = appimage::update_info(&path)? else { return Ok(None) };
+            if !github && update::github_repo(&info).is_some() {
+                return Ok(None);
+            }
+            update::check_update_info(&info, current, fs::metadata(&path)?.len(), token)
+        }
+    }
+}
+
+fn find_updates_blocking(app: &AppHandle, refresh: bool, token: Option<String>) -> Result<Vec<AvailableUpdate>, String> {
+    if !refresh {
+        if let Some((at, updates)) = app.state::<UpdateCache>().0.lock().unwrap().as_ref() {
+            if at.elapsed() < UPDATE_CACHE_TTL {
+                return Ok(updates.clone());
             }
         }
     }
-    Ok(result)
+    let storage = ensure_dirs().map_err(|e| e.to_string())?.0;
+    let meta = metadata::load();
+    let token = token.or(settings::load().github_token);
+    let ids: Vec<&String> = meta.apps.keys().filter(|id| find_appimage(&storage, &meta, id).is_some()).collect();
+    let rate_limited = AtomicBool::new(false);
+    let workers = settings::load().max_parallel_updates.unwrap_or(DEFAULT_PARALLEL_UPDATES);
+    let found = for_each_parallel(&ids, workers, |id| {
+        match check_for_update(&storage, id, &meta.get(id), token.as_deref(), !rate_limited.load(Ordering::SeqCst)) {
+            Ok(Some(info)) => Some(AvailableUpdate { id: id.to_string(), current_version: info.current, latest_version: info.latest, download_url: info.download_url }),
+            Err(download::DownloadError::RateLimited { .. }) => {
+                rate_limited.store(true, Ordering::SeqCst);
+                None
+            }
+            // One unreachable source shouldn't hide the others' updates
+            Ok(None) | Err(_) => None,
+        }
+    });
+    let updates: Vec<AvailableUpdate> = found.into_iter().flatten().collect();
+    if !rate_limited.load(Ordering::SeqCst) {
+        *app.state::<UpdateCache>().0.lock().unwrap() = Some((Instant::now(), updates.clone()));
+    }
+    Ok(updates)
+}
+
+/// How often the background checker rereads its setting while it has nothing due.
+const UPDATE_CHECK_POLL: Duration = Duration::from_secs(10 * 60);
+
+/// Set on exit to stop the background update checker, which waits on the condvar.
+static UPDATE_CHECKER_STOP: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+/// When the background checker last ran, in Unix seconds, so restarts don't bring the
+/// next check forward.
+fn last_update_check_path() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("axec/last-update-check"))
 }
 
-#[tauri::command]
-fn add_appimage(file_path: String) -> Result<AppImageEntry, String> {
-    let src = PathBuf::from(&file_path);
+fn last_update_check() -> Option<u64> {
+    fs::read_to_string(last_update_check_path().ok()?).ok()?.trim().parse().ok()
+}
+
+/// Seconds until the next background check is due, or `None` when they're off.
+fn next_update_check_in() -> Option<u64> {
+    let interval = settings::load().update_check_interval_hours.filter(|h| *h > 0)? * 3600;
+    let since = last_update_check().map_or(u64::MAX, |last| (now_millis() / 1000).saturating_sub(last));
+    Some(interval.saturating_sub(since))
+}
+
+/// Runs `find_updates` every `update_check_interval_hours` and notifies when it finds
+/// any, until `UPDATE_CHECKER_STOP` is set.
+fn run_update_checker(app: AppHandle) {
+    let (stop, wake) = &UPDATE_CHECKER_STOP;
+    let mut stopped = stop.lock().unwrap();
+    while !*stopped {
+        let wait = match next_update_check_in() {
+            Some(0) => {
+                drop(stopped);
+                if let Ok(path) = last_update_check_path() {
+                    let _ = write_atomic(&path, (now_millis() / 1000).to_string().as_bytes());
+                }
+                if let Some(updates) = find_updates_blocking(&app, true, None).ok().filter(|u| !u.is_empty()) {
+                    notify_updates(&app, &updates);
+                }
+                stopped = stop.lock().unwrap();
+                continue;
+            }
+            Some(secs) => Duration::from_secs(secs).min(UPDATE_CHECK_POLL),
+            None => UPDATE_CHECK_POLL,
+        };
+        stopped = wake.wait_timeout(stopped, wait).unwrap().0;
+    }
+}
+
+fn notify_updates(app: &AppHandle, updates: &[AvailableUpdate]) {
+    let _ = app.emit("axec://updates-available", updates);
+    let meta = metadata::load();
+    let names: Vec<String> = updates.iter().map(|u| meta.get(&u.id).name.unwrap_or_else(|| u.id.clone())).collect();
+    let title = match updates.len() {
+        1 => "1 update available".to_string(),
+        n => format!("{n} updates available"),
+    };
+    // The notification plugin can't report clicks on Linux, so this goes to notify-rust directly
+    let Ok(handle) = notify_rust::Notification::new()
+        .appname(&app.package_info().name)
+        .summary(&title)
+        .body(&format!("{}.", names.join(", ")))
+        .action("default", "Show updates")
+        .show()
+    else {
+        return;
+    };
+    let app = app.clone();
+    // Blocks until the notification is clicked or dismissed
+    thread::spawn(move || {
+        handle.wait_for_action(|action| {
+            if action == "default" {
+                show_main_window(&app);
+                let _ = app.emit("axec://open-updates", ());
+            }
+        })
+    });
+}
+
+/// Brings the main window back from the tray or the taskbar.
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+/// Picks the id for a new import whose natural id is taken, per `on_collision`.
+fn resolve_collision(storage: &Path, id: String, policy: settings::CollisionPolicy) -> Result<String, String> {
+    if find_appimage(storage, &metadata::load(), &id).is_none() {
+        return Ok(id);
+    }
+    match policy {
+        settings::CollisionPolicy::Error => Err(format!("An app with id {id} already exists")),
+        settings::CollisionPolicy::Suffix => Ok((2..).map(|n| format!("{id}-{n}")).find(|c| find_appimage(storage, &metadata::load(), c).is_none()).unwrap()),
+    }
+}
+
+/// Why an add was refused under `max_storage_bytes`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaExceeded {
+    pub used: u64,
+    pub incoming: u64,
+    pub quota: u64,
+    pub over_by: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Storage quota exceeded: adding {} bytes would go {} bytes over the {}-byte limit", self.incoming, self.over_by, self.quota)
+    }
+}
+
+fn storage_quota(settings: &Settings) -> Option<u64> {
+    settings.max_storage_bytes.filter(|q| *q > 0)
+}
+
+/// Fails if `incoming` more bytes would take storage past the quota.
+fn check_quota(storage: &Path, settings: &Settings, incoming: u64) -> Result<(), QuotaExceeded> {
+    let Some(quota) = storage_quota(settings) else { return Ok(()) };
+    let used = storage::usage(storage);
+    match (used + incoming).checked_sub(quota).filter(|over| *over > 0) {
+        Some(over_by) => Err(QuotaExceeded { used, incoming, quota, over_by }),
+        None => Ok(()),
+    }
+}
+
+/// How an imported binary gets into storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoreMode {
+    /// `symlink_mode = "preserve"`: link to the original file.
+    Symlink,
+    /// `dedup_binaries`: link to a shared blob, storing it first if new.
+    Blob,
+    Copy,
+    /// `InstallMode::Move`: take the file itself.
+    Move,
+}
+
+/// What `import_appimage` decides before touching anything; also what its dry run reports.
+struct ImportPlan {
+    real: PathBuf,
+    app_type: appimage::AppImageType,
+    name: String,
+    id: String,
+    dest_path: PathBuf,
+    mode: StoreMode,
+    /// What `dest_path` gets chmodded to; unused for `StoreMode::Symlink`.
+    file_mode: u32,
+    replacing: bool,
+    sha256: String,
+}
+
+/// `src`'s file name as it would be stored, given an `.AppImage` extension if it lacks one
+/// so library scans still find it.
+fn original_file_name(src: &Path) -> Option<String> {
+    let name = src.file_name()?.to_str()?;
+    Some(if name.to_ascii_lowercase().ends_with(".appimage") { name.to_string() } else { format!("{name}.AppImage") })
+}
+
+fn plan_import(
+    src: &Path,
+    as_id: Option<&str>,
+    sha256: Option<String>,
+    force: bool,
+    install: InstallMode,
+    settings: &Settings,
+    storage: &Path,
+) -> Result<ImportPlan, String> {
     if !src.exists() {
         return Err("File not found".into());
     }
+    // Moving a link's target would leave the link, and anything else pointing there, dangling
+    if install == InstallMode::Move && fs::symlink_metadata(src).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err("Can't move a symlink into storage; copy it or keep it in place".into());
+    }
+    // Reads through symlinks, so a link is judged by what it points at
+    let app_type = appimage::detect_type(src).map_err(|e| e.to_string())?;
+    // A plain ELF with nothing to mount would make an entry that can never launch
+    if app_type == appimage::AppImageType::Unknown && appimage::payload(src).is_ok_and(|p| p == appimage::Payload::None) {
+        return Err("Not an AppImage: it has neither the AppImage magic nor a filesystem image after the runtime".into());
+    }
+    let real = fs::canonicalize(src).map_err(|e| e.to_string())?;
+    let preserve_link = settings.symlink_mode == settings::SymlinkMode::Preserve && fs::symlink_metadata(src).is_ok_and(|m| m.file_type().is_symlink());
+    let name = parse_appimage_name(src);
+    let id = match as_id {
+        Some(id) => id.to_string(),
+        None => sanitize_filename(&name),
+    };
+    // e.g. a name made only of symbols or non-ASCII letters
+    validate_id(&id).map_err(|_| format!("Can't derive a safe app id from {:?}", src.file_name().unwrap_or_default()))?;
+    let id = if force { id } else { resolve_collision(storage, id, settings.on_collision)? };
+    let app_meta = metadata::load().get(&id);
+    let existing = stored_appimage(storage, &id, &app_meta);
+    let replacing = existing.is_some();
+    // Updates land wherever `move_app` put the build they replace
+    let dir = app_dir(storage, &app_meta);
+    let canonical = dir.join(format!("{id}.AppImage"));
+    let dest_path = match original_file_name(src).filter(|_| settings.preserve_original_filename).map(|n| dir.join(n)) {
+        // Never take over a file that belongs to another app
+        Some(dest) if existing.as_ref() == Some(&dest) || fs::symlink_metadata(&dest).is_err() => dest,
+        _ => canonical,
+    };
+    let sha256 = match sha256 {
+        Some(sha) => sha,
+        None => storage::sha256_file(&real).map_err(|e| e.to_string())?,
+    };
+    let mode = if install == InstallMode::KeepInPlace || preserve_link {
+        StoreMode::Symlink
+    } else if install == InstallMode::Move {
+        StoreMode::Move
+    } else if settings.dedup_binaries {
+        StoreMode::Blob
+    } else {
+        StoreMode::Copy
+    };
+    // A file kept in place stays the user's, so Axec won't chmod it through the link
+    if mode == StoreMode::Symlink && !is_executable(&real) {
+        return Err(format!("{} isn't executable; make it executable or add a copy instead", real.display()));
+    }
+    let file_mode = app_file_mode(settings, &app_meta);
+    let name = display_name_from_file(src);
+    Ok(ImportPlan { real, app_type, name, id, dest_path, mode, file_mode, replacing, sha256 })
+}
+
+impl ImportPlan {
+    /// Net bytes the import adds to storage, after whatever file it replaces.
+    fn incoming_bytes(&self, storage: &Path) -> u64 {
+        let size = fs::metadata(&self.real).map(|m| m.len()).unwrap_or(0);
+        let added = match self.mode {
+            StoreMode::Symlink => 0,
+            StoreMode::Blob if storage::blob_path(storage, &self.sha256).exists() => 0,
+            StoreMode::Blob | StoreMode::Copy | StoreMode::Move => size,
+        };
+        let replaced = fs::symlink_metadata(&self.dest_path).ok().filter(|m| m.is_file()).map_or(0, |m| m.len());
+        added.saturating_sub(replaced)
+    }
+
+    /// The steps `import_appimage` would take for this plan.
+    fn describe(&self, storage: &Path, apps_dir: &Path, meta: &metadata::Metadata) -> plan::Plan {
+        let mut out = plan::Plan::default();
+        let dest = &self.dest_path;
+        if fs::symlink_metadata(dest).is_ok_and(|m| self.mode == StoreMode::Symlink || m.file_type().is_symlink()) {
+            out.push(plan::Op::Remove, dest, None, 0);
+        }
+        let size = fs::metadata(&self.real).map(|m| m.len()).unwrap_or(0);
+        match self.mode {
+            StoreMode::Symlink => out.push(plan::Op::Symlink, dest, Some(&self.real), 0),
+            StoreMode::Blob => {
+                let blob = storage::blob_path(storage, &self.sha256);
+                if !blob.exists() {
+                    out.push(plan::Op::Copy, &blob, Some(&self.real), size);
+                }
+                out.push(plan::Op::Symlink, dest, Some(&blob), 0);
+            }
+            StoreMode::Copy => out.push(plan::Op::Copy, dest, Some(&self.real), size),
+            StoreMode::Move => {
+                let same_fs = fs::metadata(&self.real).ok().zip(dest.parent().and_then(|d| fs::metadata(d).ok())).is_some_and(|(a, b)| a.dev() == b.dev());
+                out.push(plan::Op::Move, dest, Some(&self.real), if same_fs { 0 } else { size });
+            }
+        }
+        out.push(plan::Op::Chmod, dest, None, 0);
+        let dir = dest.parent().unwrap_or(storage);
+        if self.replacing {
+            for icon in icons::stored_icons(dir, &self.id) {
+                out.push(plan::Op::Remove, &icon, None, 0);
+            }
+        }
+        out.push(plan::Op::Extract, dir, Some(dest), 0);
+        if let Some(old) = meta.get(&self.id).sha256.filter(|old| *old != self.sha256 && meta.references(old) == 1) {
+            let blob = storage::blob_path(storage, &old);
+            if blob.exists() {
+                out.push(plan::Op::Remove, &blob, None, 0);
+            }
+        }
+        if let Ok(path) = metadata::metadata_path() {
+            out.push(plan::Op::Write, &path, None, 0);
+        }
+        if !in_flatpak_sandbox() {
+            out.push(plan::Op::Write, &apps_dir.join(desktop::entry_file_name(&self.id)), None, 0);
+        }
+        out
+    }
+}
+
+/// Copies `src` into storage and integrates it, reporting copy progress to
+/// `on_progress(copied, total)`; `sha256` skips re-hashing when the caller already did.
+/// `as_id` stores it under that id instead of one derived from the file name.
+///
+/// `force` overwrites an app with the same id: binary, icon and desktop entry are
+/// replaced, while the user's name, categories, args and pins are kept. With
+/// `dedup_binaries` the old id's link is replaced rather than written through, so other
+/// apps sharing its blob are unaffected, and the old blob goes once nothing uses it.
+fn import_appimage(src: &Path, as_id: Option<&str>, sha256: Option<String>, force: bool, on_progress: &mut dyn FnMut(u64, u64)) -> Result<AppImageEntry, String> {
+    import_appimage_with(src, as_id, sha256, force, InstallMode::Copy, None, on_progress)
+}
+
+/// What `commit_staged` hands `import_appimage_with`: the metadata tree `stage_appimage`
+/// unpacked, and what the user changed in its dialog.
+struct Prepared {
+    squash_root: Option<PathBuf>,
+    name: Option<String>,
+    categories: Option<Vec<String>>,
+    /// A replacement icon's contents and extension.
+    icon: Option<(Vec<u8>, &'static str)>,
+}
+
+/// `import_appimage`, with `install` choosing whether the file is copied, moved or linked.
+/// A `prepared` import reads the staged tree instead of unpacking the file again.
+fn import_appimage_with(
+    src: &Path,
+    as_id: Option<&str>,
+    sha256: Option<String>,
+    force: bool,
+    install: InstallMode,
+    prepared: Option<&Prepared>,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<AppImageEntry, String> {
+    let settings = settings::load();
     let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
-    let name = parse_appimage_name(&src);
-    let id = sanitize_filename(&name);
-    let dest_path = storage.join(format!("{id}.AppImage"));
-    fs::copy(&src, &dest_path).map_err(|e| e.to_string())?;
-    make_executable(&dest_path).map_err(|e| e.to_string())?;
+    let plan = plan_import(src, as_id, sha256, force, install, &settings, &storage)?;
+    check_quota(&storage, &settings, plan.incoming_bytes(&storage)).map_err(|e| e.to_string())?;
+    // Fail before copying rather than add an app whose icon and entry couldn't be unpacked
+    extraction_base(fs::metadata(&plan.real).map_err(|e| e.to_string())?.len())?;
+    let ImportPlan { real, app_type, name, id, dest_path, mode, file_mode, replacing, sha256 } = plan;
+    // A forced update may store under another file name than the build it replaces
+    let previous = find_appimage(&storage, &metadata::load(), &id).filter(|p| *p != dest_path);
+    if fs::symlink_metadata(&dest_path).is_ok_and(|m| m.file_type().is_file()) {
+        unseal_app_file(&storage, &dest_path).map_err(|e| e.to_string())?;
+    }
+    // Never write through an existing blob link; that would clobber every app sharing it
+    if mode == StoreMode::Symlink || fs::symlink_metadata(&dest_path).is_ok_and(|m| m.file_type().is_symlink()) {
+        let _ = fs::remove_file(&dest_path);
+    }
+    let size = match mode {
+        StoreMode::Symlink => {
+            std::os::unix::fs::symlink(&real, &dest_path).map_err(|e| e.to_string())?;
+            fs::metadata(&dest_path).map_err(|e| e.to_string())?.len()
+        }
+        StoreMode::Blob => {
+            storage::link_blob(&storage, &real, &sha256, &dest_path, on_progress).map_err(|e| e.to_string())?;
+            fs::metadata(&dest_path).map_err(|e| e.to_string())?.len()
+        }
+        StoreMode::Copy => storage::copy_file(&real, &dest_path, on_progress).map_err(|e| e.to_string())?,
+        StoreMode::Move => {
+            trash::move_file_with_progress(&real, &dest_path, on_progress).map_err(|e| e.to_string())?;
+            fs::metadata(&dest_path).map_err(|e| e.to_string())?.len()
+        }
+    };
+    if mode != StoreMode::Symlink {
+        make_executable(&dest_path, file_mode).map_err(|e| e.to_string())?;
+        seal_app_file(&storage, &dest_path, &settings).map_err(|e| e.to_string())?;
+    }
+    if let Some(previous) = previous {
+        let _ = fs::remove_file(previous);
+    }
+    let dir = dest_path.parent().unwrap_or(&storage).to_path_buf();
+    if replacing {
+        // The new icon may come in another format; don't let the old one shadow it
+        for icon in icons::stored_icons(&dir, &id) {
+            let _ = fs::remove_file(icon);
+        }
+        // The new build may list other screenshots
+        if let Ok(shots) = screenshots_dir() {
+            let _ = fs::remove_dir_all(shots.join(&id));
+        }
+    }
+
+    // Icon goes next to the AppImage; the embedded entry supplies the display name and translations
+    let mut embedded = match prepared.and_then(|p| p.squash_root.as_deref()) {
+        Some(root) => read_embedded_tree(root, &dir, &id),
+        None => extract_embedded(&dest_path, &dir, &id, app_type),
+    };
+    // Hashed before an override replaces it, so rescans treat the override as the user's
+    let icon_sha256 = embedded.icon.as_deref().and_then(|p| storage::sha256_file(p).ok());
+    if let Some((data, ext)) = prepared.and_then(|p| p.icon.as_ref()) {
+        for old in icons::stored_icons(&dir, &id) {
+            let _ = fs::remove_file(old);
+        }
+        let path = dir.join(format!("{id}.{ext}"));
+        fs::write(&path, data).map_err(|e| e.to_string())?;
+        embedded.icon = Some(path);
+        embedded.icon_variant = None;
+    }
+    let locales = desktop::current_locales();
+    let entry = embedded.entry.as_ref();
+    let stream = embedded.appstream.as_ref();
+    let name = entry
+        .and_then(|d| d.get_localized("Name", &locales))
+        .map(String::from)
+        .or_else(|| stream.and_then(|s| s.name.clone()))
+        .unwrap_or(name);
 
-    // Try extract icon to storage
-    let icon_path = extract_icon(&dest_path, &storage, &id);
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    let app_meta = meta.entry(&id);
+    app_meta.app_type = Some(app_type);
+    app_meta.runtime = appimage::runtime_info(&dest_path).ok();
+    app_meta.file_name = (dest_path != dir.join(format!("{id}.AppImage"))).then(|| dest_path.file_name().unwrap_or_default().to_string_lossy().to_string());
+    // A forced reinstall keeps whatever name the app already has, unless the user picked one
+    if let Some(name) = prepared.and_then(|p| p.name.clone()) {
+        app_meta.name = Some(name);
+    }
+    let name = app_meta.name.get_or_insert(name).clone();
+    // Re-adding an existing id is an update and keeps its original timestamp
+    let is_new = app_meta.added_at.is_none();
+    app_meta.added_at.get_or_insert_with(now_millis);
+    let mime_types = entry.map(|d| desktop::valid_mime_types(d.get_list("MimeType"))).filter(|m| !m.is_empty());
+    app_meta.mime_types = mime_types.or_else(|| stream.map(|s| s.mime_types.clone())).unwrap_or_default();
+    // Keep the invocation the app ships with, without overriding args the user already set
+    let (args, field_code) = entry.map(desktop::recommended_args).unwrap_or_default();
+    if is_new || app_meta.default_args == app_meta.recommended_args {
+        app_meta.default_args = args.clone();
+    }
+    app_meta.recommended_args = args;
+    // Categories the user hasn't picked come from the AppImage; a reinstall keeps theirs
+    if is_new {
+        if let Some(categories) = entry.and_then(desktop::embedded_categories).or_else(|| stream.and_then(|s| desktop::usable_categories(s.categories.clone()))) {
+            app_meta.categories = categories;
+        }
+    }
+    if let Some(categories) = prepared.and_then(|p| p.categories.clone()) {
+        app_meta.categories = categories;
+    }
+    app_meta.keywords = entry.map(|d| d.get_list("Keywords")).unwrap_or_default();
+    app_meta.field_code = desktop::file_field_code(field_code, &app_meta.mime_types);
+    app_meta.comment = entry.and_then(|d| d.get_localized("Comment", &locales)).map(String::from).or_else(|| stream.and_then(|s| s.summary.clone()));
+    app_meta.localized = entry.map(|d| {
+        let mut localized = d.translations("Name");
+        localized.extend(d.translations("Comment"));
+        localized.extend(d.translations("Keywords"));
+        localized
+    }).unwrap_or_default();
+    app_meta.app_version = entry
+        .and_then(|d| d.get("X-AppImage-Version").map(String::from))
+        .or_else(|| stream.and_then(|a| a.version.clone()))
+        .or_else(|| version_in_filename(src));
+    app_meta.build_id = entry.and_then(|d| d.get("X-AppImage-BuildId")).map(String::from);
+    app_meta.terminal = entry.and_then(|d| d.get_bool("Terminal")).unwrap_or(false);
+    app_meta.no_display = entry.is_some_and(|d| d.get_bool("NoDisplay") == Some(true) || d.get_bool("Hidden") == Some(true));
+    app_meta.startup_wm_class = entry.and_then(|d| d.get("StartupWMClass")).filter(|c| !c.is_empty()).map(String::from);
+    app_meta.wm_class_guess = embedded.wm_class_guess.clone();
+    app_meta.toolkit = embedded.toolkit;
+    // The AppImage's own preference only applies until the user picks one
+    app_meta.integrate.get_or_insert(entry.and_then(|d| d.get_bool("X-AppImage-Integrate")).unwrap_or(true));
+    app_meta.icon_sha256 = icon_sha256;
+    app_meta.icon_variant = embedded.icon_variant;
+    app_meta.screenshots = stream.map(|a| a.screenshots.clone()).unwrap_or_default();
+    app_meta.license = embedded.license.clone();
+    let replaced_sha = app_meta.sha256.replace(sha256.clone()).filter(|old| *old != sha256);
+    if let Some(old) = replaced_sha {
+        storage::release_blob(&storage, &old, &meta).map_err(|e| e.to_string())?;
+    }
+    meta.save().map_err(|e| e.to_string())?;
+    // Other writers mustn't wait on the portal's dialog
+    let mut meta = metadata::Metadata::clone(&meta);
+    let icon_path = embedded.icon;
 
-    // Create desktop file
-    // Only write desktop entry outside sandbox; inside sandbox it won't be picked by host menu.
-    if !in_flatpak_sandbox() {
-        let desktop_path = apps_dir.join(format!("axec-{id}.desktop"));
-        write_desktop_file(&name, &dest_path, icon_path.as_deref(), &desktop_path).map_err(|e| e.to_string())?;
+    // Inside Flatpak the entry goes through the portal, since the sandbox's own applications dir isn't the host menu's
+    let desktop_path = apps_dir.join(desktop::entry_file_name(&id));
+    if !DesktopIntegrator::current().install(&storage, &apps_dir, &id, &meta).map_err(|e| e.to_string())? {
+        // Declined: keep it out of the menu instead of asking again on every refresh
+        let mut locked = metadata::lock().map_err(|e| e.to_string())?;
+        if locked.apps.contains_key(&id) {
+            locked.entry(&id).integrate = Some(false);
+            locked.save().map_err(|e| e.to_string())?;
+        }
+        meta.entry(&id).integrate = Some(false);
     }
 
     Ok(AppImageEntry {
@@ -185,53 +1442,833 @@ fn add_appimage(file_path: String) -> Result<AppImageEntry, String> {
         path: dest_path.to_string_lossy().to_string(),
         icon_path: icon_path.map(|p| p.to_string_lossy().to_string()),
         desktop_file: desktop_path.to_string_lossy().to_string(),
+        size,
+        categories: meta.resolved_categories(&id),
+        parent: meta.get(&id).parent,
+        order: meta.get(&id).order,
+        pinned: meta.get(&id).pinned,
+        integrated: meta.get(&id).integrated(),
+        added_at: meta.get(&id).added_at,
+        runtime: meta.get(&id).runtime,
+        license: meta.get(&id).license,
+        build_id: meta.get(&id).build_id,
+        notes: meta.get(&id).notes,
+        version: meta.get(&id).app_version.or(meta.get(&id).version),
+        comment: meta.get(&id).comment,
+        mime_types: meta.get(&id).mime_types,
     })
 }
 
-#[tauri::command]
-fn remove_app(id: String) -> Result<(), String> {
+/// Whether `name` is one of `id`'s cached `{id}-{size}.png` thumbnails.
+fn is_thumbnail_of(name: &str, id: &str) -> bool {
+    name.strip_prefix(&format!("{id}-")).is_some_and(|rest| rest.trim_end_matches(".png").parse::<u32>().is_ok())
+}
+
+/// Removes or trashes `id`'s files, entry and metadata, reporting `on_progress(file,
+/// copied, total)` while trashing moves data. Returns the trashed app, if it was trashed.
+fn remove_stored_app(id: &str, to_trash: bool, on_progress: &dyn Fn(&str, u64, u64)) -> Result<Option<trash::TrashedApp>, String> {
+    let id = id.to_string();
     let (storage, apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
+    let trash_dir = if to_trash { Some(trash::prepare(&id).map_err(|e| e.to_string())?) } else { None };
+    let progress_for = |p: &Path| {
+        let file = p.file_name().unwrap_or_default().to_string_lossy().to_string();
+        move |copied, total| on_progress(&file, copied, total)
+    };
+    let dispose = |p: &Path| match &trash_dir {
+        Some(dir) => trash::move_into(p, dir, &mut progress_for(p)),
+        None => fs::remove_file(p),
+    };
+    // Moving into the trash can copy gigabytes, so files go before the metadata lock is taken
+    let app_meta = metadata::load().apps.remove(&id);
+    let sha256 = app_meta.as_ref().and_then(|m| m.sha256.clone());
+    // Reference counts before the removal, so this app is still one of them
+    let shared = sha256.as_ref().is_some_and(|sha| metadata::load().references(sha) > 1);
     // Remove appimage
     let mut ok_any = false;
-    for ext in ["AppImage", "appimage"] {
-        let p = storage.join(format!("{id}.{ext}"));
-        if p.exists() {
-            fs::remove_file(&p).map_err(|e| e.to_string())?;
+    let stored = app_meta.clone().unwrap_or_default();
+    let folder = app_dir(&storage, &stored);
+    for p in app_file_candidates(&storage, &id, &stored) {
+        // symlink_metadata so a dangling blob link still gets cleaned up
+        if let Ok(file_meta) = fs::symlink_metadata(&p) {
+            // Trashed or not, the file shouldn't stay read-only once it's no longer stored
+            if file_meta.is_file() {
+                unseal_app_file(&storage, &p).map_err(|e| e.to_string())?;
+            }
+            match (storage::blob_for(&storage, &p), &trash_dir, &sha256) {
+                // Trashing the last user of a shared binary: trash the binary itself so it stays restorable
+                (Some(blob), Some(dir), Some(_)) if !shared => {
+                    unseal_app_file(&storage, &blob).map_err(|e| e.to_string())?;
+                    trash::move_file_with_progress(&blob, &dir.join(p.file_name().unwrap()), &mut progress_for(&p)).map_err(|e| e.to_string())?;
+                    fs::remove_file(&p).map_err(|e| e.to_string())?;
+                }
+                _ => dispose(&p).map_err(|e| e.to_string())?,
+            }
             ok_any = true;
         }
     }
     // Remove icon variants
-    for ext in ["png", "svg", "ico", "xpm"] {
-        let p = storage.join(format!("{id}.{ext}"));
-        let _ = fs::remove_file(p);
-    }
-    // Remove desktop file
-    if !in_flatpak_sandbox() {
-        let desktop = apps_dir.join(format!("axec-{id}.desktop"));
-        if desktop.exists() {
-            let _ = fs::remove_file(desktop);
-            ok_any = true;
+    for p in icons::stored_icons(&folder, &id) {
+        let _ = dispose(&p);
+    }
+    // A subfolder emptied by this removal goes too
+    if folder != storage {
+        let _ = fs::remove_dir(&folder);
+    }
+    let _ = fs::remove_dir_all(extracted_dir(&storage).join(&id));
+    // Remove theme icons
+    if let Ok(icons_root) = user_icons_dir() {
+        icons::remove_theme_icons(&icons_root, &format!("axec-{id}"));
+    }
+    // Remove cached thumbnails
+    if let Ok(rd) = thumbnails_dir().and_then(fs::read_dir) {
+        for e in rd.flatten() {
+            if e.file_name().to_str().is_some_and(|n| is_thumbnail_of(n, &id)) {
+                let _ = fs::remove_file(e.path());
+            }
+        }
+    }
+    // Remove downloaded screenshots
+    if let Ok(dir) = screenshots_dir() {
+        let _ = fs::remove_dir_all(dir.join(&id));
+    }
+    if DesktopIntegrator::current().uninstall(&apps_dir, &id, &|p| dispose(p)) {
+        ok_any = true;
+    }
+    if !ok_any {
+        if let Some(dir) = &trash_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+        return Err("App not found".into());
+    }
+    if let Some(app_meta) = &app_meta {
+        if let Some(dir) = &trash_dir {
+            trash::save_meta(dir, app_meta).map_err(|e| e.to_string())?;
         }
     }
-    if ok_any { Ok(()) } else { Err("App not found".into()) }
+    let mut meta = metadata::lock().map_err(|e| e.to_string())?;
+    if meta.apps.remove(&id).is_some() {
+        meta.save().map_err(|e| e.to_string())?;
+    }
+    // Only delete a shared binary once nothing references it
+    if let Some(sha) = &sha256 {
+        storage::release_blob(&storage, sha, &meta).map_err(|e| e.to_string())?;
+    }
+    Ok(trash_dir.map(|dir| trash::TrashedApp { id, dir }))
+}
+
+/// Launches the app; returns the child (unless it already exited cleanly), its log path
+/// and the systemd scope unit it runs in, if any.
+fn spawn_app(id: &str, extra_args: &[String]) -> Result<(Option<std::process::Child>, PathBuf, Option<String>), launch::LaunchError> {
+    spawn_app_with(id, extra_args, None)
+}
+
+/// `spawn_app`, as a test run stopped after `trial` when that's set.
+fn spawn_app_with(id: &str, extra_args: &[String], trial: Option<Duration>) -> Result<(Option<std::process::Child>, PathBuf, Option<String>), launch::LaunchError> {
+    validate_id(id).map_err(|_| launch::LaunchError::InvalidId { id: id.to_string() })?;
+    let storage = ensure_dirs().map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?.0;
+    let app_path = find_appimage(&storage, &metadata::load(), id).ok_or_else(|| launch::LaunchError::NotFound { id: id.to_string() })?;
+    let app_meta = metadata::load().get(id);
+    let mut args = app_meta.default_args.clone();
+    args.extend_from_slice(extra_args);
+    let log_path = app_log_path(id).map_err(|e| launch::LaunchError::SpawnFailed { message: e.to_string() })?;
+    let mut settings = settings::load();
+    // Without systemd the app just runs directly
+    let scope = (app_meta.systemd_scope.unwrap_or(settings.systemd_scope) && launch::systemd_scope_available()).then(|| launch::Scope {
+        unit: format!("axec-{id}-{}.scope", now_millis()),
+        memory_max: app_meta.memory_max.clone(),
+        cpu_quota: app_meta.cpu_quota.clone(),
+    });
+    let extracted = match app_meta.launch_mode {
+        launch::LaunchMode::Auto => None,
+        launch::LaunchMode::Fuse => {
+            settings.extract_and_run = false;
+            None
+        }
+        launch::LaunchMode::ExtractAndRun => {
+            settings.extract_and_run = false;
+            Some(extracted_tree(&storage, id, &app_path, &app_meta).map_err(|message| launch::LaunchError::SpawnFailed { message })?)
+        }
+    };
+    let options = launch::LaunchOptions {
+        args: &args,
+        wrapper: app_meta.exec_wrapper.as_deref(),
+        terminal: app_meta.terminal,
+        scope: scope.as_ref(),
+        extracted: extracted.as_deref(),
+        env: &app_meta.scale_factor.map(|scale| launch::scale_env(scale, app_meta.toolkit)).unwrap_or_default(),
+        trial,
+    };
+    let child = match launch::launch(&app_path, id, &settings, &options, &log_path) {
+        Err(launch::LaunchError::FuseUnavailable { .. } | launch::LaunchError::MountFailed { .. }) if app_meta.launch_mode == launch::LaunchMode::Auto => {
+            settings.extract_and_run = true;
+            launch::launch(&app_path, id, &settings, &options, &log_path)?
+        }
+        // Name the real cause when the runtime is one known not to mount here
+        Err(launch::LaunchError::FuseUnavailable { stderr } | launch::LaunchError::MountFailed { stderr })
+            if app_meta.runtime.clone().or_else(|| appimage::runtime_info(&app_path).ok()).is_some_and(|r| launch::runtime_broken_here(&r)) =>
+        {
+            let version = app_meta.runtime.as_ref().and_then(|r| r.version.clone());
+            return Err(launch::LaunchError::IncompatibleRuntime { version, stderr });
+        }
+        result => result?,
+    };
+    Ok((child, log_path, scope.map(|s| s.unit)))
+}
+
+/// Cached extractions for `launch_mode = "extract_and_run"`, one `{id}/{sha256}` tree per app.
+fn extracted_dir(storage: &Path) -> PathBuf {
+    storage.join("extracted")
+}
+
+/// `id`'s cached extraction, unpacked afresh when the binary's hash changed since the last one.
+fn extracted_tree(storage: &Path, id: &str, app_path: &Path, app_meta: &metadata::AppMeta) -> Result<PathBuf, String> {
+    let sha = match &app_meta.sha256 {
+        Some(sha) => sha.clone(),
+        None => storage::sha256_file(app_path).map_err(|e| e.to_string())?,
+    };
+    let app_dir = extracted_dir(storage).join(id);
+    let root = app_dir.join(&sha);
+    if root.is_dir() {
+        return Ok(root);
+    }
+    // Any other tree is from a build that's gone
+    let _ = fs::remove_dir_all(&app_dir);
+    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    let work = tempfile::Builder::new().prefix(".partial-").tempdir_in(&app_dir).map_err(|e| e.to_string())?;
+    let app_type = app_meta.app_type.unwrap_or(appimage::AppImageType::Unknown);
+    let tree = extract_tree(app_path, work.path(), app_type).ok_or("Couldn't extract the AppImage")?;
+    fs::rename(&tree, &root).map_err(|e| e.to_string())?;
+    Ok(root)
 }
 
-#[tauri::command]
-fn launch_app(id: String) -> Result<(), String> {
-    let (storage, _apps_dir) = ensure_dirs().map_err(|e| e.to_string())?;
-    let app_path = ["AppImage", "appimage"].into_iter().map(|e| storage.join(format!("{id}.{e}"))).find(|p| p.exists()).ok_or("AppImage not found")?;
-    Command::new(app_path)
-        .spawn()
-        .map_err(|e| e.to_string())?;
+fn start_app(app: &AppHandle, supervisor: &Supervisor, id: &str, extra_args: &[String]) -> Result<(), launch::LaunchError> {
+    match spawn_app(id, extra_args) {
+        Ok((Some(child), log_path, scope)) => supervisor.track(app, id, child, extra_args, log_path, scope),
+        Ok((None, _, _)) => {}
+        // Crashing within the watch window is still a crash, for notifications and restarts
+        Err(launch::LaunchError::ExitedEarly { code, stderr }) => {
+            if let Ok(log_path) = app_log_path(id) {
+                supervisor.crashed_early(app, id, extra_args, &log_path, code);
+            }
+            return Err(launch::LaunchError::ExitedEarly { code, stderr });
+        }
+        Err(e) => return Err(e),
+    }
     Ok(())
 }
 
+/// One path in `StorageLayout`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutPath {
+    pub path: String,
+    pub exists: bool,
+    pub writable: bool,
+}
+
+impl LayoutPath {
+    fn new(path: &Path) -> Self {
+        LayoutPath { path: path.to_string_lossy().to_string(), exists: path.exists(), writable: doctor::is_writable(path) }
+    }
+}
+
+/// Where Axec resolved its directories to, for troubleshooting.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageLayout {
+    pub data_dir: LayoutPath,
+    pub storage: LayoutPath,
+    pub applications: LayoutPath,
+    pub logs: LayoutPath,
+    pub config: LayoutPath,
+    pub sandboxed: bool,
+}
+
+/// Serves JSON-RPC on stdin and stdout until stdin closes, without starting the GUI.
+pub fn run_rpc() -> io::Result<()> {
+    rpc::serve(io::stdin().lock(), io::stdout().lock())
+}
+
+/// Runs the app `args[0]` with the rest as its arguments and waits for it, for launchers
+/// that start Axec as `axec --launch <id>`. Returns the exit code to leave with.
+pub fn run_launch(args: &[String]) -> i32 {
+    let Some((id, rest)) = args.split_first() else {
+        eprintln!("axec: --launch needs an app id");
+        return 2;
+    };
+    match spawn_app(id, rest) {
+        Ok((Some(mut child), _, _)) => child.wait().ok().and_then(|status| status.code()).unwrap_or(1),
+        Ok((None, _, _)) => 0,
+        Err(e) => {
+            eprintln!("axec: {e}");
+            1
+        }
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
     .plugin(tauri_plugin_opener::init())
     .plugin(tauri_plugin_dialog::init())
-    .invoke_handler(tauri::generate_handler![list_apps, add_appimage, remove_app, launch_app])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    .plugin(tauri_plugin_window_state::Builder::default().build())
+    .plugin(tauri_plugin_notification::init())
+    .plugin(tauri_plugin_clipboard_manager::init())
+    .manage(commands::remove::UndoSlot::default())
+    .manage(UpdateCache::default())
+    .manage(commands::import::Staging::default())
+    .manage(Supervisor::default())
+    .setup(|app| {
+        let _ = APP_HANDLE.set(app.handle().clone());
+        // Platforms without a system tray just run without one
+        let _ = tray::setup(app.handle());
+        let handle = app.handle().clone();
+        thread::spawn(move || run_update_checker(handle));
+        if metadata::load().desktop_naming < commands::desktop::DESKTOP_NAMING {
+            thread::spawn(|| {
+                let _ = commands::desktop::migrate_desktop_naming();
+            });
+        }
+        Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+        commands::desktop::set_integration,
+        commands::desktop::apply_category_preset,
+        commands::desktop::recategorize,
+        commands::desktop::reset_categories,
+        commands::desktop::migrate_desktop_naming,
+        commands::desktop::regenerate_desktop_files,
+        commands::desktop::edit_desktop_file,
+        commands::desktop::reload_from_desktop,
+        commands::desktop::set_name_from_metadata,
+        commands::desktop::verify_desktop_integration,
+        commands::icons::thumbnail,
+        commands::icons::get_icon_data,
+        commands::icons::rescan_icons,
+        commands::icons::apply_icon_theme,
+        commands::import::add_appimage,
+        commands::import::pick_and_add,
+        commands::import::add_from_url,
+        commands::import::import_directory,
+        commands::import::probe_appimage,
+        commands::import::stage_appimage,
+        commands::import::commit_staged,
+        commands::import::discard_staged,
+        commands::import::find_external_entries,
+        commands::import::adopt_entry,
+        commands::import::export_app_bundle,
+        commands::import::import_app_bundle,
+        commands::launch::launch_app,
+        commands::launch::launch_and_wait,
+        commands::launch::test_launch,
+        commands::launch::list_running,
+        commands::launch::get_app_log,
+        commands::launch::app_usage,
+        commands::launch::pause_app,
+        commands::launch::resume_app,
+        commands::launch::kill_app,
+        commands::launch::relaunch_app,
+        commands::launch::set_launch_args,
+        commands::launch::clear_launch_args,
+        commands::launch::set_exec_wrapper,
+        commands::launch::set_resource_limits,
+        commands::launch::set_scale_factor,
+        commands::launch::set_auto_restart,
+        commands::launch::set_launch_mode,
+        commands::launch::clear_extract_cache,
+        commands::library::list_apps,
+        commands::library::list_app_summaries,
+        commands::library::get_app,
+        commands::library::list_recent,
+        commands::library::repair_app,
+        commands::library::app_exists,
+        commands::library::set_order,
+        commands::library::set_pinned,
+        commands::library::set_notes,
+        commands::library::move_app,
+        commands::library::refresh_tray,
+        commands::library::set_parent,
+        commands::library::copy_path_to_clipboard,
+        commands::library::get_license_text,
+        commands::library::fetch_screenshots,
+        commands::remove::remove_app,
+        commands::remove::batch_remove,
+        commands::remove::restore_app,
+        commands::remove::undo_last_remove,
+        commands::settings::doctor,
+        commands::settings::get_settings,
+        commands::settings::save_settings,
+        commands::storage::set_file_permissions,
+        commands::storage::extract_to,
+        commands::storage::compact_storage,
+        commands::storage::disk_usage,
+        commands::storage::get_storage_layout,
+        commands::updates::check_update_github,
+        commands::updates::update_app,
+        commands::updates::update_all,
+        commands::updates::find_updates,
+    ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                *UPDATE_CHECKER_STOP.0.lock().unwrap() = true;
+                UPDATE_CHECKER_STOP.1.notify_all();
+                app.state::<Supervisor>().resume_all();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{
+        launch::set_launch_args,
+        library::{list_apps, repair_app, set_notes},
+        settings::save_settings,
+    };
+    use std::thread;
+
+    /// Tests that point `$HOME` and `$XDG_DATA_HOME` somewhere else run one at a time.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// A fresh home dir for the duration of a test, holding all of Axec's data.
+    fn isolated_home() -> (std::sync::MutexGuard<'static, ()>, tempfile::TempDir) {
+        let guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let home = tempfile::tempdir().unwrap();
+        std::env::set_var("HOME", home.path());
+        std::env::set_var("XDG_DATA_HOME", home.path().join(".local/share"));
+        std::env::set_var("XDG_CONFIG_HOME", home.path().join(".config"));
+        (guard, home)
+    }
+
+    /// A minimal type 2 AppImage: an ELF header with the magic and nothing mountable after it.
+    /// `seed` makes its content, and so its hash, differ from other fixtures.
+    fn fake_appimage(dir: &Path, name: &str, seed: u8) -> PathBuf {
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(b"\x7fELF");
+        data[4] = 2;
+        data[5] = 1;
+        data[8..11].copy_from_slice(b"AI\x02");
+        data.extend_from_slice(&[seed; 256]);
+        let path = dir.join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// A type 2 AppImage whose squashfs holds `files`, given as image paths and contents.
+    /// Its runtime is only a header, so it can't run: tests see the in-process read.
+    fn squashfs_appimage(dir: &Path, name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let header = backhand::NodeHeader::new(0o755, 0, 0, 0);
+        let mut writer = backhand::FilesystemWriter::default();
+        let mut dirs = std::collections::BTreeSet::new();
+        for (path, data) in files {
+            let parents: Vec<&Path> = Path::new(path).ancestors().skip(1).filter(|d| !d.as_os_str().is_empty()).collect();
+            for parent in parents.into_iter().rev() {
+                if dirs.insert(parent.to_path_buf()) {
+                    writer.push_dir(parent, header).unwrap();
+                }
+            }
+            writer.push_file(io::Cursor::new(data.to_vec()), path, header).unwrap();
+        }
+        let mut image = io::Cursor::new(Vec::new());
+        writer.write(&mut image).unwrap();
+        let path = fake_appimage(dir, name, 0);
+        let mut data = fs::read(&path).unwrap();
+        data.truncate(64);
+        // The section header table ends where the header does, so the squashfs follows it
+        data[0x28..0x30].copy_from_slice(&64u64.to_le_bytes());
+        data.extend_from_slice(&image.into_inner());
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn non_executable_appimage_is_extracted_without_running_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = squashfs_appimage(dir.path(), "App.AppImage", &[(".DirIcon", b"icon"), ("app.desktop", b"[Desktop Entry]\nName=App\n")]);
+        fs::set_permissions(&app, fs::Permissions::from_mode(0o644)).unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let root = extract_squashfs(&app, work.path()).unwrap();
+        assert_eq!(fs::read(root.join(".DirIcon")).unwrap(), b"icon");
+        assert!(root.join("app.desktop").is_file());
+        assert!(!is_executable(&app));
+    }
+
+    #[test]
+    fn wm_class_is_guessed_only_when_the_entry_sets_none_and_the_setting_is_on() {
+        let (_guard, home) = isolated_home();
+        let (_, apps_dir) = app_dirs().unwrap();
+        let entry_of = |src: &Path| {
+            let id = import_appimage(src, None, None, true, &mut |_, _| {}).unwrap().id;
+            desktop::parse(&fs::read_to_string(apps_dir.join(desktop::entry_file_name(&id))).unwrap())
+        };
+        let unset = squashfs_appimage(home.path(), "Unset.AppImage", &[("tool.desktop", b"[Desktop Entry]\nName=Tool\nExec=/usr/bin/tool-bin %U\n")]);
+        let explicit = squashfs_appimage(home.path(), "Explicit.AppImage", &[("tool.desktop", b"[Desktop Entry]\nName=Tool\nExec=tool-bin\nStartupWMClass=ToolWindow\n")]);
+
+        settings::save(&Settings { guess_wm_class: true, ..Settings::default() }).unwrap();
+        assert_eq!(entry_of(&unset).get("StartupWMClass"), Some("tool-bin"));
+        assert_eq!(entry_of(&explicit).get("StartupWMClass"), Some("ToolWindow"));
+
+        settings::save(&Settings { guess_wm_class: false, ..Settings::default() }).unwrap();
+        assert_eq!(entry_of(&unset).get("StartupWMClass"), None);
+        assert_eq!(entry_of(&explicit).get("StartupWMClass"), Some("ToolWindow"));
+    }
+
+    #[test]
+    fn staged_imports_read_the_staged_tree_and_apply_overrides_in_place() {
+        let (_guard, home) = isolated_home();
+        // Nothing can be unpacked from this file, so whatever's found came from the tree
+        let src = fake_appimage(home.path(), "Staged.AppImage", 4);
+        let tree = tempfile::tempdir().unwrap();
+        fs::write(tree.path().join("tool.desktop"), "[Desktop Entry]\nName=Tool\nCategories=Game;\n").unwrap();
+        fs::write(tree.path().join(".DirIcon"), b"embedded").unwrap();
+        let import = |prepared: &Prepared| import_appimage_with(&src, None, None, true, InstallMode::Copy, Some(prepared), &mut |_, _| {}).unwrap();
+
+        let plain = Prepared { squash_root: Some(tree.path().to_path_buf()), name: None, categories: None, icon: None };
+        let entry = import(&plain);
+        assert_eq!(entry.name, "Tool");
+        assert_eq!(entry.categories, ["Game"]);
+        assert_eq!(fs::read(entry.icon_path.unwrap()).unwrap(), b"embedded");
+
+        let overridden = Prepared {
+            name: Some("Renamed".into()),
+            categories: Some(vec!["Utility".into()]),
+            icon: Some((b"chosen".to_vec(), "png")),
+            ..plain
+        };
+        let entry = import(&overridden);
+        assert_eq!(entry.name, "Renamed");
+        assert_eq!(entry.categories, ["Utility"]);
+        let icon = PathBuf::from(entry.icon_path.unwrap());
+        assert_eq!(icon.extension().unwrap(), "png");
+        assert_eq!(fs::read(&icon).unwrap(), b"chosen");
+        let app_meta = metadata::load().get(&entry.id);
+        assert_eq!(icons::stored_icons(&app_dir(&app_dirs().unwrap().0, &app_meta), &entry.id), [icon]);
+        let desktop = desktop::parse(&fs::read_to_string(&entry.desktop_file).unwrap());
+        assert_eq!(desktop.get("Name"), Some("Renamed"));
+    }
+
+    #[test]
+    fn ids_that_could_escape_storage_are_rejected() {
+        for id in ["", ".", "..", "../etc", "a/b", "/abs", "a\\b", "a\0b", "a b", "a.AppImage", "ü"] {
+            assert!(validate_id(id).is_err(), "{id:?} was accepted");
+        }
+        assert!(validate_id(&"a".repeat(MAX_ID_LEN + 1)).is_err());
+        for id in ["krita", "app-2", "my_app", "X11", &"a".repeat(MAX_ID_LEN)] {
+            assert!(validate_id(id).is_ok(), "{id:?} was rejected");
+        }
+    }
+
+    #[test]
+    fn adversarial_names_sanitize_to_safe_ids() {
+        let cases = [
+            ("Krita", "krita"),
+            ("a\nb\tc\0d\x1be", "abcde"),
+            ("../../etc/passwd", "etc-passwd"),
+            ("a/b\\c:d", "a-b-c-d"),
+            ("Café Üml", "caf---ml"),
+            ("日本語 Editor", "editor"),
+            ("  -Spaced Out-  ", "spaced-out"),
+            ("my_app-2", "my_app-2"),
+        ];
+        for (name, id) in cases {
+            assert_eq!(sanitize_filename(name), id, "for {name:?}");
+            assert!(validate_id(id).is_ok());
+        }
+        let long = sanitize_filename(&"x".repeat(500));
+        assert_eq!(long.len(), MAX_ID_LEN);
+        // Truncation happens before trimming, so a cut never leaves a trailing separator
+        assert_eq!(sanitize_filename(&format!("{}!{}", "y".repeat(MAX_ID_LEN - 1), "z".repeat(10))), "y".repeat(MAX_ID_LEN - 1));
+    }
+
+    #[test]
+    fn names_without_id_characters_are_rejected() {
+        for name in ["", "+++", "!!!---???", "\0\n", "日本語"] {
+            assert_eq!(sanitize_filename(name), "", "for {name:?}");
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let src = fake_appimage(dir.path(), "+++.AppImage", 0);
+        let result = plan_import(&src, None, None, false, InstallMode::Copy, &Settings::default(), dir.path());
+        assert!(result.is_err_and(|e| e.starts_with("Can't derive a safe app id")));
+    }
+
+    #[test]
+    fn symlinked_appimages_follow_the_symlink_mode() {
+        let (_guard, home) = isolated_home();
+        let (storage, _) = ensure_dirs().unwrap();
+        let target = fake_appimage(home.path(), "Real.AppImage", 1);
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o755)).unwrap();
+        let link = home.path().join("Tool.AppImage");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let plan = |settings: &Settings| plan_import(&link, None, None, false, InstallMode::Copy, settings, &storage).unwrap();
+
+        let copied = plan(&Settings::default());
+        assert_eq!(copied.mode, StoreMode::Copy);
+        assert_eq!(copied.real, fs::canonicalize(&target).unwrap());
+        // The id comes from the name the user picked, not the target's
+        assert_eq!(copied.id, "tool");
+
+        let preserved = plan(&Settings { symlink_mode: settings::SymlinkMode::Preserve, ..Settings::default() });
+        assert_eq!(preserved.mode, StoreMode::Symlink);
+        let entry = import_appimage(&link, None, None, false, &mut |_, _| {}).unwrap();
+        assert!(!fs::symlink_metadata(&entry.path).unwrap().file_type().is_symlink());
+
+        // Moving a link would strand its target; that's refused outright
+        let moved = plan_import(&link, None, None, false, InstallMode::Move, &Settings::default(), &storage);
+        assert!(moved.is_err());
+    }
+
+    #[test]
+    fn kept_in_place_files_keep_their_mode() {
+        let (_guard, home) = isolated_home();
+        settings::save(&Settings { file_mode: Some("700".into()), read_only_storage: true, ..Settings::default() }).unwrap();
+        let src = fake_appimage(home.path(), "InPlace.AppImage", 3);
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o644)).unwrap();
+        let keep = |src: &Path| import_appimage_with(src, None, None, false, InstallMode::KeepInPlace, None, &mut |_, _| {});
+        // Not executable: refused rather than chmodded through the link
+        assert!(keep(&src).unwrap_err().contains("isn't executable"));
+        assert_eq!(fs::metadata(&src).unwrap().permissions().mode() & 0o777, 0o644);
+
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o754)).unwrap();
+        let entry = keep(&src).unwrap();
+        assert!(fs::symlink_metadata(&entry.path).unwrap().file_type().is_symlink());
+        assert_eq!(fs::metadata(&src).unwrap().permissions().mode() & 0o777, 0o754);
+    }
+
+    #[test]
+    fn data_dir_candidates_follow_the_environment() {
+        let (_guard, home) = isolated_home();
+        let xdg = home.path().join("xdg");
+        std::env::set_var("XDG_DATA_HOME", &xdg);
+        assert_eq!(data_dir_candidates()[0], Some(xdg.clone()));
+        assert_eq!(data_dir().unwrap(), xdg);
+
+        // Unset or relative, XDG_DATA_HOME gives way to $HOME
+        let from_home = home.path().join(".local/share");
+        for value in [None, Some("relative/dir")] {
+            match value {
+                Some(v) => std::env::set_var("XDG_DATA_HOME", v),
+                None => std::env::remove_var("XDG_DATA_HOME"),
+            }
+            let candidates = data_dir_candidates();
+            assert_eq!(candidates[0], None);
+            assert_eq!(candidates[2], Some(from_home.clone()));
+            assert_eq!(data_dir().unwrap(), from_home);
+        }
+
+        std::env::remove_var("XDG_DATA_HOME");
+        std::env::remove_var("HOME");
+        let candidates = data_dir_candidates();
+        assert_eq!((&candidates[0], &candidates[2]), (&None, &None));
+        std::env::set_var("HOME", home.path());
+    }
+
+    #[test]
+    fn unusable_data_dirs_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, b"").unwrap();
+        let fine = dir.path().join("fine");
+        assert_eq!(first_usable_dir([file.join("below-a-file"), file.clone(), fine.clone()]), Some(fine));
+        assert_eq!(first_usable_dir([file.join("below-a-file")]), None);
+    }
+
+    #[test]
+    fn fallback_dir_must_be_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let uid = unsafe { libc::getuid() };
+        let private = dir.path().join("private");
+        fs::DirBuilder::new().mode(0o700).create(&private).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(is_private_dir(&private, uid));
+        assert!(!is_private_dir(&private, uid + 1));
+
+        let open = dir.path().join("open");
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(!is_private_dir(&open, uid));
+
+        // Someone else's link to a dir we own is still refused
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&private, &link).unwrap();
+        assert!(!is_private_dir(&link, uid));
+        assert!(!is_private_dir(&dir.path().join("missing"), uid));
+    }
+
+    #[test]
+    fn lowercase_extension_is_listed_as_is_and_fixed_by_repair() {
+        let (_guard, _home) = isolated_home();
+        let (storage, _) = ensure_dirs().unwrap();
+        let dropped = fake_appimage(&storage, "tool.appimage", 3);
+        let listed = list_apps(None, None, None).unwrap();
+        assert_eq!(listed.apps.len(), 1);
+        assert_eq!(listed.apps[0].id, "tool");
+        assert!(dropped.exists(), "listing renamed the file");
+
+        let plan::DryRun::Applied(entry) = repair_app("tool".into(), None).unwrap() else { panic!("repair only planned") };
+        let canonical = storage.join("tool.AppImage");
+        assert_eq!(entry.path, canonical.to_string_lossy());
+        assert!(canonical.is_file());
+        assert_eq!(fs::read_dir(&storage).unwrap().flatten().filter(|e| e.file_name() == "tool.appimage").count(), 0);
+    }
+
+    #[test]
+    fn toggling_theme_icons_installs_and_removes_them() {
+        let (_guard, home) = isolated_home();
+        let mut png = io::Cursor::new(Vec::new());
+        image::RgbaImage::from_pixel(48, 48, image::Rgba([0, 128, 255, 255])).write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let src = squashfs_appimage(home.path(), "Painter.AppImage", &[(".DirIcon", png.get_ref())]);
+        let entry = import_appimage(&src, None, None, false, &mut |_, _| {}).unwrap();
+        assert!(entry.icon_path.is_some());
+        let themed = user_icons_dir().unwrap().join("hicolor/48x48/apps/axec-painter.png");
+        let icon_line = || desktop::parse(&fs::read_to_string(&entry.desktop_file).unwrap()).get("Icon").map(String::from);
+
+        save_settings(Settings { theme_icons: true, ..settings::load() }).unwrap();
+        assert!(themed.is_file());
+        assert_eq!(icon_line().as_deref(), Some("axec-painter"));
+
+        save_settings(Settings { theme_icons: false, ..settings::load() }).unwrap();
+        assert!(!themed.exists());
+        assert_eq!(icon_line(), entry.icon_path);
+    }
+
+    #[test]
+    fn failed_atomic_write_keeps_the_previous_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.json");
+        write_atomic(&path, b"old contents").unwrap();
+        let result = write_atomic_with(&path, |file| {
+            io::Write::write_all(file, b"new con")?;
+            Err(io::Error::other("disk full"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old contents");
+        // The partial temp file doesn't linger either
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        write_atomic(&path, b"new contents").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new contents");
+    }
+
+    #[test]
+    fn read_only_apps_can_be_updated_and_removed() {
+        let (_guard, home) = isolated_home();
+        for (dedup_binaries, to_trash) in [(false, true), (true, false)] {
+            settings::save(&Settings { read_only_storage: true, dedup_binaries, ..Settings::default() }).unwrap();
+            let (storage, _) = app_dirs().unwrap();
+            let stored_mode = |path: &str| fs::metadata(owned_file(&storage, Path::new(path)).unwrap()).unwrap().permissions().mode() & 0o777;
+            let old = fake_appimage(home.path(), &format!("Sealed{dedup_binaries}.AppImage"), 1);
+            let entry = import_appimage(&old, None, None, false, &mut |_, _| {}).unwrap();
+            assert_eq!(stored_mode(&entry.path), 0o555);
+
+            // What `update_from` and `roll_back_update` do with a new build
+            let new = fake_appimage(home.path(), &format!("Sealed{dedup_binaries}-2.AppImage"), 2);
+            let updated = import_appimage(&new, Some(&entry.id), None, true, &mut |_, _| {}).unwrap();
+            assert_eq!(fs::read(&updated.path).unwrap(), fs::read(&new).unwrap());
+            assert_eq!(stored_mode(&updated.path), 0o555);
+            let sha = metadata::load().get(&entry.id).sha256.unwrap();
+
+            remove_stored_app(&entry.id, to_trash, &|_, _, _| {}).unwrap();
+            assert!(find_appimage(&storage, &metadata::load(), &entry.id).is_none());
+            assert!(!storage::blob_path(&storage, &sha).exists());
+            if to_trash {
+                let trashed = trash::find(&entry.id).unwrap().join(Path::new(&updated.path).file_name().unwrap());
+                assert_eq!(fs::read(&trashed).unwrap(), fs::read(&new).unwrap());
+                assert_eq!(fs::metadata(&trashed).unwrap().permissions().mode() & 0o200, 0o200);
+            }
+        }
+    }
+
+    #[test]
+    fn removing_one_of_two_apps_sharing_a_blob_keeps_it() {
+        let (_guard, home) = isolated_home();
+        settings::save(&Settings { dedup_binaries: true, ..Settings::default() }).unwrap();
+        let src = fake_appimage(home.path(), "Shared.AppImage", 7);
+        let first = import_appimage(&src, None, None, false, &mut |_, _| {}).unwrap();
+        let second = import_appimage(&src, Some("shared-copy"), None, false, &mut |_, _| {}).unwrap();
+        let (storage, _) = app_dirs().unwrap();
+        let sha = metadata::load().get(&first.id).sha256.unwrap();
+        let blob = storage::blob_path(&storage, &sha);
+        assert_eq!(metadata::load().references(&sha), 2);
+
+        remove_stored_app(&first.id, false, &|_, _, _| {}).unwrap();
+        assert!(blob.is_file());
+        assert_eq!(fs::read(&second.path).unwrap(), fs::read(&src).unwrap());
+        assert_eq!(metadata::load().references(&sha), 1);
+
+        remove_stored_app(&second.id, false, &|_, _, _| {}).unwrap();
+        assert!(!blob.exists());
+    }
+
+    #[test]
+    fn parallel_adds_and_edits_keep_metadata_consistent() {
+        let (_guard, home) = isolated_home();
+        let sources: Vec<PathBuf> = (0..6u8).map(|i| fake_appimage(home.path(), &format!("App{i}.AppImage"), i)).collect();
+        import_appimage(&sources[0], None, None, false, &mut |_, _| {}).unwrap();
+        let mut workers = Vec::new();
+        for src in &sources[1..] {
+            let src = src.clone();
+            workers.push(thread::spawn(move || import_appimage(&src, None, None, false, &mut |_, _| {}).map(|e| e.id)));
+        }
+        for i in 0..8 {
+            workers.push(thread::spawn(move || set_notes("app0".into(), format!("note {i}")).map(|_| "app0".to_string())));
+            workers.push(thread::spawn(move || set_launch_args("app0".into(), vec![format!("--n={i}")]).map(|_| "app0".to_string())));
+        }
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+        let meta = metadata::load();
+        assert_eq!(meta.apps.len(), sources.len());
+        for i in 0..sources.len() {
+            let app = &meta.apps[&format!("app{i}")];
+            assert!(app.sha256.is_some() && app.added_at.is_some());
+        }
+        // Whichever write landed last, none was lost halfway
+        let app0 = &meta.apps["app0"];
+        assert!(app0.notes.starts_with("note "));
+        assert_eq!(app0.default_args.len(), 1);
+    }
+
+    #[test]
+    fn app_settings_need_an_installed_app_and_reach_its_entry() {
+        let (_guard, home) = isolated_home();
+        assert_eq!(set_notes("ghost".into(), "hello".into()), Err("AppImage not found".to_string()));
+        assert!(metadata::load().apps.is_empty());
+
+        let id = import_appimage(&fake_appimage(home.path(), "Tool.AppImage", 1), None, None, false, &mut |_, _| {}).unwrap().id;
+        set_launch_args(id.clone(), vec!["--safe-mode".into()]).unwrap();
+        let (_, apps_dir) = app_dirs().unwrap();
+        let entry = desktop::parse(&fs::read_to_string(apps_dir.join(desktop::entry_file_name(&id))).unwrap());
+        assert!(entry.get("Exec").unwrap().contains("--safe-mode"));
+    }
+
+    #[test]
+    fn corrupt_metadata_is_kept_and_never_saved_over() {
+        let (_guard, home) = isolated_home();
+        let id = import_appimage(&fake_appimage(home.path(), "Tool.AppImage", 1), None, None, false, &mut |_, _| {}).unwrap().id;
+        let path = metadata::metadata_path().unwrap();
+        let mut broken = fs::read(&path).unwrap();
+        broken.truncate(broken.len() / 2);
+        fs::write(&path, &broken).unwrap();
+
+        // Readers see nothing, but writers refuse to start over from empty
+        assert!(metadata::load().apps.is_empty());
+        assert_eq!(metadata::lock().err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        assert!(set_notes(id, "hello".into()).unwrap_err().contains("is corrupt"));
+        assert_eq!(fs::read(&path).unwrap(), broken);
+        assert_eq!(fs::read(path.with_extension("json.bak")).unwrap(), broken);
+    }
+
+    #[test]
+    fn parallel_work_covers_every_item_once_in_order() {
+        let items: Vec<usize> = (0..50).collect();
+        for workers in [0, 1, 3, 100] {
+            let calls = AtomicUsize::new(0);
+            let out = for_each_parallel(&items, workers, |i| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                i * 2
+            });
+            assert_eq!(out, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+            assert_eq!(calls.into_inner(), items.len());
+        }
+        assert!(for_each_parallel(&[] as &[usize], 4, |i| *i).is_empty());
+    }
 }
@@ -0,0 +1,59 @@
+//! Finds the license an AppImage bundles, either as a file in its tree or the
+//! `<project_license>` its AppStream metadata declares.
+
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// File names, compared case-insensitively and ignoring any extension, that hold a license.
+const FILE_STEMS: [&str; 4] = ["license", "licence", "copying", "copyright"];
+/// Most of a license file `read_text` returns; real ones are a few dozen KiB at most.
+const MAX_TEXT: u64 = 1 << 20;
+/// Phrases from the permissive and MPL texts, with the SPDX id they identify.
+const SPDX_MARKERS: [(&str, &str); 4] = [
+    ("mozilla public license version 2.0", "MPL-2.0"),
+    ("apache license", "Apache-2.0"),
+    ("permission is hereby granted, free of charge", "MIT"),
+    ("redistribution and use in source and binary forms", "BSD-3-Clause"),
+];
+
+fn is_license_name(path: &Path) -> bool {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    FILE_STEMS.contains(&stem.as_str()) && path.is_file()
+}
+
+/// A license file at the tree's top level, else the first one under `usr/share/licenses`.
+pub fn find_file(squash_root: &Path) -> Option<PathBuf> {
+    let mut top: Vec<PathBuf> = fs::read_dir(squash_root).ok()?.flatten().map(|e| e.path()).filter(|p| is_license_name(p)).collect();
+    top.sort();
+    top.into_iter().next().or_else(|| {
+        let mut nested: Vec<PathBuf> = walkdir::WalkDir::new(squash_root.join("usr/share/licenses")).max_depth(2).into_iter().flatten().map(|e| e.into_path()).filter(|p| is_license_name(p)).collect();
+        nested.sort();
+        nested.into_iter().next()
+    })
+}
+
+pub fn read_text(path: &Path) -> Option<String> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(MAX_TEXT).read_to_end(&mut data).ok()?;
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Best-guess SPDX id for a license text; `None` for anything not recognised.
+pub fn guess_spdx(text: &str) -> Option<&'static str> {
+    let lower = text.to_ascii_lowercase();
+    // Every GNU v3 text carries the same date line; the older versions differ
+    let v3 = lower.contains("version 3, 29 june 2007");
+    if lower.contains("gnu affero general public license") {
+        return Some("AGPL-3.0");
+    }
+    if lower.contains("gnu lesser general public license") {
+        return Some(if v3 { "LGPL-3.0" } else { "LGPL-2.1" });
+    }
+    if lower.contains("gnu general public license") {
+        return Some(if v3 { "GPL-3.0" } else { "GPL-2.0" });
+    }
+    SPDX_MARKERS.iter().find(|(marker, _)| lower.contains(marker)).map(|(_, id)| *id)
+}
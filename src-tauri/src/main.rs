@@ -2,5 +2,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // Editors and scripts drive Axec over stdin and stdout instead of opening the window
+    if std::env::args().nth(1).as_deref() == Some("--rpc") {
+        if let Err(e) = axec_lib::run_rpc() {
+            eprintln!("axec: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Menu entries made through the Flatpak portal start apps this way
+    if std::env::args().nth(1).as_deref() == Some("--launch") {
+        let args: Vec<String> = std::env::args().skip(2).collect();
+        std::process::exit(axec_lib::run_launch(&args));
+    }
     axec_lib::run()
 }
@@ -0,0 +1,265 @@
+use crate::{
+    appimage::{AppImageType, RuntimeInfo},
+    launch::LaunchMode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io,
+    ops::{Deref, DerefMut},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Serializes writers within this process; the file lock covers other processes.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Per-app state Axec keeps beside the stored AppImages, keyed by app id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppMeta {
+    /// Display name chosen at add time; the file name is only a fallback.
+    pub name: Option<String>,
+    /// Stored file name when it isn't `{id}.AppImage`, with `preserve_original_filename`.
+    pub file_name: Option<String>,
+    /// Folder under storage holding the AppImage and icon, set by `move_app`; `None` is the top.
+    pub subdir: Option<String>,
+    pub comment: Option<String>,
+    /// The user's own notes on the app; Axec never writes these itself.
+    pub notes: String,
+    /// `Keywords=` from the embedded desktop entry, so menu search finds the app by them.
+    pub keywords: Vec<String>,
+    /// `Name[..]`/`Comment[..]`/`Keywords[..]` translations from the embedded desktop entry.
+    pub localized: BTreeMap<String, String>,
+    /// Freedesktop `Categories=` values for the generated entry; empty inherits from `parent`.
+    pub categories: Vec<String>,
+    /// Entry this one belongs to, like a suite's main app, for inherited settings.
+    pub parent: Option<String>,
+    /// Position in the user's manual ordering; unordered apps sort last.
+    pub order: Option<u32>,
+    /// Shown in the tray menu for quick launch.
+    pub pinned: bool,
+    /// Format detected when the app was added; decides how it gets unpacked.
+    pub app_type: Option<AppImageType>,
+    /// Which FUSE the embedded runtime needs, detected when the app was added.
+    pub runtime: Option<RuntimeInfo>,
+    /// Content hash of the stored binary; also the reference count for shared blobs.
+    pub sha256: Option<String>,
+    /// Passed before any per-launch args, and baked into the desktop entry's `Exec=`.
+    pub default_args: Vec<String>,
+    /// Args the AppImage's own entry asks for; `default_args` follows them on updates
+    /// until the user sets others.
+    pub recommended_args: Vec<String>,
+    /// File/URL field code for the desktop entry's `Exec=`, from the embedded entry; none when unset.
+    pub field_code: Option<char>,
+    /// The embedded entry's `MimeType=` values.
+    pub mime_types: Vec<String>,
+    /// Template like `mangohud %command%` wrapping every launch and the desktop entry.
+    pub exec_wrapper: Option<String>,
+    /// Unix millis of the first successful add; kept when the app is updated.
+    pub added_at: Option<u64>,
+    /// Where the app was downloaded from: a URL or a GitHub `owner/repo`.
+    pub source: Option<String>,
+    /// Release tag of the installed build, when it came from GitHub.
+    pub version: Option<String>,
+    /// The embedded entry's `X-AppImage-Version`, else the AppStream release version, else
+    /// one in the file name it was added as.
+    pub app_version: Option<String>,
+    /// The embedded entry's `X-AppImage-BuildId`.
+    pub build_id: Option<String>,
+    /// From the embedded entry's `Terminal=`; launches open a terminal emulator.
+    pub terminal: bool,
+    /// From the embedded entry's `NoDisplay=` or `Hidden=`.
+    pub no_display: bool,
+    /// The embedded entry's `StartupWMClass=`.
+    pub startup_wm_class: Option<String>,
+    /// What `desktop::guess_wm_class` made of the image, for `guess_wm_class`.
+    pub wm_class_guess: Option<String>,
+    /// Theme the active icon is drawn for, when the AppImage ships light and dark ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_variant: Option<crate::icons::ThemeVariant>,
+    /// Hash of the icon as extracted; a stored icon that no longer matches was replaced by the user.
+    pub icon_sha256: Option<String>,
+    /// Screenshot URLs from the embedded AppStream metadata.
+    pub screenshots: Vec<String>,
+    /// SPDX id from AppStream's `<project_license>`, else guessed from a bundled license file.
+    pub license: Option<String>,
+    /// Whether a menu entry is written; starts as the embedded `X-AppImage-Integrate=`.
+    pub integrate: Option<bool>,
+    pub launch_mode: LaunchMode,
+    /// Overrides the `systemd_scope` setting for this app.
+    pub systemd_scope: Option<bool>,
+    /// `MemoryMax=` for the app's scope, like `2G`.
+    pub memory_max: Option<String>,
+    /// `CPUQuota=` for the app's scope, like `150%`.
+    pub cpu_quota: Option<String>,
+    /// Relaunch the app when it crashes, up to a few times a minute.
+    pub auto_restart: bool,
+    /// Octal mode like `750` for the stored AppImage; overrides the `file_mode` setting.
+    pub file_mode: Option<String>,
+    /// HiDPI scale to launch with, for apps that don't pick it up themselves.
+    pub scale_factor: Option<f32>,
+    /// Toolkit found in the bundled libraries, which picks the scaling variables.
+    pub toolkit: Option<crate::launch::Toolkit>,
+}
+
+impl AppMeta {
+    /// Apps without a recorded choice, like ones added before it existed, are integrated.
+    pub fn integrated(&self) -> bool {
+        self.integrate.unwrap_or(true)
+    }
+
+    pub fn categories_or_default(&self) -> Vec<String> {
+        if self.categories.is_empty() {
+            vec!["Utility".to_string()]
+        } else {
+            self.categories.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    pub apps: BTreeMap<String, AppMeta>,
+    /// Desktop file naming scheme the entries were last migrated to; 0 is `axec-{id}`.
+    pub desktop_naming: u32,
+}
+
+impl Metadata {
+    pub fn get(&self, id: &str) -> AppMeta {
+        self.apps.get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn entry(&mut self, id: &str) -> &mut AppMeta {
+        self.apps.entry(id.to_string()).or_default()
+    }
+
+    /// `id`'s own categories, else the nearest ancestor's, else `Utility`.
+    pub fn resolved_categories(&self, id: &str) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut current = self.apps.get(id);
+        while let Some(app) = current {
+            if !app.categories.is_empty() {
+                return app.categories.clone();
+            }
+            // A cycle of parents just ends the walk
+            current = app.parent.as_deref().filter(|p| seen.insert(*p)).and_then(|p| self.apps.get(p));
+        }
+        AppMeta::default().categories_or_default()
+    }
+
+    /// Every app that inherits from `id`, directly or through other apps.
+    pub fn descendants(&self, id: &str) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        let mut frontier = vec![id.to_string()];
+        while let Some(current) = frontier.pop() {
+            for (child, _) in self.apps.iter().filter(|(c, m)| m.parent.as_deref() == Some(current.as_str()) && c.as_str() != id) {
+                if !out.contains(child) {
+                    out.push(child.clone());
+                    frontier.push(child.clone());
+                }
+            }
+        }
+        out
+    }
+
+    /// The app stored under `file_name` by its original name, if any.
+    pub fn id_for_file(&self, file_name: &str) -> Option<&str> {
+        self.apps.iter().find(|(_, m)| m.file_name.as_deref() == Some(file_name)).map(|(id, _)| id.as_str())
+    }
+
+    /// How many apps store a binary with this content hash.
+    pub fn references(&self, sha256: &str) -> usize {
+        self.apps.values().filter(|m| m.sha256.as_deref() == Some(sha256)).count()
+    }
+}
+
+pub fn metadata_path() -> io::Result<PathBuf> {
+    Ok(crate::data_dir()?.join("axec/metadata.json"))
+}
+
+/// Reads the metadata file; a missing one is empty, an unreadable one an error.
+fn read(path: &Path) -> io::Result<Metadata> {
+    match fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Metadata::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read-only view of the metadata, empty if the file can't be read. Anything that saves
+/// must go through `lock`, which refuses to start from a file it couldn't parse.
+pub fn load() -> Metadata {
+    metadata_path().and_then(|path| read(&path)).unwrap_or_default()
+}
+
+/// Metadata loaded under an exclusive lock, so a read-modify-write can't interleave with
+/// another writer's. Unlocked when dropped; plain `load` readers never block, since saves
+/// replace the file atomically.
+pub struct Locked {
+    metadata: Metadata,
+    _file: File,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Locked {
+    pub fn save(&self) -> io::Result<()> {
+        save(&self.metadata)
+    }
+}
+
+impl Deref for Locked {
+    type Target = Metadata;
+
+    fn deref(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+impl DerefMut for Locked {
+    fn deref_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+}
+
+/// Takes the metadata write lock and loads the current contents. Not reentrant: don't
+/// call it again before dropping the result.
+pub fn lock() -> io::Result<Locked> {
+    let guard = WRITE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let path = metadata_path()?.with_extension("json.lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::options().create(true).truncate(false).write(true).open(&path)?;
+    while unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    let metadata_path = metadata_path()?;
+    let metadata = read(&metadata_path).map_err(|e| {
+        if e.kind() != io::ErrorKind::InvalidData {
+            return e;
+        }
+        // Saving over the file would lose every app in it, so stop and keep a copy
+        let backup = metadata_path.with_extension("json.bak");
+        match fs::copy(&metadata_path, &backup) {
+            Ok(_) => io::Error::new(e.kind(), format!("{} is corrupt ({e}); a copy was kept as {}", metadata_path.display(), backup.display())),
+            Err(copy) => io::Error::new(e.kind(), format!("{} is corrupt ({e}) and couldn't be backed up: {copy}", metadata_path.display())),
+        }
+    })?;
+    Ok(Locked { metadata, _file: file, _guard: guard })
+}
+
+fn save(metadata: &Metadata) -> io::Result<()> {
+    let path = metadata_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(metadata).map_err(io::Error::other)?;
+    crate::write_atomic(&path, json.as_bytes())
+}
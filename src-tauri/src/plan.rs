@@ -0,0 +1,69 @@
+//! Dry-run support: the filesystem changes a mutating command would make, listed
+//! instead of performed.
+
+use serde::Serialize;
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    Copy,
+    /// Symlink `path` to `from`, for preserved links and shared blobs.
+    Symlink,
+    /// Unpack the icon and desktop entry from the AppImage at `from` into `path`.
+    Extract,
+    Write,
+    Chmod,
+    Rename,
+    /// Move the file at `from` to `path`, copying when they're on different filesystems.
+    Move,
+    /// Move into the trash dir at `path`.
+    Trash,
+    Remove,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Action {
+    pub op: Op,
+    pub path: String,
+    pub from: Option<String>,
+    /// Bytes this step copies; zero for renames, links and metadata writes.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+    pub bytes_copied: u64,
+}
+
+impl Plan {
+    pub fn push(&mut self, op: Op, path: &Path, from: Option<&Path>, bytes: u64) {
+        self.bytes_copied += bytes;
+        self.actions.push(Action { op, path: path.to_string_lossy().to_string(), from: from.map(|p| p.to_string_lossy().to_string()), bytes });
+    }
+}
+
+/// A command's result, or with `dry_run` the plan it would have carried out.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DryRun<T> {
+    Applied(T),
+    Planned(Plan),
+}
+
+/// Bytes moving `src` under `dest_dir` copies: none when a rename will do.
+pub fn move_cost(src: &Path, dest_dir: &Path) -> u64 {
+    let Ok(src_meta) = fs::symlink_metadata(src) else { return 0 };
+    // The dir may not exist yet; it lands on whatever filesystem its nearest ancestor is on
+    let dest_dev = dest_dir.ancestors().find_map(|p| fs::metadata(p).ok()).map(|m| m.dev());
+    if src_meta.file_type().is_symlink() || dest_dev == Some(src_meta.dev()) {
+        0
+    } else {
+        src_meta.len()
+    }
+}
@@ -0,0 +1,127 @@
+//! Menu entries from inside Flatpak, through the `org.freedesktop.portal.DynamicLauncher`
+//! portal: the sandbox can't write the host's applications dir, but the portal installs
+//! entries there once the user confirms.
+//!
+//! Calls go through `gdbus`, which the Flatpak runtimes ship.
+
+use std::{
+    io::{self, BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+const DEST: &str = "org.freedesktop.portal.Desktop";
+const OBJECT: &str = "/org/freedesktop/portal/desktop";
+const INTERFACE: &str = "org.freedesktop.portal.DynamicLauncher";
+/// How long the user gets to answer the portal's install dialog.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(300);
+/// `launcher_type` for an application, as opposed to a web app.
+const LAUNCHER_APPLICATION: u32 = 1;
+
+fn call(method: &str, args: &[String]) -> io::Result<String> {
+    let output = Command::new("gdbus")
+        .args(["call", "--session", "--dest", DEST, "--object-path", OBJECT, "--method", method])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `s` as a GVariant text-format string literal.
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+/// `data` as a serialized `GBytesIcon`, the only icon form the portal takes.
+fn bytes_icon(data: &[u8]) -> String {
+    let bytes: Vec<String> = data.iter().map(|b| format!("0x{b:02x}")).collect();
+    format!("<('bytes', <[byte {}]>)>", bytes.join(", "))
+}
+
+/// Whether the session has a portal with the DynamicLauncher interface.
+pub fn available() -> bool {
+    call("org.freedesktop.DBus.Properties.Get", &[string(INTERFACE), string("version")]).is_ok()
+}
+
+/// Whether the portal already installed `desktop_file_id` for us.
+pub fn is_installed(desktop_file_id: &str) -> bool {
+    call(&format!("{INTERFACE}.GetDesktopEntry"), &[string(desktop_file_id)]).is_ok()
+}
+
+/// The `token` value of a `Request.Response` signal line, or why there's none.
+fn response_token(line: &str) -> io::Result<Option<String>> {
+    let args = line.split_once(" (").map(|(_, args)| args).unwrap_or_default();
+    match args.strip_prefix("uint32 ").and_then(|a| a.split(',').next()) {
+        Some("0") => {}
+        // 1 is the user cancelling
+        Some("1") => return Ok(None),
+        _ => return Err(io::Error::other("The portal couldn't prepare the launcher")),
+    }
+    let token = args.split_once("'token': <'").and_then(|(_, rest)| rest.split('\'').next());
+    token.map(|t| Some(t.to_string())).ok_or_else(|| io::Error::other("The portal's answer carries no token"))
+}
+
+/// Asks the portal to install `entry` as `desktop_file_id`, showing the user its
+/// confirmation dialog. The portal rewrites `Exec=` to run it through `flatpak run` and
+/// sets the icon from `icon`. Returns false when the user declines.
+pub fn install(desktop_file_id: &str, name: &str, icon: &[u8], entry: &str) -> io::Result<bool> {
+    let handle_token = format!("axec{}", uuid::Uuid::new_v4().simple());
+    // Listen before asking, so the answer can't arrive unseen
+    let mut monitor = Command::new("gdbus")
+        .args(["monitor", "--session", "--dest", DEST])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = monitor.stdout.take().ok_or_else(|| io::Error::other("gdbus monitor has no output"))?;
+    let (lines_tx, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if lines_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let result = (|| {
+        // gdbus says who owns the name once its match rule is in place
+        while !lines.recv_timeout(Duration::from_secs(10)).map_err(|_| io::Error::other("gdbus monitor didn't start"))?.contains("is owned by") {}
+        let options = format!("{{'handle_token': <{}>, 'modal': <true>, 'launcher_type': <uint32 {LAUNCHER_APPLICATION}>}}", string(&handle_token));
+        call(&format!("{INTERFACE}.PrepareInstall"), &[string(""), string(name), bytes_icon(icon), options])?;
+        let suffix = format!("/{handle_token}: org.freedesktop.portal.Request.Response ");
+        let line = loop {
+            let line = lines.recv_timeout(RESPONSE_TIMEOUT).map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "No answer from the launcher dialog"))?;
+            if line.contains(&suffix) {
+                break line;
+            }
+        };
+        let Some(token) = response_token(&line)? else { return Ok(false) };
+        call(&format!("{INTERFACE}.Install"), &[string(&token), string(desktop_file_id), string(entry), "@a{sv} {}".to_string()])?;
+        Ok(true)
+    })();
+    let _ = monitor.kill();
+    let _ = monitor.wait();
+    result
+}
+
+/// Removes a launcher the portal installed for us.
+pub fn uninstall(desktop_file_id: &str) -> io::Result<()> {
+    call(&format!("{INTERFACE}.Uninstall"), &[string(desktop_file_id), "@a{sv} {}".to_string()]).map(|_| ())
+}
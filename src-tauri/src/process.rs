@@ -0,0 +1,301 @@
+//! Keeps track of the AppImages Axec launched so they can be listed, stopped and relaunched.
+//!
+//! Each app runs in its own process group, so signals reach helpers it forked too.
+//! Apps started in a systemd scope are also signalled through the unit, which catches
+//! helpers that left the group.
+
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+/// How long a stopped app gets to exit after `SIGTERM` before it is killed outright.
+const STOP_GRACE: Duration = Duration::from_secs(5);
+/// Crash restarts allowed within `RESTART_WINDOW` before the watchdog gives up on an app.
+const RESTART_LIMIT: usize = 3;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningApp {
+    pub id: String,
+    pub pid: u32,
+    /// Unix millis.
+    pub started_at: u64,
+    /// Per-launch args on top of the saved defaults, reused by `relaunch_app`.
+    pub args: Vec<String>,
+    /// Where the app's stdout and stderr are captured.
+    pub log_path: PathBuf,
+    /// Frozen with `SIGSTOP` by `pause_app`.
+    pub paused: bool,
+    /// The systemd scope unit the app runs in, if any.
+    pub scope: Option<String>,
+}
+
+/// What an app's process group is using right now; all zeros when it isn't running.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResourceUsage {
+    pub running: bool,
+    pub processes: usize,
+    /// Resident memory summed over the group; pages shared between its processes count once per process.
+    pub rss_bytes: u64,
+    /// User plus system CPU time since each process started.
+    pub cpu_time_ms: u64,
+}
+
+/// Sums `/proc/<pid>/stat` over every process in the group `pgid` leads.
+fn group_usage(pgid: u32) -> ResourceUsage {
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+    let mut usage = ResourceUsage { running: true, ..Default::default() };
+    let Ok(rd) = std::fs::read_dir("/proc") else { return usage };
+    for entry in rd.flatten().filter(|e| e.file_name().to_str().is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))) {
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else { continue };
+        // The command name may itself contain spaces and parens; the fields follow its last ')'
+        let Some((_, rest)) = stat.rsplit_once(')') else { continue };
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let field = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok()).unwrap_or(0);
+        if field(2) != pgid as u64 {
+            continue;
+        }
+        usage.processes += 1;
+        usage.cpu_time_ms += (field(11) + field(12)) * 1000 / ticks_per_sec;
+        usage.rss_bytes += field(21) * page_size;
+    }
+    usage
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AppLaunched {
+    id: String,
+    pid: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AppExited {
+    id: String,
+    pid: u32,
+    code: Option<i32>,
+    /// Whether Axec stopped it, as opposed to it exiting on its own.
+    killed: bool,
+}
+
+/// Sent when an app exits non-zero or dies from a signal Axec didn't send.
+#[derive(Debug, Clone, Serialize)]
+struct AppCrashed {
+    id: String,
+    code: Option<i32>,
+    log_path: PathBuf,
+}
+
+/// Sent when an `auto_restart` app crashed too often, or couldn't be relaunched.
+#[derive(Debug, Clone, Serialize)]
+struct RestartGivingUp {
+    id: String,
+    /// Restarts within the last `RESTART_WINDOW`.
+    restarts: usize,
+    error: Option<String>,
+}
+
+struct Tracked {
+    info: RunningApp,
+    exited: Arc<(Mutex<bool>, Condvar)>,
+    killed: Arc<AtomicBool>,
+}
+
+/// Tauri state holding the newest instance of each launched app.
+#[derive(Default)]
+pub struct Supervisor {
+    apps: Mutex<HashMap<String, Tracked>>,
+    /// When each app was last restarted after a crash, within `RESTART_WINDOW`.
+    restarts: Mutex<HashMap<String, Vec<Instant>>>,
+}
+
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // A negative pid addresses the whole process group the app leads
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+/// Signals the app's group, and every process in its scope when it has one.
+fn signal_app(pid: u32, scope: Option<&str>, signal: libc::c_int) {
+    signal_group(pid, signal);
+    if let Some(unit) = scope {
+        let _ = Command::new("systemctl")
+            .args(["--user", "kill", "--signal", &signal.to_string(), unit])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// Stops an app Axec isn't tracking the way `Supervisor::kill` stops tracked ones:
+/// `SIGTERM`, then `SIGKILL` after `STOP_GRACE`. Returns the exit code, if it had one.
+pub fn terminate(child: &mut Child, scope: Option<&str>) -> Option<i32> {
+    signal_app(child.id(), scope, libc::SIGTERM);
+    let started = std::time::Instant::now();
+    while started.elapsed() < STOP_GRACE {
+        if let Ok(Some(status)) = child.try_wait() {
+            return status.code();
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    signal_app(child.id(), scope, libc::SIGKILL);
+    child.wait().ok().and_then(|s| s.code())
+}
+
+fn report_crash(app: &AppHandle, id: &str, log_path: &Path, code: Option<i32>) {
+    let _ = app.emit("axec://app-crashed", AppCrashed { id: id.to_string(), code, log_path: log_path.to_path_buf() });
+    if !crate::settings::load().notify_on_crash {
+        return;
+    }
+    let name = crate::metadata::load().get(id).name.unwrap_or_else(|| id.to_string());
+    let reason = match code {
+        Some(code) => format!("exited with code {code}"),
+        None => "was killed by a signal".to_string(),
+    };
+    let _ = app
+        .notification()
+        .builder()
+        .title(format!("{name} crashed"))
+        .body(format!("It {reason}. Log: {}", log_path.display()))
+        .show();
+}
+
+impl Supervisor {
+    /// Starts watching `child` and emits `axec://app-launched` now and `axec://app-exited` when it ends.
+    pub fn track(&self, app: &AppHandle, id: &str, mut child: Child, args: &[String], log_path: PathBuf, scope: Option<String>) {
+        let info = RunningApp { id: id.to_string(), pid: child.id(), started_at: crate::now_millis(), args: args.to_vec(), log_path, paused: false, scope };
+        let exited = Arc::new((Mutex::new(false), Condvar::new()));
+        let killed = Arc::new(AtomicBool::new(false));
+        let _ = app.emit("axec://app-launched", AppLaunched { id: info.id.clone(), pid: info.pid });
+        self.apps.lock().unwrap().insert(id.to_string(), Tracked { info: info.clone(), exited: Arc::clone(&exited), killed: Arc::clone(&killed) });
+
+        let app = app.clone();
+        thread::spawn(move || {
+            let code = child.wait().ok().and_then(|s| s.code());
+            {
+                let supervisor = app.state::<Supervisor>();
+                let mut apps = supervisor.apps.lock().unwrap();
+                // A newer instance may have replaced this one in the meantime
+                if apps.get(&info.id).is_some_and(|t| t.info.pid == info.pid) {
+                    apps.remove(&info.id);
+                }
+            }
+            let (done, cvar) = &*exited;
+            *done.lock().unwrap() = true;
+            cvar.notify_all();
+            let killed = killed.load(Ordering::SeqCst);
+            if !killed && code != Some(0) {
+                report_crash(&app, &info.id, &info.log_path, code);
+            }
+            let _ = app.emit("axec://app-exited", AppExited { id: info.id.clone(), pid: info.pid, code, killed });
+            if !killed && code != Some(0) && crate::metadata::load().get(&info.id).auto_restart {
+                app.state::<Supervisor>().restart(&app, &info.id, &info.args);
+            }
+        });
+    }
+
+    /// Handles an app that exited non-zero while `launch` was still watching it, and so
+    /// was never tracked, like a tracked crash: it's reported, and restarted if it has
+    /// `auto_restart`. The restart gets its own thread so the failed launch returns now.
+    pub fn crashed_early(&self, app: &AppHandle, id: &str, args: &[String], log_path: &Path, code: Option<i32>) {
+        report_crash(app, id, log_path, code);
+        if crate::metadata::load().get(id).auto_restart {
+            let (app, id, args) = (app.clone(), id.to_string(), args.to_vec());
+            thread::spawn(move || app.state::<Supervisor>().restart(&app, &id, &args));
+        }
+    }
+
+    /// Relaunches a crashed app with its old args, unless it already used up its
+    /// `RESTART_LIMIT` for the window; then it gets `axec://restart-giving-up` instead.
+    fn restart(&self, app: &AppHandle, id: &str, args: &[String]) {
+        let allowed = {
+            let mut restarts = self.restarts.lock().unwrap();
+            let recent = restarts.entry(id.to_string()).or_default();
+            recent.retain(|at| at.elapsed() < RESTART_WINDOW);
+            if recent.len() < RESTART_LIMIT {
+                recent.push(Instant::now());
+                Ok(recent.len())
+            } else {
+                // A later manual launch starts counting afresh
+                Err(std::mem::take(recent).len())
+            }
+        };
+        let (restarts, error) = match allowed {
+            Ok(restarts) => match crate::start_app(app, self, id, args) {
+                // Crashing again right away went through `crashed_early`, which counts it
+                Ok(()) | Err(crate::launch::LaunchError::ExitedEarly { .. }) => return,
+                Err(e) => (restarts, Some(e.to_string())),
+            },
+            Err(restarts) => (restarts, None),
+        };
+        let _ = app.emit("axec://restart-giving-up", RestartGivingUp { id: id.to_string(), restarts, error });
+    }
+
+    pub fn running(&self) -> Vec<RunningApp> {
+        let mut running: Vec<RunningApp> = self.apps.lock().unwrap().values().map(|t| t.info.clone()).collect();
+        running.sort_by_key(|r| r.started_at);
+        running
+    }
+
+    pub fn is_running(&self, id: &str) -> bool {
+        self.apps.lock().unwrap().contains_key(id)
+    }
+
+    pub fn usage(&self, id: &str) -> ResourceUsage {
+        let pid = self.apps.lock().unwrap().get(id).map(|t| t.info.pid);
+        pid.map(group_usage).unwrap_or_default()
+    }
+
+    /// Freezes or thaws the app's whole process group.
+    pub fn set_paused(&self, id: &str, paused: bool) -> Result<(), String> {
+        let mut apps = self.apps.lock().unwrap();
+        let tracked = apps.get_mut(id).ok_or_else(|| format!("{id} is not running"))?;
+        signal_group(tracked.info.pid, if paused { libc::SIGSTOP } else { libc::SIGCONT });
+        tracked.info.paused = paused;
+        Ok(())
+    }
+
+    /// Thaws every paused app, so none is left frozen once Axec is gone.
+    pub fn resume_all(&self) {
+        for tracked in self.apps.lock().unwrap().values_mut().filter(|t| t.info.paused) {
+            signal_group(tracked.info.pid, libc::SIGCONT);
+            tracked.info.paused = false;
+        }
+    }
+
+    /// Asks the app to quit, kills it if it hasn't after `STOP_GRACE`, and waits until it is gone.
+    pub fn kill(&self, id: &str) -> Result<(), String> {
+        let (info, exited, killed) = {
+            let apps = self.apps.lock().unwrap();
+            let tracked = apps.get(id).ok_or_else(|| format!("{id} is not running"))?;
+            (tracked.info.clone(), Arc::clone(&tracked.exited), Arc::clone(&tracked.killed))
+        };
+        killed.store(true, Ordering::SeqCst);
+        let (done, cvar) = &*exited;
+        let guard = done.lock().unwrap();
+        if *guard {
+            return Ok(());
+        }
+        signal_app(info.pid, info.scope.as_deref(), libc::SIGTERM);
+        // A stopped process can't act on SIGTERM until it is continued
+        signal_app(info.pid, info.scope.as_deref(), libc::SIGCONT);
+        let (guard, timeout) = cvar.wait_timeout_while(guard, STOP_GRACE, |done| !*done).unwrap();
+        if timeout.timed_out() {
+            signal_app(info.pid, info.scope.as_deref(), libc::SIGKILL);
+            drop(cvar.wait_while(guard, |done| !*done).unwrap());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! JSON-RPC 2.0 over stdin and stdout, for editors and scripts that drive Axec without
+//! the GUI (`axec --rpc`).
+//!
+//! Each line in is one request and each line out one response; notifications (requests
+//! without an `id`) get none. Methods call the same functions as the Tauri commands.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    thread,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The method ran and failed; `data` holds the command's own error.
+const COMMAND_FAILED: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into(), data: None }
+    }
+
+    /// Wraps a command's error, keeping its structure (e.g. a `LaunchError`) in `data`.
+    fn failed(error: impl Serialize + std::fmt::Display) -> Self {
+        RpcError { code: COMMAND_FAILED, message: error.to_string(), data: serde_json::to_value(&error).ok() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    sort: Option<crate::SortKey>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddParams {
+    path: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveParams {
+    id: String,
+    #[serde(default)]
+    to_trash: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct LaunchParams {
+    id: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeParams {
+    path: String,
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Omitted params are as good as an empty object
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_result(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(COMMAND_FAILED, e.to_string()))
+}
+
+fn dispatch(method: &str, raw: Value) -> Result<Value, RpcError> {
+    match method {
+        "list" => {
+            let p: ListParams = params(raw)?;
+            to_result(crate::commands::library::list_apps(p.sort, p.offset, p.limit).map_err(RpcError::failed)?)
+        }
+        "add" => {
+            let p: AddParams = params(raw)?;
+            to_result(crate::import_appimage(&PathBuf::from(p.path), None, None, p.force, &mut |_, _| {}).map_err(RpcError::failed)?)
+        }
+        "remove" => {
+            let p: RemoveParams = params(raw)?;
+            crate::validate_id(&p.id).map_err(RpcError::failed)?;
+            crate::remove_stored_app(&p.id, p.to_trash, &|_, _, _| {}).map_err(RpcError::failed)?;
+            Ok(Value::Null)
+        }
+        "launch" => {
+            let p: LaunchParams = params(raw)?;
+            let (child, log_path, _scope) = crate::spawn_app(&p.id, &p.args).map_err(RpcError::failed)?;
+            let pid = child.map(|mut child| {
+                let pid = child.id();
+                // Reap it when it exits; it isn't tied to this session otherwise
+                thread::spawn(move || child.wait());
+                pid
+            });
+            Ok(json!({ "pid": pid, "log_path": log_path }))
+        }
+        "probe" => {
+            let p: ProbeParams = params(raw)?;
+            to_result(crate::commands::import::probe_appimage(p.path).map_err(RpcError::failed)?)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {method}"))),
+    }
+}
+
+/// The response line for one request line, or `None` for a notification.
+fn handle(line: &str) -> Option<Value> {
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Err(e) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": RpcError::new(PARSE_ERROR, e.to_string()) })),
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": RpcError::new(INVALID_REQUEST, e.to_string()) })),
+        },
+    };
+    let result = if request.jsonrpc == "2.0" { dispatch(&request.method, request.params) } else { Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")) };
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    })
+}
+
+/// Answers requests from `input` on `output` until `input` ends.
+pub fn serve(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&line) {
+            writeln!(output, "{response}")?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+
+const SETTINGS_FILE: &str = "axec/settings.json";
+
+/// What `add_appimage` stores when the chosen file is a symlink to an AppImage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkMode {
+    /// Import a copy of the link's target.
+    #[default]
+    Copy,
+    /// Store a link to the resolved target, so updates to it are picked up in place.
+    Preserve,
+}
+
+/// What adding an app does when its id is already taken (and `force` isn't set).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    #[default]
+    Error,
+    /// Add it alongside as `{id}-2`, `{id}-3`, ...
+    Suffix,
+}
+
+/// Icon format desktop entries point at, for launchers that can't show PNG or SVG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconFormat {
+    /// Whatever the AppImage shipped.
+    #[default]
+    Native,
+    Png,
+    Ico,
+    Xpm,
+}
+
+impl IconFormat {
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            IconFormat::Native => None,
+            IconFormat::Png => Some("png"),
+            IconFormat::Ico => Some("ico"),
+            IconFormat::Xpm => Some("xpm"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Base directory handed to the AppImage runtime as `TMPDIR`, which is where it
+    /// creates its `.mount_*` FUSE mount point. `{id}` expands to the app id and a
+    /// leading `~/` to the home dir. Unset keeps the system default.
+    pub mount_dir_template: Option<String>,
+    /// Where AppImages are unpacked while being added or inspected; a leading `~/` is the
+    /// home dir. Unset uses a folder in storage, so it's on the same disk as the apps.
+    pub extract_temp_dir: Option<String>,
+    /// Launch every app with `APPIMAGE_EXTRACT_AND_RUN=1` instead of mounting it.
+    pub extract_and_run: bool,
+    /// Store identical AppImages once and symlink each app to the shared copy.
+    pub dedup_binaries: bool,
+    /// Store AppImages as `0555` so nothing writes to them by accident; Axec itself lifts
+    /// the mode whenever it replaces or moves one.
+    pub read_only_storage: bool,
+    /// Octal mode like `750` stored AppImages get, e.g. group access on shared installs;
+    /// unset is `755`. The owner always keeps read and execute.
+    pub file_mode: Option<String>,
+    /// Install icons into the user's hicolor theme and reference them by name instead
+    /// of by absolute path. Off by default since sandboxed launchers may not see the theme.
+    pub theme_icons: bool,
+    /// Converts icons for the desktop entry when they're in another format; takes precedence over `theme_icons`.
+    pub icon_format: IconFormat,
+    /// Template like `{name} (AppImage)` for the menu name of every generated entry;
+    /// Axec's own list keeps the plain name. Unset leaves names as they are.
+    pub name_decoration: Option<String>,
+    /// Give entries whose AppImage sets no `StartupWMClass` one guessed from its binary
+    /// name, for apps whose windows don't group under their menu entry.
+    pub guess_wm_class: bool,
+    pub symlink_mode: SymlinkMode,
+    pub on_collision: CollisionPolicy,
+    /// Store AppImages under the name they were added with instead of `{id}.AppImage`;
+    /// the id still names the desktop entry and icon.
+    pub preserve_original_filename: bool,
+    /// Read icons and desktop entries straight from the squashfs when adding apps,
+    /// instead of running the AppImage's `--appimage-extract`.
+    pub safe_extraction: bool,
+    /// Show a desktop notification when a launched app crashes.
+    pub notify_on_crash: bool,
+    /// Launch apps with only session basics (`HOME`, `PATH`, display and locale vars)
+    /// instead of Axec's full environment.
+    pub clean_env: bool,
+    /// Launch apps in a transient `systemd-run --user --scope` unit, for resource limits
+    /// and tracking; ignored where systemd isn't running.
+    pub systemd_scope: bool,
+    /// How many files `import_directory` and icon rescans work on at once.
+    pub max_parallel_imports: Option<usize>,
+    /// How many apps update checks and `update_all` work on at once.
+    pub max_parallel_updates: Option<usize>,
+    /// Caps the bytes Axec keeps in storage; unset or zero means unlimited.
+    pub max_storage_bytes: Option<u64>,
+    /// Sent to the GitHub API for update checks; raises the rate limit and reaches private repos.
+    pub github_token: Option<String>,
+    /// Hours between background update checks; unset or zero turns them off.
+    pub update_check_interval_hours: Option<u64>,
+}
+
+pub fn settings_path() -> io::Result<PathBuf> {
+    let config_dir = dirs::config_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "XDG config dir not found"))?;
+    Ok(config_dir.join(SETTINGS_FILE))
+}
+
+pub fn load() -> Settings {
+    // Missing or unreadable settings fall back to defaults rather than breaking every command
+    settings_path()
+        .and_then(fs::read_to_string)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(settings: &Settings) -> io::Result<()> {
+    let path = settings_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(io::Error::other)?;
+    crate::write_atomic(&path, json.as_bytes())
+}
+
+/// `name` as `name_decoration` dresses it for desktop entries.
+pub fn decorate_name(settings: &Settings, name: &str) -> String {
+    match &settings.name_decoration {
+        Some(template) => template.replace("{name}", name),
+        None => name.to_string(),
+    }
+}
+
+/// Undoes `decorate_name`, for names read back from an entry; other names pass through.
+pub fn undecorate_name<'a>(settings: &Settings, name: &'a str) -> &'a str {
+    let Some((prefix, suffix)) = settings.name_decoration.as_deref().and_then(|t| t.split_once("{name}")) else { return name };
+    name.strip_prefix(prefix).and_then(|n| n.strip_suffix(suffix)).unwrap_or(name)
+}
+
+/// Expands `mount_dir_template` for the given app id.
+pub fn mount_dir_for(settings: &Settings, id: &str) -> Option<PathBuf> {
+    let template = settings.mount_dir_template.as_deref()?.trim();
+    if template.is_empty() {
+        return None;
+    }
+    expand_home(&template.replace("{id}", id))
+}
+
+/// The `extract_temp_dir` setting, expanded.
+pub fn extract_temp_dir(settings: &Settings) -> Option<PathBuf> {
+    settings.extract_temp_dir.as_deref().map(str::trim).filter(|d| !d.is_empty()).and_then(expand_home)
+}
+
+fn expand_home(path: &str) -> Option<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map(|h| h.join(rest)),
+        None => Some(PathBuf::from(path)),
+    }
+}
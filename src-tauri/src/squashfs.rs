@@ -0,0 +1,116 @@
+//! Reads a type 2 AppImage's squashfs in-process, so probing never executes the binary.
+//!
+//! Only the files integration needs are unpacked: top-level entries (desktop file,
+//! `.DirIcon`), icons, AppStream metadata and license files. Symlinks are resolved
+//! inside the image and written out as plain files, so nothing extracted can point
+//! outside `dest`.
+
+use crate::appimage;
+use backhand::{FilesystemReader, InnerNode, Node, SquashfsFileReader};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+/// Directories, besides the image root, whose files are unpacked.
+const WANTED_DIRS: [&str; 5] = ["/usr/share/icons", "/usr/share/pixmaps", "/usr/share/metainfo", "/usr/share/appdata", "/usr/share/licenses"];
+/// Hops followed when a symlink points at another symlink.
+const MAX_LINK_HOPS: usize = 8;
+/// Files above this are skipped; icons and metadata are far smaller.
+const MAX_FILE_SIZE: usize = 32 << 20;
+
+fn is_wanted(path: &Path) -> bool {
+    path.parent() == Some(Path::new("/")) || WANTED_DIRS.iter().any(|d| path.starts_with(d))
+}
+
+/// Joins a symlink target onto the link's directory, resolving `.` and `..` lexically.
+fn resolve_link(link_path: &Path, target: &Path) -> PathBuf {
+    let base = if target.is_absolute() { PathBuf::from("/") } else { link_path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("/")) };
+    let mut out = base;
+    for c in target.components() {
+        match c {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::Normal(part) => out.push(part),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Unpacks the integration files of the AppImage at `path` into `dest`, laid out like
+/// `--appimage-extract` would, and returns `dest`.
+pub fn extract_metadata(path: &Path, dest: &Path) -> io::Result<PathBuf> {
+    let offset = appimage::squashfs_offset(path)?;
+    let reader = BufReader::new(File::open(path)?);
+    let fs_reader = FilesystemReader::from_reader_with_offset(reader, offset).map_err(io::Error::other)?;
+    let nodes: HashMap<&Path, &Node<SquashfsFileReader>> = fs_reader.files().map(|n| (n.fullpath.as_path(), n)).collect();
+
+    for node in fs_reader.files().filter(|n| is_wanted(&n.fullpath)) {
+        // Follow symlinks within the image to the file they name
+        let mut current = node;
+        for _ in 0..MAX_LINK_HOPS {
+            let InnerNode::Symlink(link) = &current.inner else { break };
+            match nodes.get(resolve_link(&current.fullpath, &link.link).as_path()) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        let InnerNode::File(file) = &current.inner else { continue };
+        let Ok(rel) = node.fullpath.strip_prefix("/") else { continue };
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            continue;
+        }
+        let target = dest.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = Vec::new();
+        fs_reader.file(file).reader().take(MAX_FILE_SIZE as u64 + 1).read_to_end(&mut data)?;
+        if data.len() <= MAX_FILE_SIZE {
+            fs::write(target, data)?;
+        }
+    }
+    Ok(dest.to_path_buf())
+}
+
+/// Unpacks the whole squashfs into `dest`, for runtimes that have no working
+/// `--appimage-extract` on a host without `unsquashfs`. Unlike `extract_metadata`,
+/// symlinks stay symlinks and modes are kept, so the tree can be run.
+pub fn extract_all(path: &Path, dest: &Path) -> io::Result<PathBuf> {
+    let offset = appimage::squashfs_offset(path)?;
+    let reader = BufReader::new(File::open(path)?);
+    let fs_reader = FilesystemReader::from_reader_with_offset(reader, offset).map_err(io::Error::other)?;
+    fs::create_dir_all(dest)?;
+    let mut dirs = Vec::new();
+    for node in fs_reader.files() {
+        let Ok(rel) = node.fullpath.strip_prefix("/") else { continue };
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            continue;
+        }
+        let target = dest.join(rel);
+        match &node.inner {
+            InnerNode::Dir(_) => {
+                fs::create_dir_all(&target)?;
+                dirs.push((target, node.header.permissions));
+            }
+            InnerNode::File(file) => {
+                let mut out = File::create(&target)?;
+                io::copy(&mut fs_reader.file(file).reader(), &mut out)?;
+                out.set_permissions(fs::Permissions::from_mode(u32::from(node.header.permissions) & 0o777))?;
+            }
+            InnerNode::Symlink(link) => symlink(&link.link, &target)?,
+            // Devices, pipes and sockets have no place in an app tree
+            _ => {}
+        }
+    }
+    // Last, so a read-only dir doesn't stop its own contents being written
+    for (dir, mode) in dirs.into_iter().rev() {
+        fs::set_permissions(dir, fs::Permissions::from_mode(u32::from(mode) & 0o777 | 0o700))?;
+    }
+    Ok(dest.to_path_buf())
+}
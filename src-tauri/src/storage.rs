@@ -0,0 +1,246 @@
+//! Content-addressed storage for deduplicated binaries.
+//!
+//! With `dedup_binaries` on, each distinct AppImage is stored once as
+//! `blobs/<sha256>.AppImage` and every app using it gets an `<id>.AppImage` symlink.
+//! The metadata `sha256` fields act as the reference count.
+
+use crate::metadata::Metadata;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    os::{
+        fd::AsRawFd,
+        unix::fs::{symlink, FileExt},
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+const COPY_CHUNK: usize = 4 << 20;
+/// Tries `retry_transient` makes in all; the wait doubles after each failure.
+const RETRY_ATTEMPTS: u32 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Errors a busy disk or network filesystem can clear up by itself.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+        || matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::EBUSY | libc::ETIMEDOUT))
+}
+
+/// Runs `op`, retrying with exponential backoff while it fails with a transient error.
+/// Anything else, or the last transient failure, is returned as is.
+pub fn retry_transient<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = RETRY_BASE_DELAY;
+    for _ in 1..RETRY_ATTEMPTS {
+        match op() {
+            Err(e) if is_transient(&e) => {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+/// Where `copy_buffered` writes: positional, so a chunk that failed halfway can be
+/// written again without duplicating the part that got through.
+trait ChunkSink {
+    fn write_chunk(&mut self, data: &[u8], offset: u64) -> io::Result<()>;
+}
+
+impl ChunkSink for File {
+    fn write_chunk(&mut self, data: &[u8], offset: u64) -> io::Result<()> {
+        self.write_all_at(data, offset)
+    }
+}
+
+/// Copies `src` to `dest` as a reflink where the filesystem can share extents (Btrfs,
+/// XFS), else a buffered copy with `on_progress(copied, total)` calls. The copy is
+/// synced and its size checked; a failed one may leave a partial `dest` behind.
+///
+/// Transient errors are retried per step and per chunk, so a busy disk never restarts
+/// a large copy from the first byte.
+pub fn copy_file(src: &Path, dest: &Path, on_progress: &mut dyn FnMut(u64, u64)) -> io::Result<u64> {
+    let reader = retry_transient(|| File::open(src))?;
+    let mut writer = retry_transient(|| File::create(dest))?;
+    let total = reader.metadata()?.len();
+    on_progress(0, total);
+    // Fails with EOPNOTSUPP, EXDEV or EINVAL when extents can't be shared
+    let copied = if unsafe { libc::ioctl(writer.as_raw_fd(), libc::FICLONE, reader.as_raw_fd()) } == 0 {
+        on_progress(total, total);
+        total
+    } else {
+        copy_buffered(&reader, &mut writer, total, on_progress)?
+    };
+    retry_transient(|| writer.sync_all())?;
+    if copied != total || writer.metadata()?.len() != total {
+        return Err(io::Error::other(format!("Copy of {} is incomplete", src.display())));
+    }
+    Ok(total)
+}
+
+fn copy_buffered(reader: &File, writer: &mut impl ChunkSink, total: u64, on_progress: &mut dyn FnMut(u64, u64)) -> io::Result<u64> {
+    let mut buf = vec![0u8; COPY_CHUNK];
+    let mut copied = 0u64;
+    loop {
+        let n = retry_transient(|| reader.read_at(&mut buf, copied))?;
+        if n == 0 {
+            return Ok(copied);
+        }
+        retry_transient(|| writer.write_chunk(&buf[..n], copied))?;
+        copied += n as u64;
+        on_progress(copied, total);
+    }
+}
+
+pub fn blob_path(storage: &Path, sha: &str) -> PathBuf {
+    storage.join("blobs").join(format!("{sha}.AppImage"))
+}
+
+/// Bytes stored under `storage`, blobs included; symlinks count as nothing, so shared
+/// and preserved binaries aren't counted twice or at all.
+pub fn usage(storage: &Path) -> u64 {
+    walkdir::WalkDir::new(storage)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Stores `src` as a blob (if not already present) and points `dest` at it.
+pub fn link_blob(storage: &Path, src: &Path, sha: &str, dest: &Path, on_progress: &mut dyn FnMut(u64, u64)) -> io::Result<()> {
+    let blob = blob_path(storage, sha);
+    if !blob.exists() {
+        fs::create_dir_all(blob.parent().unwrap())?;
+        // Copy under a temp name so a crash never leaves a truncated blob behind
+        let partial = blob.with_extension("partial");
+        if let Err(e) = copy_file(src, &partial, on_progress) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &blob)?;
+    }
+    symlink(&blob, dest)
+}
+
+/// Whether `path` is an app symlink into the blob store.
+pub fn is_blob_link(storage: &Path, path: &Path) -> bool {
+    fs::read_link(path).is_ok_and(|target| target.starts_with(storage.join("blobs")))
+}
+
+/// Deletes the blob for `sha` once no app in `meta` references it. Returns whether it was deleted.
+pub fn release_blob(storage: &Path, sha: &str, meta: &Metadata) -> io::Result<bool> {
+    let blob = blob_path(storage, sha);
+    if meta.references(sha) > 0 || !blob.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(blob)?;
+    Ok(true)
+}
+
+/// Resolves an app's blob file, if its stored path is a blob link.
+pub fn blob_for(storage: &Path, path: &Path) -> Option<PathBuf> {
+    is_blob_link(storage, path).then(|| fs::read_link(path).ok()).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::AppMeta;
+
+    /// Fails the first write of each chunk with `EAGAIN` after half of it got through.
+    #[derive(Default)]
+    struct FlakySink {
+        data: Vec<u8>,
+        tried: std::collections::HashSet<u64>,
+        failures: usize,
+    }
+
+    impl ChunkSink for FlakySink {
+        fn write_chunk(&mut self, chunk: &[u8], offset: u64) -> io::Result<()> {
+            let offset = offset as usize;
+            let end = if self.tried.insert(offset as u64) { offset + chunk.len() / 2 } else { offset + chunk.len() };
+            if self.data.len() < end {
+                self.data.resize(end, 0);
+            }
+            self.data[offset..end].copy_from_slice(&chunk[..end - offset]);
+            if end < offset + chunk.len() {
+                self.failures += 1;
+                return Err(io::Error::from_raw_os_error(libc::EAGAIN));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn transient_write_failures_retry_only_the_failed_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("app.AppImage");
+        let content: Vec<u8> = (0..COPY_CHUNK * 2 + 1000).map(|i| (i % 251) as u8).collect();
+        fs::write(&src, &content).unwrap();
+        let mut sink = FlakySink::default();
+        let mut reports = Vec::new();
+        let copied = copy_buffered(&File::open(&src).unwrap(), &mut sink, content.len() as u64, &mut |done, _| reports.push(done)).unwrap();
+        assert_eq!(copied, content.len() as u64);
+        assert_eq!(sink.data, content);
+        assert_eq!(sink.failures, 3);
+        // Progress only moves forward; nothing restarted from zero
+        assert_eq!(reports, [COPY_CHUNK as u64, 2 * COPY_CHUNK as u64, content.len() as u64]);
+    }
+
+    #[test]
+    fn retry_gives_up_on_lasting_or_permanent_errors() {
+        let mut calls = 0;
+        let result: io::Result<()> = retry_transient(|| {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(libc::EBUSY))
+        });
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EBUSY));
+        assert_eq!(calls, RETRY_ATTEMPTS);
+
+        calls = 0;
+        let result: io::Result<()> = retry_transient(|| {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn blob_survives_until_its_last_reference_goes() {
+        let storage = tempfile::tempdir().unwrap();
+        let blob = blob_path(storage.path(), "abc");
+        fs::create_dir_all(blob.parent().unwrap()).unwrap();
+        fs::write(&blob, b"binary").unwrap();
+        let mut meta = Metadata::default();
+        for id in ["one", "two"] {
+            meta.apps.insert(id.to_string(), AppMeta { sha256: Some("abc".to_string()), ..AppMeta::default() });
+        }
+        meta.apps.remove("one");
+        assert!(!release_blob(storage.path(), "abc", &meta).unwrap());
+        assert!(blob.is_file());
+        meta.apps.remove("two");
+        assert!(release_blob(storage.path(), "abc", &meta).unwrap());
+        assert!(!blob.exists());
+    }
+}
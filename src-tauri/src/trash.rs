@@ -0,0 +1,95 @@
+use crate::metadata::AppMeta;
+use std::{
+    fs,
+    io,
+    os::unix::fs::{symlink, MetadataExt},
+    path::{Path, PathBuf},
+};
+
+pub const META_FILE: &str = "meta.json";
+
+/// An app moved to Axec's trash: its files live in `dir` until restored.
+#[derive(Debug, Clone)]
+pub struct TrashedApp {
+    pub id: String,
+    pub dir: PathBuf,
+}
+
+pub fn trash_root() -> io::Result<PathBuf> {
+    Ok(crate::data_dir()?.join("axec/trash"))
+}
+
+/// Creates an empty trash slot for `id`, replacing an older trashed copy.
+pub fn prepare(id: &str) -> io::Result<PathBuf> {
+    let dir = trash_root()?.join(id);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+pub fn move_file(src: &Path, dest: &Path) -> io::Result<()> {
+    move_file_with_progress(src, dest, &mut |_, _| {})
+}
+
+/// Moves `src` to `dest`, renaming when both are on one filesystem and otherwise
+/// copying with `on_progress(copied, total)` calls. The source is only deleted once
+/// the copy is synced and its size checked.
+pub fn move_file_with_progress(src: &Path, dest: &Path, on_progress: &mut dyn FnMut(u64, u64)) -> io::Result<()> {
+    let src_meta = fs::symlink_metadata(src)?;
+    let dest_dir = dest.parent().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no parent directory"))?;
+    if fs::metadata(dest_dir)?.dev() == src_meta.dev() {
+        return fs::rename(src, dest);
+    }
+    if src_meta.file_type().is_symlink() {
+        // Copying would follow the link and duplicate a shared blob
+        symlink(fs::read_link(src)?, dest)?;
+        return fs::remove_file(src);
+    }
+    if let Err(e) = crate::storage::copy_file(src, dest, on_progress) {
+        let _ = fs::remove_file(dest);
+        return Err(e);
+    }
+    fs::set_permissions(dest, src_meta.permissions())?;
+    fs::remove_file(src)
+}
+
+/// Moves `src` into the trash slot, keeping its file name.
+pub fn move_into(src: &Path, dir: &Path, on_progress: &mut dyn FnMut(u64, u64)) -> io::Result<()> {
+    let name = src.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    move_file_with_progress(src, &dir.join(name), on_progress)
+}
+
+pub fn save_meta(dir: &Path, meta: &AppMeta) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(meta).map_err(io::Error::other)?;
+    fs::write(dir.join(META_FILE), json)
+}
+
+pub fn find(id: &str) -> io::Result<PathBuf> {
+    let dir = trash_root()?.join(id);
+    if dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, format!("{id} is not in the trash")))
+    }
+}
+
+/// Moves a trashed app's files back and returns the metadata it had when removed.
+pub fn restore(dir: &Path, storage: &Path, apps_dir: &Path) -> io::Result<AppMeta> {
+    let mut meta = AppMeta::default();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let p = entry.path();
+        let name = entry.file_name();
+        if name == META_FILE {
+            meta = fs::read_to_string(&p).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+            // Files come back to the top of storage, whichever subfolder they were in
+            meta.subdir = None;
+            continue;
+        }
+        let is_desktop = p.extension().and_then(|s| s.to_str()) == Some("desktop");
+        move_file(&p, &if is_desktop { apps_dir.join(&name) } else { storage.join(&name) })?;
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(meta)
+}
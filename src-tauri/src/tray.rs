@@ -0,0 +1,73 @@
+use crate::{ensure_dirs, find_appimage, metadata, process::Supervisor, show_main_window, start_app};
+use tauri::{
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    tray::TrayIconBuilder,
+    AppHandle, Manager, Wry,
+};
+
+const TRAY_ID: &str = "axec-tray";
+const LAUNCH_PREFIX: &str = "launch:";
+
+/// Pinned apps that still have a stored AppImage, as `(id, name)`, sorted by name.
+fn pinned_apps() -> Vec<(String, String)> {
+    let Ok((storage, _)) = ensure_dirs() else { return Vec::new() };
+    let meta = metadata::load();
+    let mut pinned: Vec<(String, String)> = meta
+        .apps
+        .iter()
+        .filter(|(id, m)| m.pinned && find_appimage(&storage, &meta, id).is_some())
+        .map(|(id, m)| (id.clone(), m.name.clone().unwrap_or_else(|| id.clone())))
+        .collect();
+    pinned.sort_by_key(|(_, name)| name.to_lowercase());
+    pinned
+}
+
+fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let menu = Menu::new(app)?;
+    let pinned = pinned_apps();
+    if pinned.is_empty() {
+        menu.append(&MenuItem::with_id(app, "no-pins", "No pinned apps", false, None::<&str>)?)?;
+    }
+    for (id, name) in pinned {
+        menu.append(&MenuItem::with_id(app, format!("{LAUNCH_PREFIX}{id}"), name, true, None::<&str>)?)?;
+    }
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&MenuItem::with_id(app, "show", "Show Axec", true, None::<&str>)?)?;
+    menu.append(&MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?)?;
+    Ok(menu)
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if let Some(app_id) = id.strip_prefix(LAUNCH_PREFIX) {
+        // Nowhere to show an error from the tray; the window surfaces launch failures
+        let _ = start_app(app, &app.state::<Supervisor>(), app_id, &[]);
+        return;
+    }
+    match id {
+        "show" => show_main_window(app),
+        "quit" => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Creates the tray icon. Errors mean the platform has no tray; callers may ignore them.
+pub fn setup(app: &AppHandle) -> tauri::Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Axec")
+        .menu(&build_menu(app)?)
+        .on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    Ok(())
+}
+
+/// Rebuilds the tray menu after pins or names change. No-op when there is no tray.
+pub fn refresh(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.set_menu(Some(build_menu(app)?))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Update checks for installed apps, based on where they were downloaded from.
+
+use crate::download::{self, DownloadError};
+use serde::Serialize;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInfo {
+    /// Tag of the installed build, if known.
+    pub current: Option<String>,
+    pub latest: String,
+    pub asset_name: String,
+    pub download_url: String,
+}
+
+/// Numeric parts of a tag like `v1.2.10-beta`, so `1.10` sorts after `1.9`.
+fn version_parts(tag: &str) -> Option<Vec<u64>> {
+    let core = tag.trim_start_matches(|c: char| !c.is_ascii_digit());
+    let core = core.split(|c: char| !(c.is_ascii_digit() || c == '.')).next()?;
+    let parts: Option<Vec<u64>> = core.split('.').filter(|p| !p.is_empty()).map(|p| p.parse().ok()).collect();
+    parts.filter(|p| !p.is_empty())
+}
+
+/// Whether `latest` is newer than `current`; tags that don't parse count as newer when they differ.
+fn is_newer(latest: &str, current: Option<&str>) -> bool {
+    let Some(current) = current else { return true };
+    match (version_parts(latest), version_parts(current)) {
+        (Some(l), Some(c)) => l.cmp(&c) == Ordering::Greater,
+        _ => latest != current,
+    }
+}
+
+/// Where the embedded update information `info` says new builds come from, as an
+/// `owner/repo` or a URL. Only the latest-release GitHub form and plain zsync have a
+/// fixed source; the zsync case fetches the control file to learn the URL.
+pub fn source_from_update_info(info: &str, headers: &[(String, String)]) -> Result<Option<String>, DownloadError> {
+    let parts: Vec<&str> = info.split('|').collect();
+    match parts.as_slice() {
+        ["gh-releases-zsync", owner, repo, "latest", ..] => Ok(Some(format!("{owner}/{repo}"))),
+        ["zsync", url, ..] => download::zsync_target(url, headers).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Whether `name` matches `pattern`, where each `*` stands for any run of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// The `.zsync` control file embedded update information `info` points to, with the
+/// release tag when it names a GitHub release.
+pub fn zsync_control(info: &str, headers: &[(String, String)], token: Option<&str>) -> Result<Option<(String, Option<String>)>, DownloadError> {
+    let parts: Vec<&str> = info.split('|').collect();
+    match parts.as_slice() {
+        ["gh-releases-zsync", owner, repo, "latest", pattern, ..] => {
+            let release = download::latest_release(&format!("{owner}/{repo}"), &download::github_headers(headers, token))?;
+            let asset = release.assets.iter().find(|a| matches_pattern(pattern, &a.name));
+            Ok(asset.and_then(|a| a.browser_download_url.clone()).map(|url| (url, Some(release.tag_name.clone()))))
+        }
+        ["zsync", url, ..] => Ok(Some((url.to_string(), None))),
+        _ => Ok(None),
+    }
+}
+
+/// Looks up `repo`'s latest release and returns it if it's newer than `current`.
+pub fn check_github(repo: &str, current: Option<&str>, token: Option<&str>) -> Result<Option<UpdateInfo>, DownloadError> {
+    let release = download::latest_release(repo, &download::github_headers(&[], token))?;
+    if !is_newer(&release.tag_name, current) {
+        return Ok(None);
+    }
+    let asset = download::pick_asset(&release).ok_or_else(|| DownloadError::NoAsset { repo: repo.to_string(), tag: release.tag_name.clone() })?;
+    Ok(Some(UpdateInfo { current: current.map(String::from), latest: release.tag_name.clone(), asset_name: asset.name.clone(), download_url: asset.url.clone() }))
+}
+
+/// The `owner/repo` whose latest release embedded update information `info` follows, for
+/// checks that go through GitHub's API.
+pub fn github_repo(info: &str) -> Option<String> {
+    match info.split('|').collect::<Vec<_>>().as_slice() {
+        ["gh-releases-zsync", owner, repo, "latest", ..] => Some(format!("{owner}/{repo}")),
+        _ => None,
+    }
+}
+
+/// Checks the source named by embedded update information `info` for a build newer than
+/// `current`. Plain zsync carries no version, so there a different `Length:` than
+/// `installed_len` counts as an update.
+pub fn check_update_info(info: &str, current: Option<&str>, installed_len: u64, token: Option<&str>) -> Result<Option<UpdateInfo>, DownloadError> {
+    if let Some(repo) = github_repo(info) {
+        return check_github(&repo, current, token);
+    }
+    let parts: Vec<&str> = info.split('|').collect();
+    match parts.as_slice() {
+        ["zsync", url, ..] => {
+            let header = download::zsync_header(url, &[])?;
+            if header.length.is_none_or(|len| len == installed_len) {
+                return Ok(None);
+            }
+            let asset_name = header.url.rsplit('/').next().unwrap_or(&header.url).to_string();
+            Ok(Some(UpdateInfo { current: current.map(String::from), latest: asset_name.clone(), asset_name, download_url: header.url }))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_gh_releases_info_goes_through_github() {
+        assert_eq!(github_repo("gh-releases-zsync|owner|tool|latest|tool-*-x86_64.AppImage.zsync").as_deref(), Some("owner/tool"));
+        assert_eq!(github_repo("gh-releases-zsync|owner|tool|v1.0|tool.AppImage.zsync"), None);
+        assert_eq!(github_repo("zsync|https://example.com/tool.AppImage.zsync"), None);
+        assert_eq!(github_repo(""), None);
+    }
+}
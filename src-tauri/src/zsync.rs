@@ -0,0 +1,385 @@
+//! Delta updates from `.zsync` control files: blocks the installed build shares with the
+//! new one are copied from disk, and only the rest is fetched with HTTP range requests.
+
+use crate::download::{self, DownloadError};
+use md4::Md4;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+/// A control file holds a few bytes per block; this covers images of many GiB.
+const MAX_CONTROL: u64 = 64 << 20;
+/// Missing runs this close together are fetched as one range, to save requests.
+const MERGE_GAP: usize = 4;
+/// Past this many range requests a plain download is quicker.
+const MAX_RANGES: usize = 2000;
+/// How much of the old build is held in memory at once while looking for reusable blocks.
+const WINDOW: usize = 16 << 20;
+
+/// A parsed control file.
+struct Control {
+    /// The file's own name, from `Filename:`.
+    file_name: Option<String>,
+    url: String,
+    length: u64,
+    block_size: usize,
+    /// How many consecutive blocks must match before one counts, 1 or 2.
+    seq_matches: usize,
+    rsum_bytes: usize,
+    checksum_bytes: usize,
+    /// Hex SHA-1 of the whole file.
+    sha1: Option<String>,
+    /// Per block: the masked rolling checksum and the truncated MD4.
+    blocks: Vec<(u32, Vec<u8>)>,
+}
+
+fn invalid(message: &str) -> DownloadError {
+    DownloadError::Io { message: format!("Unusable zsync control file: {message}") }
+}
+
+fn parse(data: &[u8], control_url: &str) -> Result<Control, DownloadError> {
+    let end = data.windows(2).position(|w| w == b"\n\n").ok_or_else(|| invalid("no end of header"))?;
+    let header = String::from_utf8_lossy(&data[..end]);
+    let field = |name: &str| header.lines().find_map(|l| l.strip_prefix(name)).map(str::trim);
+    let number = |name: &str| field(name).and_then(|v| v.parse::<u64>().ok()).ok_or_else(|| invalid(&format!("missing {name}")));
+    let length = number("Length:")?;
+    let block_size = number("Blocksize:")? as usize;
+    let url = field("URL:").ok_or_else(|| invalid("missing URL:"))?;
+    let lengths: Vec<usize> = field("Hash-Lengths:").unwrap_or("1,4,16").split(',').filter_map(|n| n.trim().parse().ok()).collect();
+    let [seq_matches, rsum_bytes, checksum_bytes] = lengths[..] else {
+        return Err(invalid("bad Hash-Lengths:"));
+    };
+    if block_size == 0 || !(1..=2).contains(&seq_matches) || !(1..=4).contains(&rsum_bytes) || !(3..=16).contains(&checksum_bytes) {
+        return Err(invalid("unsupported block parameters"));
+    }
+    let count = length.div_ceil(block_size as u64) as usize;
+    let sums = &data[end + 2..];
+    let entry = rsum_bytes + checksum_bytes;
+    if sums.len() < count * entry {
+        return Err(invalid("truncated block checksums"));
+    }
+    let blocks = sums
+        .chunks_exact(entry)
+        .take(count)
+        .map(|c| (c[..rsum_bytes].iter().fold(0u32, |k, &b| k << 8 | u32::from(b)), c[rsum_bytes..].to_vec()))
+        .collect();
+    Ok(Control {
+        file_name: field("Filename:").map(String::from),
+        url: download::resolve_url(control_url, url),
+        length,
+        block_size,
+        seq_matches,
+        rsum_bytes,
+        checksum_bytes,
+        sha1: field("SHA-1:").map(|h| h.to_ascii_lowercase()),
+        blocks,
+    })
+}
+
+/// zsync's weak checksum of one block.
+fn rsum(block: &[u8]) -> (u16, u16) {
+    let len = block.len();
+    block.iter().enumerate().fold((0u16, 0u16), |(a, b), (i, &c)| (a.wrapping_add(u16::from(c)), b.wrapping_add(((len - i) as u16).wrapping_mul(u16::from(c)))))
+}
+
+/// The last `bytes` bytes of the big-endian `a`, `b` pair, as the control file stores them.
+fn masked((a, b): (u16, u16), bytes: usize) -> u32 {
+    let full = u32::from(a) << 16 | u32::from(b);
+    if bytes >= 4 { full } else { full & ((1 << (8 * bytes)) - 1) }
+}
+
+fn key(sums: &[u32]) -> u64 {
+    sums.iter().fold(0u64, |k, &s| k << 32 | u64::from(s))
+}
+
+/// The first `bytes` bytes of a block's MD4, as the control file stores them.
+fn strong(block: &[u8], bytes: usize) -> Vec<u8> {
+    Md4::digest(block)[..bytes].to_vec()
+}
+
+/// A file read through a buffer of at least `capacity` bytes, zero-padded past its end
+/// up to `len`.
+struct Window {
+    file: File,
+    len: u64,
+    capacity: usize,
+    start: u64,
+    buf: Vec<u8>,
+}
+
+impl Window {
+    fn new(file: File, len: u64, capacity: usize) -> Self {
+        Window { file, len, capacity, start: 0, buf: Vec::new() }
+    }
+
+    /// `n` bytes from `at`, refilling the buffer from there when they aren't all in it.
+    fn get(&mut self, at: u64, n: usize) -> io::Result<&[u8]> {
+        if at < self.start || at + n as u64 > self.start + self.buf.len() as u64 {
+            let size = (self.len - at).min(self.capacity.max(n) as u64) as usize;
+            self.buf.clear();
+            self.buf.resize(size, 0);
+            let mut filled = 0;
+            while filled < size {
+                match self.file.read_at(&mut self.buf[filled..], at + filled as u64)? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            self.start = at;
+        }
+        let from = (at - self.start) as usize;
+        Ok(&self.buf[from..from + n])
+    }
+}
+
+/// Copies every block of `control`'s file that `old` holds somewhere into `out`, by
+/// rolling zsync's weak checksum over `old` a byte at a time and confirming hits with
+/// MD4. At most `window` bytes of `old` (or two blocks, if more) are in memory at once.
+/// Returns which blocks were found.
+fn reuse_blocks(control: &Control, old: &Path, out: &File, window: usize) -> io::Result<Vec<bool>> {
+    let bs = control.block_size;
+    let seq = control.seq_matches;
+    let count = control.blocks.len();
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    for k in 0..(count + 1).saturating_sub(seq) {
+        let sums: Vec<u32> = control.blocks[k..k + seq].iter().map(|b| b.0).collect();
+        index.entry(key(&sums)).or_default().push(k);
+    }
+    let file = File::open(old)?;
+    // The last block is zero-padded in the control file too
+    let len = file.metadata()?.len() + bs as u64;
+    let mut data = Window::new(file, len, window);
+    let mut have = vec![false; count];
+    let write_block = |k: usize, block: &[u8]| -> io::Result<()> {
+        let offset = (k * bs) as u64;
+        let len = (control.length - offset).min(bs as u64) as usize;
+        out.write_all_at(&block[..len], offset)
+    };
+    let span = bs * seq;
+    let fits = |p: u64| p + span as u64 <= len;
+    // Each step looks at the blocks at `p` and, to roll on, the byte after them
+    let mut p = 0u64;
+    let mut sums: Vec<(u16, u16)> = Vec::new();
+    if fits(0) {
+        let here = data.get(0, span)?;
+        sums = (0..seq).map(|j| rsum(&here[j * bs..(j + 1) * bs])).collect();
+    }
+    while fits(p) {
+        let here = data.get(p, (span + 1).min((len - p) as usize))?;
+        let masked_sums: Vec<u32> = sums.iter().map(|s| masked(*s, control.rsum_bytes)).collect();
+        let mut matched = false;
+        if let Some(candidates) = index.get(&key(&masked_sums)) {
+            let hashes: Vec<Vec<u8>> = (0..seq).map(|j| strong(&here[j * bs..(j + 1) * bs], control.checksum_bytes)).collect();
+            for &k in candidates {
+                let fits = (0..seq).all(|j| hashes[j] == control.blocks[k + j].1);
+                if fits && (0..seq).any(|j| !have[k + j]) {
+                    for j in 0..seq {
+                        write_block(k + j, &here[j * bs..(j + 1) * bs])?;
+                        have[k + j] = true;
+                    }
+                    matched = true;
+                }
+            }
+        }
+        if matched {
+            p += bs as u64;
+            if fits(p) {
+                let here = data.get(p, span)?;
+                sums = (0..seq).map(|j| rsum(&here[j * bs..(j + 1) * bs])).collect();
+            }
+            continue;
+        }
+        if here.len() == span {
+            break;
+        }
+        for (j, (a, b)) in sums.iter_mut().enumerate() {
+            let (gone, added) = (here[j * bs], here[(j + 1) * bs]);
+            *a = a.wrapping_sub(u16::from(gone)).wrapping_add(u16::from(added));
+            *b = b.wrapping_sub((bs as u32 * u32::from(gone)) as u16).wrapping_add(*a);
+        }
+        p += 1;
+    }
+    Ok(have)
+}
+
+/// Checks fetched `data` starting at block `first` against the control file's per-block
+/// checksums, so a bad range is caught even when there's no whole-file SHA-1.
+fn check_fetched(control: &Control, first: usize, data: &[u8]) -> Result<(), DownloadError> {
+    let bs = control.block_size;
+    for (j, chunk) in data.chunks(bs).enumerate() {
+        let mut block = chunk.to_vec();
+        block.resize(bs, 0);
+        let (sum, hash) = &control.blocks[first + j];
+        if masked(rsum(&block), control.rsum_bytes) != *sum || strong(&block, control.checksum_bytes) != *hash {
+            return Err(DownloadError::Io { message: format!("Block {} of the update doesn't match its checksum", first + j) });
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds the file the control file at `control_url` describes in `dir`, reusing what
+/// it can from `old`, and returns its path. Fetched blocks are checked against their
+/// checksums, and the whole file against its SHA-1 when the control file has one.
+///
+/// Fails when nothing matches, the server can't serve ranges, the reused parts would
+/// need too many requests, or the result doesn't check out; callers then download the
+/// whole file instead.
+pub fn sync(
+    control_url: &str,
+    headers: &[(String, String)],
+    old: &Path,
+    dir: &Path,
+    max_bytes: Option<u64>,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<PathBuf, DownloadError> {
+    let control = parse(&download::fetch(control_url, headers, MAX_CONTROL)?, control_url)?;
+    if let Some(limit) = max_bytes.filter(|limit| control.length > *limit) {
+        return Err(DownloadError::QuotaExceeded { over_by: control.length - limit });
+    }
+    let name = control.file_name.as_deref().and_then(|n| Path::new(n).file_name()).map(|n| n.to_os_string()).unwrap_or_else(|| "update.AppImage".into());
+    let dest = dir.join(name);
+    let out = File::create(&dest)?;
+    out.set_len(control.length)?;
+
+    let bs = control.block_size;
+    let count = control.blocks.len();
+    let have = reuse_blocks(&control, old, &out, WINDOW)?;
+    if !have.contains(&true) {
+        return Err(invalid("the installed build shares nothing with the new one"));
+    }
+
+    // Runs of missing blocks, with short gaps between them folded in
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for k in (0..count).filter(|&k| !have[k]) {
+        match ranges.last_mut() {
+            Some((_, last)) if k - *last <= MERGE_GAP + 1 => *last = k,
+            _ => ranges.push((k, k)),
+        }
+    }
+    if ranges.len() > MAX_RANGES {
+        return Err(invalid("too many changed regions for a delta"));
+    }
+    let byte_range = |(first, last): (usize, usize)| ((first * bs) as u64, (((last + 1) * bs) as u64).min(control.length) - 1);
+    let total: u64 = ranges.iter().map(|&r| byte_range(r)).map(|(start, end)| end - start + 1).sum();
+    let mut fetched = 0;
+    on_progress(0, total);
+    for range in ranges {
+        let (start, end) = byte_range(range);
+        let data = download::fetch_range(&control.url, headers, start, end)?;
+        check_fetched(&control, range.0, &data)?;
+        out.write_all_at(&data, start)?;
+        fetched += end - start + 1;
+        on_progress(fetched, total);
+    }
+    out.sync_all()?;
+    if out.metadata()?.len() != control.length {
+        return Err(DownloadError::Io { message: "The rebuilt file has the wrong length".into() });
+    }
+    if let Some(expected) = &control.sha1 {
+        if sha1_file(&dest)? != *expected {
+            return Err(DownloadError::Io { message: "The rebuilt file doesn't match its SHA-1".into() });
+        }
+    }
+    Ok(dest)
+}
+
+/// Hex SHA-1 of a file, the whole-file check zsync records.
+fn sha1_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha1::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The control file zsyncmake would write for `data`, minus the parts `reuse_blocks` ignores.
+    fn control_for(data: &[u8], block_size: usize, seq_matches: usize, rsum_bytes: usize) -> Control {
+        let blocks = data
+            .chunks(block_size)
+            .map(|chunk| {
+                let mut block = chunk.to_vec();
+                block.resize(block_size, 0);
+                (masked(rsum(&block), rsum_bytes), strong(&block, 8))
+            })
+            .collect();
+        Control {
+            file_name: None,
+            url: String::new(),
+            length: data.len() as u64,
+            block_size,
+            seq_matches,
+            rsum_bytes,
+            checksum_bytes: 8,
+            sha1: None,
+            blocks,
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn digests_match_the_reference_vectors() {
+        assert_eq!(hex(&strong(b"abc", 16)), "a448017aaf21d8525fc10ae87aa6729d");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(sha1_file(&path).unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn shifted_blocks_are_found_across_window_refills() {
+        let mut seed = 1u32;
+        let new: Vec<u8> = (0..1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        // The old build lacks a few bytes near the start and has others changed later on
+        let mut old = new[5..600].to_vec();
+        old.extend_from_slice(b"something else entirely");
+        old.extend_from_slice(&new[640..]);
+        let dir = tempfile::tempdir().unwrap();
+        let old_path = dir.path().join("old.AppImage");
+        std::fs::write(&old_path, &old).unwrap();
+        for (seq, rsum_bytes) in [(1, 4), (2, 2)] {
+            let control = control_for(&new, 32, seq, rsum_bytes);
+            let out_path = dir.path().join(format!("new-{seq}.AppImage"));
+            let out = File::create(&out_path).unwrap();
+            out.set_len(new.len() as u64).unwrap();
+            let have = reuse_blocks(&control, &old_path, &out, 70).unwrap();
+            let rebuilt = std::fs::read(&out_path).unwrap();
+            for (k, found) in have.iter().enumerate() {
+                let block = k * 32..((k + 1) * 32).min(new.len());
+                if *found {
+                    assert_eq!(rebuilt[block.clone()], new[block], "block {k}");
+                }
+            }
+            // Only the first block (cut short) and those around the change are missing
+            let missing: Vec<usize> = (0..have.len()).filter(|&k| !have[k]).collect();
+            assert!(missing.contains(&0) && missing.iter().all(|&k| k == 0 || (18..=20).contains(&k)), "{missing:?}");
+            // The short last block is matched through its zero padding
+            assert!(have[have.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn fetched_blocks_are_checked_against_their_checksums() {
+        let new: Vec<u8> = (0..100u8).collect();
+        let control = control_for(&new, 32, 1, 4);
+        assert!(check_fetched(&control, 1, &new[32..]).is_ok());
+        let mut bad = new[32..].to_vec();
+        bad[50] ^= 1;
+        assert!(check_fetched(&control, 1, &bad).is_err());
+        assert!(check_fetched(&control, 0, &new[32..64]).is_err());
+    }
+}
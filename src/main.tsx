@@ -2,6 +2,7 @@ import React, { useEffect, useMemo, useState } from 'react';
 import ReactDOM from 'react-dom/client';
 import './styles.css';
 import { invoke, convertFileSrc } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
 
 type AppImageEntry = {
@@ -10,20 +11,174 @@ type AppImageEntry = {
   path: string;
   icon_path?: string | null;
   desktop_file: string;
+  size: number;
+  categories: string[];
+  parent?: string | null;
+  order?: number | null;
+  pinned: boolean;
+  integrated: boolean;
+  added_at?: number | null;
+  license?: string | null;
+  build_id?: string | null;
+  notes: string;
+  version?: string | null;
+  comment?: string | null;
+  mime_types: string[];
 };
 
+type ScanWarning = {
+  path: string;
+  message: string;
+};
+
+type AppList = {
+  apps: AppImageEntry[];
+  warnings: ScanWarning[];
+  total: number;
+};
+
+type CompactReport = {
+  removed: string[];
+  bytes_reclaimed: number;
+};
+
+function formatBytes(bytes: number): string {
+  const units = ['B', 'KB', 'MB', 'GB'];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${unit === 0 ? value : value.toFixed(1)} ${units[unit]}`;
+}
+
+type ConflictStrategy = 'skip' | 'rename' | 'overwrite' | 'skip_if_same_hash';
+
+type ImportOutcome =
+  | { status: 'added'; entry: AppImageEntry; conflict: ConflictStrategy | null }
+  | { status: 'skipped'; path: string; existing_id: string | null; conflict: ConflictStrategy }
+  | { status: 'failed'; path: string; error: string };
+
+type RunningApp = {
+  id: string;
+  pid: number;
+  started_at: number;
+  paused: boolean;
+};
+
+type AppCrashed = {
+  id: string;
+  code: number | null;
+  log_path: string;
+};
+
+type RestartGivingUp = {
+  id: string;
+  restarts: number;
+  error: string | null;
+};
+
+type AvailableUpdate = {
+  id: string;
+  current_version: string | null;
+  latest_version: string;
+  download_url: string;
+};
+
+type UpdateOutcome =
+  | { status: 'updated'; entry: AppImageEntry; from: string | null; to: string | null }
+  | { status: 'unchanged'; id: string };
+
+type MenuPathWarning = {
+  apps_dir: string;
+  message: string;
+  fix: string;
+};
+
+type DoctorCheck = {
+  name: string;
+  status: 'pass' | 'warn' | 'fail';
+  detail: string;
+  fix: string | null;
+};
+
+type RemoveProgress = {
+  id: string;
+  file: string;
+  copied: number;
+  total: number;
+};
+
+type LaunchError =
+  | { kind: 'invalid_id'; id: string }
+  | { kind: 'not_found'; id: string }
+  | { kind: 'not_executable'; path: string }
+  | { kind: 'invalid_app_image'; path: string; reason: string }
+  | { kind: 'fuse_unavailable'; stderr: string }
+  | { kind: 'mount_failed'; stderr: string }
+  | { kind: 'runtime_lost_self'; stderr: string }
+  | { kind: 'incompatible_runtime'; version: string | null; stderr: string }
+  | { kind: 'exited_early'; code: number | null; stderr: string }
+  | { kind: 'spawn_failed'; message: string };
+
+type LaunchTestResult = {
+  ok: boolean;
+  code: number | null;
+  output: string;
+  error: LaunchError | null;
+};
+
+function describeLaunchError(e: unknown): string {
+  if (typeof e !== 'object' || e === null || !('kind' in e)) return String(e);
+  const err = e as LaunchError;
+  switch (err.kind) {
+    case 'invalid_id':
+      return `Invalid app id: ${err.id}`;
+    case 'not_found':
+      return 'This AppImage is missing from storage. Try re-adding it.';
+    case 'not_executable':
+      return `${err.path} is not executable. Mark it executable and try again.`;
+    case 'invalid_app_image':
+      return `The file looks corrupt or was built for another architecture (${err.reason}). Try re-downloading it.`;
+    case 'fuse_unavailable':
+      return 'FUSE is not available. Install libfuse2 (fuse2) or enable extract-and-run in settings.';
+    case 'mount_failed':
+      return 'The AppImage could not be mounted. Set a writable mount directory or enable extract-and-run in settings.';
+    case 'runtime_lost_self':
+      return 'The AppImage runtime could not find its own file. Remove any exec wrapper or turn off clean_env, then try again.';
+    case 'incompatible_runtime':
+      return `This AppImage's runtime${err.version ? ` (${err.version})` : ''} needs libfuse2, which isn't installed. Install libfuse2 or set its launch mode to extract-and-run.`;
+    case 'exited_early':
+      return `The app exited immediately${err.code !== null ? ` (code ${err.code})` : ''}${err.stderr ? `: ${err.stderr}` : ''}`;
+    case 'spawn_failed':
+      return `Failed to start the app: ${err.message}`;
+  }
+}
+
 function App() {
   const [apps, setApps] = useState<AppImageEntry[]>([]);
+  const [warnings, setWarnings] = useState<ScanWarning[]>([]);
   const [query, setQuery] = useState('');
   const [busy, setBusy] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [canUndo, setCanUndo] = useState(false);
+  const [notice, setNotice] = useState<string | null>(null);
+  const [checks, setChecks] = useState<DoctorCheck[] | null>(null);
+  const [removeProgress, setRemoveProgress] = useState<RemoveProgress | null>(null);
+  const [running, setRunning] = useState<Set<string>>(new Set());
+  const [paused, setPaused] = useState<Set<string>>(new Set());
+  const [view, setView] = useState<'library' | 'updates'>('library');
+  const [updates, setUpdates] = useState<AvailableUpdate[] | null>(null);
+  const [updating, setUpdating] = useState<string | null>(null);
 
   const load = async () => {
     setBusy(true);
     setError(null);
     try {
-      const result = await invoke<AppImageEntry[]>('list_apps');
-      setApps(result);
+      const result = await invoke<AppList>('list_apps', { sort: 'manual' });
+      setApps(result.apps);
+      setWarnings(result.warnings);
     } catch (e: any) {
       setError(String(e));
     } finally {
@@ -35,26 +190,130 @@ function App() {
     load();
   }, []);
 
+  useEffect(() => {
+    const refreshRunning = () =>
+      invoke<RunningApp[]>('list_running')
+        .then((list) => {
+          setRunning(new Set(list.map((r) => r.id)));
+          setPaused(new Set(list.filter((r) => r.paused).map((r) => r.id)));
+        })
+        .catch(() => {});
+    refreshRunning();
+    const unlistens = [
+      listen('axec://app-launched', refreshRunning),
+      listen('axec://app-exited', refreshRunning),
+      listen<AppCrashed>('axec://app-crashed', (event) => {
+        const { id, code, log_path } = event.payload;
+        const message = `${id} crashed${code !== null ? ` with exit code ${code}` : ''}. Log: ${log_path}`;
+        setError(message);
+        // Show the last lines it printed, which usually say why
+        invoke<string>('get_app_log', { id, maxBytes: 2048 })
+          .then((log) => {
+            const tail = log.split('\n').slice(-5).join('\n');
+            if (tail) setError(`${message}\n${tail}`);
+          })
+          .catch(() => {});
+      }),
+      listen<RestartGivingUp>('axec://restart-giving-up', (event) => {
+        const { id, restarts, error } = event.payload;
+        setError(error ? `Couldn't restart ${id}: ${error}` : `${id} crashed ${restarts} times in a minute; no longer restarting it.`);
+      }),
+      listen<AvailableUpdate[]>('axec://updates-available', (event) => {
+        setUpdates(event.payload);
+        setNotice(`Updates available: ${event.payload.map((u) => `${u.id} ${u.latest_version}`).join(', ')}`);
+      }),
+      // Clicking the update notification
+      listen('axec://open-updates', () => setView('updates')),
+      listen<MenuPathWarning>('axec://menu-path-warning', (event) => {
+        setError(`${event.payload.message}. ${event.payload.fix}.`);
+      }),
+    ];
+    return () => {
+      unlistens.forEach((u) => u.then((f) => f()));
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<RemoveProgress>('axec://remove-progress', (event) => setRemoveProgress(event.payload));
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const loadUpdates = async (refresh: boolean) => {
+    setError(null);
+    try {
+      setBusy(true);
+      setUpdates(await invoke<AvailableUpdate[]>('find_updates', { refresh }));
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  useEffect(() => {
+    if (view === 'updates' && updates === null) loadUpdates(false);
+  }, [view]);
+
+  const handleUpdate = async (id: string) => {
+    setError(null);
+    try {
+      setUpdating(id);
+      const outcome = await invoke<UpdateOutcome>('update_app', { id });
+      setNotice(
+        outcome.status === 'updated'
+          ? `Updated ${outcome.entry.name}${outcome.to ? ` to ${outcome.to}` : ''}.`
+          : `${id} is already up to date.`,
+      );
+      setUpdates((prev) => prev && prev.filter((u) => u.id !== id));
+      await load();
+    } catch (e: any) {
+      // Download errors come as `{ kind, ... }`; most carry a message
+      setError(typeof e === 'object' && e !== null ? String(e.message ?? e.kind) : String(e));
+    } finally {
+      setUpdating(null);
+    }
+  };
+
   const filtered = useMemo(() => {
     const q = query.trim().toLowerCase();
     if (!q) return apps;
     return apps.filter((a) => a.name.toLowerCase().includes(q));
   }, [apps, query]);
 
+  // Apps sharing a name get their version shown so they can be told apart
+  const sharedNames = useMemo(() => {
+    const seen = new Set<string>();
+    const shared = new Set<string>();
+    for (const a of apps) (seen.has(a.name) ? shared : seen).add(a.name);
+    return shared;
+  }, [apps]);
+
   const handleAdd = async () => {
     setError(null);
     try {
-      const selection = await open({
-        multiple: false,
-        directory: false,
-        filters: [
-          { name: 'AppImage', extensions: ['appimage', 'AppImage'] },
-          { name: 'All files', extensions: ['*'] },
-        ],
-      });
-      if (!selection || Array.isArray(selection)) return;
       setBusy(true);
-      await invoke<AppImageEntry>('add_appimage', { filePath: selection });
+      const entry = await invoke<AppImageEntry | null>('pick_and_add');
+      if (entry) await load();
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleImportFolder = async () => {
+    setError(null);
+    try {
+      const dir = await open({ directory: true, multiple: false });
+      if (!dir || Array.isArray(dir)) return;
+      setBusy(true);
+      const outcomes = await invoke<ImportOutcome[]>('import_directory', { dir, recursive: true });
+      const count = (status: ImportOutcome['status']) => outcomes.filter((o) => o.status === status).length;
+      setNotice(`Imported ${count('added')}, skipped ${count('skipped')} already present, ${count('failed')} failed.`);
+      const failures = outcomes.flatMap((o) => (o.status === 'failed' ? [`${o.path}: ${o.error}`] : []));
+      if (failures.length > 0) setError(failures.join('\n'));
       await load();
     } catch (e: any) {
       setError(String(e));
@@ -66,6 +325,55 @@ function App() {
   const handleLaunch = async (id: string) => {
     try {
       await invoke('launch_app', { id });
+    } catch (e: any) {
+      setError(describeLaunchError(e));
+    }
+  };
+
+  const handleRelaunch = async (id: string) => {
+    try {
+      await invoke('relaunch_app', { id });
+    } catch (e: any) {
+      setError(describeLaunchError(e));
+    }
+  };
+
+  const handleKill = async (id: string) => {
+    try {
+      await invoke('kill_app', { id });
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
+  const handleTogglePause = async (id: string) => {
+    const pause = !paused.has(id);
+    try {
+      await invoke(pause ? 'pause_app' : 'resume_app', { id });
+      setPaused((prev) => {
+        const next = new Set(prev);
+        if (pause) next.add(id);
+        else next.delete(id);
+        return next;
+      });
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
+  const handleTogglePin = async (app: AppImageEntry) => {
+    try {
+      await invoke('set_pinned', { id: app.id, pinned: !app.pinned });
+      await load();
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
+  const handleToggleIntegration = async (app: AppImageEntry) => {
+    try {
+      await invoke('set_integration', { id: app.id, integrate: !app.integrated });
+      await load();
     } catch (e: any) {
       setError(String(e));
     }
@@ -74,7 +382,22 @@ function App() {
   const handleRemove = async (id: string) => {
     try {
       setBusy(true);
-      await invoke('remove_app', { id });
+      await invoke('remove_app', { id, toTrash: true });
+      setCanUndo(true);
+      await load();
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setRemoveProgress(null);
+      setBusy(false);
+    }
+  };
+
+  const handleUndo = async () => {
+    try {
+      setBusy(true);
+      await invoke<AppImageEntry>('undo_last_remove');
+      setCanUndo(false);
       await load();
     } catch (e: any) {
       setError(String(e));
@@ -83,11 +406,103 @@ function App() {
     }
   };
 
+  const handleCompact = async () => {
+    setError(null);
+    try {
+      setBusy(true);
+      const report = await invoke<CompactReport>('compact_storage');
+      setNotice(
+        report.removed.length === 0
+          ? 'Nothing to clean up.'
+          : `Removed ${report.removed.length} leftover files, freeing ${formatBytes(report.bytes_reclaimed)}.`,
+      );
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleDoctor = async () => {
+    setError(null);
+    try {
+      const report = await invoke<{ checks: DoctorCheck[] }>('doctor');
+      setChecks(report.checks);
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
+  const handleTestLaunch = async (id: string) => {
+    setError(null);
+    try {
+      setBusy(true);
+      const result = await invoke<LaunchTestResult>('test_launch', { id });
+      if (result.ok) {
+        setNotice('The app started fine.');
+      } else if (result.error) {
+        setError(describeLaunchError(result.error));
+      } else {
+        setError(`The app exited${result.code !== null ? ` with code ${result.code}` : ''}${result.output ? `: ${result.output}` : ''}`);
+      }
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleCopyPath = async (id: string) => {
+    setError(null);
+    try {
+      const path = await invoke<string>('copy_path_to_clipboard', { id });
+      setNotice(`Copied ${path}`);
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
+  const handleExtract = async (id: string) => {
+    setError(null);
+    try {
+      const dest = await open({ directory: true, multiple: false });
+      if (!dest || Array.isArray(dest)) return;
+      setBusy(true);
+      const root = await invoke<string>('extract_to', { id, dest });
+      setNotice(`Extracted to ${root}`);
+    } catch (e: any) {
+      setError(String(e));
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleVerifyIntegration = async (id: string) => {
+    setError(null);
+    try {
+      const report = await invoke<{ checks: DoctorCheck[] }>('verify_desktop_integration', { id });
+      setChecks(report.checks);
+    } catch (e: any) {
+      setError(String(e));
+    }
+  };
+
   return (
     <div className="min-h-screen bg-slate-950 text-slate-100">
       <header className="border-b border-slate-800 bg-slate-900/60 backdrop-blur sticky top-0 z-10">
         <div className="max-w-4xl mx-auto px-4 py-3 flex items-center gap-3">
           <div className="text-xl font-semibold">Axec</div>
+          <nav className="flex items-center gap-1">
+            {(['library', 'updates'] as const).map((tab) => (
+              <button
+                key={tab}
+                className={`px-2.5 py-1.5 rounded-md text-sm ${view === tab ? 'bg-slate-800 text-slate-100' : 'text-slate-400 hover:text-slate-200'}`}
+                onClick={() => setView(tab)}
+              >
+                {tab === 'library' ? 'Library' : `Updates${updates && updates.length > 0 ? ` (${updates.length})` : ''}`}
+              </button>
+            ))}
+          </nav>
           <div className="ml-auto flex items-center gap-2">
             <input
               className="px-3 py-2 rounded-md bg-slate-800 border border-slate-700 placeholder-slate-400 focus:outline-none focus:ring-2 focus:ring-indigo-500"
@@ -102,21 +517,131 @@ function App() {
             >
               Add AppImage
             </button>
+            <button
+              className="px-3 py-2 rounded-md bg-slate-800 border border-slate-700 hover:bg-slate-700 disabled:opacity-50"
+              onClick={handleImportFolder}
+              disabled={busy}
+            >
+              Import folder
+            </button>
+            <button
+              className="px-3 py-2 rounded-md bg-slate-800 border border-slate-700 hover:bg-slate-700 disabled:opacity-50"
+              onClick={handleCompact}
+              disabled={busy}
+              title="Remove leftover icons, thumbnails and temporary files"
+            >
+              Clean up
+            </button>
+            <button
+              className="px-3 py-2 rounded-md bg-slate-800 border border-slate-700 hover:bg-slate-700 disabled:opacity-50"
+              onClick={handleDoctor}
+              disabled={busy}
+              title="Check the system for common integration problems"
+            >
+              Diagnose
+            </button>
           </div>
         </div>
       </header>
 
       <main className="max-w-4xl mx-auto p-4">
         {error && (
-          <div className="mb-3 rounded-md border border-red-700 bg-red-900/30 text-red-200 px-3 py-2">
+          <div className="mb-3 rounded-md border border-red-700 bg-red-900/30 text-red-200 px-3 py-2 whitespace-pre-line">
             {error}
           </div>
         )}
+        {warnings.length > 0 && (
+          <div
+            className="mb-3 rounded-md border border-amber-700 bg-amber-900/30 text-amber-200 px-3 py-2"
+            title={warnings.map((w) => `${w.path}: ${w.message}`).join('\n')}
+          >
+            {warnings.length === 1 ? '1 app could not be read' : `${warnings.length} apps could not be read`}
+          </div>
+        )}
+        {notice && (
+          <div className="mb-3 rounded-md border border-slate-700 bg-slate-900/60 px-3 py-2 flex items-center">
+            <span className="text-slate-300">{notice}</span>
+            <button className="ml-auto text-slate-400 hover:text-slate-200" onClick={() => setNotice(null)}>
+              Dismiss
+            </button>
+          </div>
+        )}
+        {checks && (
+          <div className="mb-3 rounded-md border border-slate-700 bg-slate-900/60 px-3 py-2">
+            <div className="flex items-center mb-1">
+              <span className="font-medium">Diagnostics</span>
+              <button className="ml-auto text-slate-400 hover:text-slate-200" onClick={() => setChecks(null)}>
+                Dismiss
+              </button>
+            </div>
+            <ul className="text-sm space-y-1">
+              {checks.map((c) => (
+                <li key={c.name}>
+                  <span
+                    className={
+                      c.status === 'pass' ? 'text-emerald-300' : c.status === 'warn' ? 'text-amber-300' : 'text-red-300'
+                    }
+                  >
+                    {c.status === 'pass' ? '✓' : c.status === 'warn' ? '!' : '✗'} {c.name}
+                  </span>
+                  <span className="text-slate-400"> — {c.detail}</span>
+                  {c.fix && <div className="text-slate-500 ml-4">{c.fix}</div>}
+                </li>
+              ))}
+            </ul>
+          </div>
+        )}
+        {canUndo && (
+          <div className="mb-3 rounded-md border border-slate-700 bg-slate-900/60 px-3 py-2 flex items-center">
+            <span className="text-slate-300">App moved to trash.</span>
+            <button className="ml-auto text-indigo-300 hover:text-indigo-200" onClick={handleUndo} disabled={busy}>
+              Undo
+            </button>
+          </div>
+        )}
         {busy && (
-          <div className="mb-3 text-slate-400">Working…</div>
+          <div className="mb-3 text-slate-400">
+            {removeProgress && removeProgress.total > 0
+              ? `Moving ${removeProgress.file} to trash… ${Math.floor((removeProgress.copied / removeProgress.total) * 100)}%`
+              : 'Working…'}
+          </div>
         )}
 
-        {filtered.length === 0 ? (
+        {view === 'updates' ? (
+          <div>
+            <div className="mb-3 flex items-center">
+              <span className="text-slate-400 text-sm">
+                {updates === null ? 'Checking for updates…' : updates.length === 0 ? 'Everything is up to date.' : ''}
+              </span>
+              <button
+                className="ml-auto px-3 py-2 rounded-md bg-slate-800 border border-slate-700 hover:bg-slate-700 disabled:opacity-50"
+                onClick={() => loadUpdates(true)}
+                disabled={busy}
+              >
+                Check again
+              </button>
+            </div>
+            <ul className="space-y-2">
+              {(updates ?? []).map((u) => (
+                <li key={u.id} className="rounded-lg border border-slate-800 bg-slate-900/40 p-3 flex items-center gap-3">
+                  <div className="flex-1 min-w-0">
+                    <div className="font-medium truncate">{apps.find((a) => a.id === u.id)?.name ?? u.id}</div>
+                    <div className="text-xs text-slate-400 truncate" title={u.download_url}>
+                      {u.current_version ?? 'unknown'} → {u.latest_version}
+                    </div>
+                  </div>
+                  <button
+                    className="px-2.5 py-1.5 rounded-md bg-indigo-600 hover:bg-indigo-500 text-sm disabled:opacity-50"
+                    onClick={() => handleUpdate(u.id)}
+                    disabled={updating !== null}
+                  >
+                    {updating === u.id ? 'Updating…' : 'Update'}
+                  </button>
+                </li>
+              ))}
+            </ul>
+          </div>
+        ) : filtered.length === 0 ? (
           <div className="mt-16 text-center text-slate-400">
             <div className="text-2xl mb-2">No AppImages yet</div>
             <div>Click “Add AppImage” to import one.</div>
@@ -128,16 +653,18 @@ function App() {
                 key={app.id}
                 className="rounded-lg border border-slate-800 bg-slate-900/40 p-3 flex items-center gap-3"
               >
-                <img
-                  src={app.icon_path ? convertFileSrc(app.icon_path) : '/src/assets/tauri.svg'}
-                  alt="icon"
-                  className="w-10 h-10 rounded"
-                  onError={(e) => {
-                    (e.target as HTMLImageElement).src = '/src/assets/tauri.svg';
-                  }}
-                />
+                <AppIcon app={app} />
                 <div className="flex-1 min-w-0">
-                  <div className="font-medium truncate">{app.name}</div>
+                  <div className="font-medium truncate">
+                    {app.name}
+                    {app.version && sharedNames.has(app.name) && ` ${app.version}`}
+                    {running.has(app.id) && (
+                      <span className="ml-2 text-xs px-1.5 py-0.5 rounded bg-emerald-900/50 text-emerald-300">
+                        {paused.has(app.id) ? 'Paused' : 'Running'}
+                      </span>
+                    )}
+                    {running.has(app.id) && <UsageBadge id={app.id} />}
+                  </div>
                   <div className="text-xs text-slate-400 truncate" title={app.path}>
                     {app.path}
                   </div>
@@ -149,7 +676,22 @@ function App() {
                   >
                     Launch
                   </button>
-                  <Menu onRemove={() => handleRemove(app.id)} />
+                  <Menu
+                    pinned={app.pinned}
+                    integrated={app.integrated}
+                    running={running.has(app.id)}
+                    paused={paused.has(app.id)}
+                    onTogglePause={() => handleTogglePause(app.id)}
+                    onRelaunch={() => handleRelaunch(app.id)}
+                    onKill={() => handleKill(app.id)}
+                    onTogglePin={() => handleTogglePin(app)}
+                    onToggleIntegration={() => handleToggleIntegration(app)}
+                    onVerifyIntegration={() => handleVerifyIntegration(app.id)}
+                    onCopyPath={() => handleCopyPath(app.id)}
+                    onExtract={() => handleExtract(app.id)}
+                    onTestLaunch={() => handleTestLaunch(app.id)}
+                    onRemove={() => handleRemove(app.id)}
+                  />
                 </div>
               </li>
             ))}
@@ -160,7 +702,92 @@ function App() {
   );
 }
 
-function Menu({ onRemove }: { onRemove: () => void }) {
+type ResourceUsage = {
+  running: boolean;
+  processes: number;
+  rss_bytes: number;
+  cpu_time_ms: number;
+};
+
+function UsageBadge({ id }: { id: string }) {
+  const [usage, setUsage] = useState<ResourceUsage | null>(null);
+
+  useEffect(() => {
+    const poll = () =>
+      invoke<ResourceUsage>('app_usage', { id })
+        .then(setUsage)
+        .catch(() => setUsage(null));
+    poll();
+    const timer = setInterval(poll, 2000);
+    return () => clearInterval(timer);
+  }, [id]);
+
+  if (!usage?.running) return null;
+  return (
+    <span className="ml-2 text-xs text-slate-400" title={`${usage.processes} processes, ${Math.round(usage.cpu_time_ms / 1000)}s CPU`}>
+      {formatBytes(usage.rss_bytes)}
+    </span>
+  );
+}
+
+function AppIcon({ app }: { app: AppImageEntry }) {
+  const [src, setSrc] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!app.icon_path) return;
+    // 80px covers the 40px tile on HiDPI screens
+    invoke<string>('thumbnail', { id: app.id, size: 80 })
+      .then(setSrc)
+      .catch(() =>
+        invoke<{ mime: string; data_uri: string }>('get_icon_data', { id: app.id })
+          .then((icon) => setSrc(icon.data_uri))
+          .catch(() => setSrc(convertFileSrc(app.icon_path!))),
+      );
+  }, [app.id, app.icon_path]);
+
+  return (
+    <img
+      src={src ?? '/src/assets/tauri.svg'}
+      alt="icon"
+      className="w-10 h-10 rounded"
+      onError={(e) => {
+        (e.target as HTMLImageElement).src = '/src/assets/tauri.svg';
+      }}
+    />
+  );
+}
+
+function Menu({
+  pinned,
+  integrated,
+  running,
+  paused,
+  onTogglePause,
+  onRelaunch,
+  onKill,
+  onTogglePin,
+  onToggleIntegration,
+  onVerifyIntegration,
+  onCopyPath,
+  onExtract,
+  onTestLaunch,
+  onRemove,
+}: {
+  pinned: boolean;
+  integrated: boolean;
+  running: boolean;
+  paused: boolean;
+  onTogglePause: () => void;
+  onRelaunch: () => void;
+  onKill: () => void;
+  onTogglePin: () => void;
+  onToggleIntegration: () => void;
+  onVerifyIntegration: () => void;
+  onCopyPath: () => void;
+  onExtract: () => void;
+  onTestLaunch: () => void;
+  onRemove: () => void;
+}) {
   const [open, setOpen] = useState(false);
   return (
     <div className="relative">
@@ -172,6 +799,91 @@ function Menu({ onRemove }: { onRemove: () => void }) {
       </button>
       {open && (
         <div className="absolute right-0 mt-1 w-40 rounded-md border border-slate-700 bg-slate-900 shadow-lg z-20">
+          {running && (
+            <>
+              <button
+                className="w-full text-left px-3 py-2 hover:bg-slate-800"
+                onClick={() => {
+                  setOpen(false);
+                  onTogglePause();
+                }}
+              >
+                {paused ? 'Resume' : 'Pause'}
+              </button>
+              <button
+                className="w-full text-left px-3 py-2 hover:bg-slate-800"
+                onClick={() => {
+                  setOpen(false);
+                  onRelaunch();
+                }}
+              >
+                Relaunch
+              </button>
+              <button
+                className="w-full text-left px-3 py-2 hover:bg-slate-800"
+                onClick={() => {
+                  setOpen(false);
+                  onKill();
+                }}
+              >
+                Stop
+              </button>
+            </>
+          )}
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onTogglePin();
+            }}
+          >
+            {pinned ? 'Unpin from tray' : 'Pin to tray'}
+          </button>
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onToggleIntegration();
+            }}
+          >
+            {integrated ? 'Remove from menu' : 'Add to menu'}
+          </button>
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onVerifyIntegration();
+            }}
+          >
+            Check menu entry
+          </button>
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onCopyPath();
+            }}
+          >
+            Copy path
+          </button>
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onExtract();
+            }}
+          >
+            Extract contents…
+          </button>
+          <button
+            className="w-full text-left px-3 py-2 hover:bg-slate-800"
+            onClick={() => {
+              setOpen(false);
+              onTestLaunch();
+            }}
+          >
+            Test launch
+          </button>
           <button
             className="w-full text-left px-3 py-2 text-red-300 hover:bg-red-900/30"
             onClick={() => {